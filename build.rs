@@ -0,0 +1,34 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 执行命令并返回去除首尾空白的标准输出，失败时返回 None
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    Some(text.trim().to_string())
+}
+
+// 在编译时写入 git 提交、构建时间与 rustc 版本，供 /version 接口使用
+fn main() {
+    if let Some(sha) = command_output("git", &["rev-parse", "--short", "HEAD"]) {
+        println!("cargo:rustc-env=WEBDISK_GIT_SHA={}", sha);
+    }
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    if let Some(version) = command_output(&rustc, &["--version"]) {
+        println!("cargo:rustc-env=WEBDISK_RUSTC_VERSION={}", version);
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=WEBDISK_BUILD_TIMESTAMP={}", timestamp);
+
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-changed=build.rs");
+}
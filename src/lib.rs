@@ -0,0 +1,7252 @@
+use actix_files::NamedFile;
+use actix_multipart::Multipart;
+use actix_web::{get, guard, post, App, HttpResponse, Result, web, Error, HttpRequest};
+use actix_web::middleware::{from_fn, Condition, Next};
+use actix_web::body::{BodySize, BoxBody, EitherBody, MessageBody};
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_cors::Cors;
+use actix_web::http::header::{self, DispositionType};
+use actix_web::http::StatusCode;
+use actix_web::mime::{self, Mime};
+use serde::{Serialize, Deserialize};
+use std::{env, fs};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::net::{SocketAddr, ToSocketAddrs};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::time::Duration;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
+use chrono::{DateTime, Local, Utc};
+use encoding_rs::{Encoding, UTF_8};
+use chrono_tz::Tz;
+use std::process::Command;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use dav_server::DavHandler;
+use dav_server::davpath::DavPath;
+use dav_server::fs::{DavDirEntry, DavFile, DavFileSystem, DavMetaData, FsError, FsFuture, FsStream, OpenOptions, ReadDirMeta};
+use dav_server::localfs::LocalFs;
+use dav_server::memls::MemLs;
+use futures_util::StreamExt;
+use notify::{RecursiveMode, Watcher};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use rand::{thread_rng, Rng};
+use rand::distributions::Slice;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use zip::write::{SimpleFileOptions, StreamWriter};
+use zip::ZipWriter;
+
+// 添加自定义序列化模块
+mod ordered_map {
+    use serde::{self, Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S, K, V>(value: &BTreeMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        K: serde::Serialize + Ord,
+        V: serde::Serialize,
+    {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(value.len()))?;
+        for (k, v) in value {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+
+    pub fn deserialize<'de, D, K, V>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error>
+    where
+        D: Deserializer<'de>,
+        K: serde::Deserialize<'de> + Ord,
+        V: serde::Deserialize<'de>,
+    {
+        BTreeMap::deserialize(deserializer)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Config {
+    pub ip: String,
+    pub ipv6: String,
+    pub port: u16,
+    #[serde(default)]
+    pub bind_mode: BindMode,  // 监听方式：dual 同时监听 IPv4 与 IPv6，ipv4、ipv6 只监听其一
+    pub cwd: String,
+    pub webdav: WebDAVConfig,  // 添加 WebDAV 配置
+    #[serde(default)]
+    pub server: ServerConfig,  // 连接超时配置
+    #[serde(default)]
+    pub limits: LimitsConfig,  // 并发连接数限制
+    #[serde(default)]
+    pub proxy: ProxyConfig,  // 前端反向代理配置
+    #[serde(default)]
+    pub compression: CompressionConfig,  // 响应压缩配置
+    #[serde(default)]
+    pub branding: BrandingConfig,  // 站点品牌配置
+    #[serde(default = "default_lang")]
+    pub lang: String,  // 界面语言：zh、en 或 auto（根据 Accept-Language 选择）
+    #[serde(default = "default_true")]
+    pub group_dirs_first: bool,  // 目录列表中目录是否排在文件之前
+    #[serde(default)]
+    pub sort: SortKey,  // 默认排序字段
+    #[serde(default)]
+    pub order: SortOrder,  // 默认排序方向
+    #[serde(default)]
+    pub view: ViewMode,  // 默认显示方式
+    #[serde(default = "default_true")]
+    pub show_hidden: bool,  // 是否显示以 . 开头的隐藏文件
+    #[serde(default)]
+    pub show_symlink_targets: bool,  // 是否显示软链接指向的路径（可能暴露服务器上的目录结构），默认不显示
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,  // 跨域配置，未设置时不发送任何 CORS 响应头
+    #[serde(default)]
+    pub timezone: Option<String>,  // 显示修改时间使用的 IANA 时区，如 Asia/Shanghai，未设置时使用系统时区
+    #[serde(default = "default_stream_threshold")]
+    pub stream_threshold: usize,  // 条目数超过该值时以流式方式输出目录页面
+    #[serde(default = "default_max_listing_entries")]
+    pub max_listing_entries: usize,  // 目录页面最多显示的条目数，超出部分截断，0 表示不限制
+    #[serde(default)]
+    pub upload: UploadConfig,  // 上传配置
+    #[serde(default)]
+    pub readme: ReadmeConfig,  // 目录说明文件配置
+    #[serde(default)]
+    pub sitemap: SitemapConfig,  // 全站文件索引配置
+    #[serde(default)]
+    pub hide: HideConfig,  // 不在目录列表中显示的临时文件
+    #[serde(default)]
+    pub preview: PreviewConfig,  // 列表中内联预览的配置
+    #[serde(default)]
+    pub log: LogConfig,  // 日志配置
+    #[serde(default)]
+    pub web_auth: WebAuthConfig,  // 网页与文件下载的登录要求
+    #[serde(default)]
+    pub pwa: PwaConfig,  // 渐进式网页应用配置
+    #[serde(default)]
+    pub cache: CacheConfig,  // 浏览器缓存配置
+    #[serde(default = "default_columns")]
+    pub columns: Vec<Column>,  // 目录列表显示的列及顺序，文件名始终显示在最前
+    #[serde(default)]
+    pub debug: bool,  // 出错时在页面中显示详细的错误信息，仅用于排查问题
+    #[serde(default)]
+    pub image_transcode: bool,  // 是否按浏览器支持情况将 JPEG/PNG 转码为 WebP 或 AVIF（占用较多 CPU）
+    #[serde(default)]
+    pub trust_svg: bool,  // 是否允许直接在浏览器中打开 SVG；SVG 可以内嵌脚本，仅在文件来源可信时开启
+    #[serde(default)]
+    pub sniff_extensionless: bool,  // 目录列表中是否读取无扩展名文件的文件头，按内容选择图标与类型
+    #[serde(default)]
+    pub preview_charset: Option<String>,  // 文本预览无法可靠识别编码时使用的字符集，如 gbk；未设置时采用猜测结果
+    #[serde(default = "default_true")]
+    pub normalize_paths: bool,  // 是否把非规范路径（含 //、.、..）及不带 / 的目录请求 301 重定向到规范地址
+    #[serde(default)]
+    pub expose_build_info: bool,  // /version 是否返回 git 提交、构建时间等构建信息
+    #[serde(default)]
+    pub live_updates: bool,  // 是否通过 WebSocket 推送目录变更，页面自动刷新列表
+    #[serde(default)]
+    pub allow_web_delete: bool,  // 是否允许在目录页面删除文件与目录（POST /delete/<路径>），需以有 w 权限的 WebDAV 用户登录
+    #[serde(default)]
+    pub allow_web_mkdir: bool,  // 是否允许在目录页面新建文件夹（POST /mkdir/<目录>），账号要求同上
+    #[serde(default)]
+    pub allow_web_rename: bool,  // 是否允许在目录页面重命名或移动文件与目录（POST /rename），账号要求同上
+    #[serde(default = "default_max_recursion_depth")]
+    pub max_recursion_depth: usize,  // 打包下载等递归操作进入子目录的最大层数
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct UploadConfig {
+    pub on_conflict: ConflictPolicy,  // 上传目标已存在时的处理方式
+    pub min_free_bytes: u64,          // 写入后磁盘至少需保留的空闲字节数
+    pub dedup: bool,                  // 按内容哈希去重存储，相同内容只保存一份
+    pub progress_events: bool,        // 是否通过 /events（SSE）推送 WebDAV 上传进度
+    pub web_form: bool,               // 是否在目录页面提供上传表单（POST /upload/<目录>），需以有 w 权限的 WebDAV 用户登录
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    Error,      // 拒绝上传
+    #[default]
+    Overwrite,  // 覆盖已有文件
+    Rename,     // 自动追加 " (1)"、" (2)" 等后缀
+}
+
+fn default_max_recursion_depth() -> usize {
+    20
+}
+
+fn default_stream_threshold() -> usize {
+    1000
+}
+
+fn default_max_listing_entries() -> usize {
+    5000
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BindMode {
+    #[default]
+    Dual,
+    Ipv4,
+    Ipv6,
+}
+
+impl BindMode {
+    // 与配置文件中的写法一致
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BindMode::Dual => "dual",
+            BindMode::Ipv4 => "ipv4",
+            BindMode::Ipv6 => "ipv6",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BindMode::Dual => "双栈（IPv4 + IPv6）",
+            BindMode::Ipv4 => "仅 IPv4",
+            BindMode::Ipv6 => "仅 IPv6",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewMode {
+    #[default]
+    List,
+    Grid,
+}
+
+// 目录列表中文件名之后显示的列
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Name,
+    Size,
+    Modified,
+    Permissions,
+    Downloads,
+    Type,
+}
+
+fn default_columns() -> Vec<Column> {
+    vec![Column::Name, Column::Size, Column::Modified]
+}
+
+// 目录列表的最终显示设置（全局配置与目录覆盖设置合并后的结果）
+#[derive(Debug, Serialize, Clone)]
+struct ListingSettings {
+    sort: SortKey,
+    order: SortOrder,
+    view: ViewMode,
+    group_dirs_first: bool,
+    show_hidden: bool,
+    #[serde(skip)]
+    hide: HideConfig,
+    #[serde(skip)]
+    preview_limits: PreviewLimits,
+    #[serde(skip)]
+    show_symlink_targets: bool,
+    #[serde(skip)]
+    timezone: Option<Tz>,
+    columns: Vec<Column>,
+    #[serde(skip)]
+    max_entries: usize,
+    #[serde(skip)]
+    sniff_extensionless: bool,
+    #[serde(skip)]
+    search: Option<String>,  // 小写的搜索词，在截断前过滤，搜索结果不受条目数上限影响
+    web_delete: bool,  // 是否在条目旁显示删除按钮
+    web_rename: bool,  // 是否在条目旁显示重命名按钮
+}
+
+impl ListingSettings {
+    fn has_column(&self, column: Column) -> bool {
+        self.columns.contains(&column)
+    }
+}
+
+// 目录下 .webdisk.yaml 中的覆盖设置，未填写的项沿用全局配置
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+struct DirOverrides {
+    sort: Option<SortKey>,
+    order: Option<SortOrder>,
+    view: Option<ViewMode>,
+    group_dirs_first: Option<bool>,
+    show_hidden: Option<bool>,
+    write_only: bool,  // 投递箱：只允许上传，不能浏览或下载其中（含子目录）的内容
+}
+
+// 目录配置文件名
+const DIR_CONFIG_FILE: &str = ".webdisk.yaml";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,  // 允许的来源，"*" 表示任意来源
+    #[serde(default)]
+    pub allow_credentials: bool,
+    #[serde(default = "default_cors_methods")]
+    pub allowed_methods: Vec<String>,
+}
+
+fn default_cors_methods() -> Vec<String> {
+    vec!["GET".to_string(), "HEAD".to_string(), "OPTIONS".to_string()]
+}
+
+impl CorsConfig {
+    // 构建 CORS 中间件，预检 OPTIONS 请求由中间件直接应答
+    fn build(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods(self.allowed_methods.iter().map(|m| m.as_str()))
+            .allow_any_header()
+            .expose_headers([header::CONTENT_DISPOSITION, header::CONTENT_LENGTH, header::CONTENT_RANGE])
+            .max_age(3600);
+
+        if self.allowed_origins.iter().any(|o| o == "*") {
+            cors = cors.allow_any_origin();
+        } else {
+            for origin in &self.allowed_origins {
+                cors = cors.allowed_origin(origin);
+            }
+        }
+
+        if self.allow_credentials {
+            cors = cors.supports_credentials();
+        }
+        cors
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_lang() -> String {
+    "zh".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BrandingConfig {
+    pub site_title: String,
+    pub footer_text: String,
+    pub footer_url: String,
+    pub logo_url: Option<String>,
+}
+
+impl Default for BrandingConfig {
+    fn default() -> Self {
+        BrandingConfig {
+            site_title: "文件索引".to_string(),
+            footer_text: "©2025 云溪起源".to_string(),
+            footer_url: "https://yxyos.cn".to_string(),
+            logo_url: None,
+        }
+    }
+}
+
+// 浏览器缓存配置
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub max_age_secs: u64,       // 文件响应的缓存时间，0 表示不发送 Cache-Control
+    pub immutable_hashed: bool,  // 文件名含内容哈希（如 app.3f9a2c1b.js）时附加 immutable
+}
+
+impl CacheConfig {
+    // 文件响应使用的 Cache-Control，未配置缓存时返回 None；
+    // 需要登录或携带了认证信息的响应使用 private，避免被共享代理缓存后提供给其他人
+    fn file_policy(&self, name: &str, private: bool) -> Option<String> {
+        if self.max_age_secs == 0 {
+            return None;
+        }
+        let scope = if private { "private" } else { "public" };
+        let mut policy = format!("{}, max-age={}", scope, self.max_age_secs);
+        if self.immutable_hashed && is_hashed_name(name) {
+            policy.push_str(", immutable");
+        }
+        Some(policy)
+    }
+}
+
+// 目录列表随时可能变化，每次都向服务器重新获取
+const LISTING_CACHE_POLICY: &str = "no-cache";
+// 排序、显示方式保存在 Cookie 中，界面语言可能取自 Accept-Language，缓存需按两者区分
+const LISTING_VARY: &str = "Cookie, Accept-Language";
+
+// 文件名中是否含有构建工具生成的内容哈希：至少 8 位、同时含字母与数字的十六进制片段
+fn is_hashed_name(name: &str) -> bool {
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    stem.split(['.', '-', '_']).any(|part| {
+        part.len() >= 8
+            && part.chars().all(|c| c.is_ascii_hexdigit())
+            && part.chars().any(|c| c.is_ascii_digit())
+            && part.chars().any(|c| c.is_ascii_alphabetic())
+    })
+}
+
+// 目录说明文件配置，目录中存在说明文件时渲染在列表上方
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ReadmeConfig {
+    pub enabled: bool,
+    pub files: Vec<String>,  // 按顺序查找的文件名，使用第一个存在的文件
+}
+
+impl Default for ReadmeConfig {
+    fn default() -> Self {
+        ReadmeConfig {
+            enabled: false,
+            files: vec!["README.md".to_string(), "README.txt".to_string()],
+        }
+    }
+}
+
+// 全站文件索引配置，开启后提供 /sitemap.xml 与 /index.json 供外部索引程序使用
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SitemapConfig {
+    pub enabled: bool,
+    pub exclude: Vec<String>,  // 不编入索引的子目录，相对于 cwd，如 private 或 backup/old
+}
+
+impl SitemapConfig {
+    // 相对路径是否位于排除的子目录之中
+    fn excludes(&self, rel: &str) -> bool {
+        self.exclude.iter()
+            .map(|ex| ex.trim_matches('/'))
+            .filter(|ex| !ex.is_empty())
+            .any(|ex| rel == ex || rel.starts_with(&format!("{}/", ex)))
+    }
+}
+
+// 不在目录列表中显示的文件（与隐藏文件相同，直接访问不受影响），用于隐藏编辑器、同步工具产生的临时文件
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct HideConfig {
+    pub patterns: Vec<String>,  // 文件名通配符，* 匹配任意个字符，? 匹配单个字符，不区分大小写
+    pub empty_files: bool,      // 是否隐藏大小为 0 的文件
+}
+
+impl Default for HideConfig {
+    fn default() -> Self {
+        HideConfig {
+            patterns: ["*.tmp", "*.part", "*.crdownload", "~$*", ".~lock.*#", "*.swp", "*~", "Thumbs.db", "desktop.ini", ".webdisk-upload-*"]
+                .map(String::from)
+                .to_vec(),
+            empty_files: false,
+        }
+    }
+}
+
+impl HideConfig {
+    fn hides_name(&self, name: &str) -> bool {
+        self.patterns.iter().any(|pattern| wildcard_match(pattern, name))
+    }
+
+    fn hides_metadata(&self, md: &StorageMetadata) -> bool {
+        self.empty_files && !md.is_dir && md.len == 0
+    }
+}
+
+// 文件名通配符匹配：* 匹配任意个字符，? 匹配单个字符，不区分大小写
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // 最近一个 * 的位置及其当前匹配到的名称位置，失配时回退到这里让 * 多吞一个字符
+    let mut backtrack = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// 列表中内联预览的配置
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PreviewConfig {
+    pub max_inline_bytes: PreviewLimits,  // 超过该大小的文件点击预览后先显示占位，再次点击才加载
+}
+
+// 各分类直接加载预览的大小上限（字节），0 表示不限制
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct PreviewLimits {
+    pub image: u64,
+    pub video: u64,
+    pub audio: u64,
+}
+
+impl Default for PreviewLimits {
+    fn default() -> Self {
+        PreviewLimits {
+            image: 5 * 1024 * 1024,
+            video: 0,
+            audio: 0,
+        }
+    }
+}
+
+impl PreviewLimits {
+    fn defers(&self, name: &str, size: u64) -> bool {
+        let limit = match file_category(name) {
+            Category::Image => self.image,
+            Category::Video => self.video,
+            Category::Audio => self.audio,
+            _ => 0,
+        };
+        limit > 0 && size > limit
+    }
+}
+
+// 网页访问的认证配置，账号与 WebDAV 共用 webdav.users
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct WebAuthConfig {
+    pub enabled: bool,  // 是否要求浏览网页、下载文件等所有非 WebDAV 请求以 Basic 认证登录，读取需要 r 权限
+}
+
+// 日志配置
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LogConfig {
+    pub format: LogFormat,  // 日志格式
+    pub access: bool,       // 是否为每个请求记录一条访问日志
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,  // 便于阅读的单行文本，输出到标准错误
+    Json,  // 每行一个 JSON 对象，输出到标准输出，便于 Loki、ELK 等采集
+}
+
+// 初始化全局日志，只在进程启动时调用一次，重复调用时保留第一次的设置
+pub fn init_logging(format: LogFormat) {
+    let builder = tracing_subscriber::fmt().with_target(false);
+    let _ = match format {
+        LogFormat::Text => builder
+            .with_ansi(std::io::IsTerminal::is_terminal(&std::io::stderr()))
+            .with_writer(std::io::stderr)
+            .try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).with_current_span(false).try_init(),
+    };
+}
+
+// 启动时先于加载配置确定日志格式，只读取 log.format，配置文件不存在或格式错误时使用文本格式
+pub fn configured_log_format() -> LogFormat {
+    let path = env::var("YUNXI_CONFIG").unwrap_or_else(|_| "data/config.yaml".to_string());
+    fs::read_to_string(path).ok()
+        .and_then(|text| serde_yaml::from_str::<serde_yaml::Value>(&text).ok())
+        .and_then(|value| serde_yaml::from_value(value["log"]["format"].clone()).ok())
+        .unwrap_or_default()
+}
+
+// 渐进式网页应用（PWA）配置，开启后可在手机浏览器中「添加到主屏幕」
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PwaConfig {
+    pub enabled: bool,
+    pub name: Option<String>,        // 应用名称，未设置时使用 branding.site_title
+    pub short_name: Option<String>,  // 主屏幕图标下显示的名称，未设置时与 name 相同
+    pub theme_color: String,         // 浏览器地址栏与任务切换界面的颜色
+    pub background_color: String,    // 启动画面的背景色
+    pub icons: Vec<PwaIcon>,         // 应用图标，未设置时使用网站图标
+}
+
+impl Default for PwaConfig {
+    fn default() -> Self {
+        PwaConfig {
+            enabled: false,
+            name: None,
+            short_name: None,
+            theme_color: "#2196F3".to_string(),
+            background_color: "#f8f9fa".to_string(),
+            icons: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PwaIcon {
+    pub path: String,   // 图标文件，相对于程序运行目录，如 data/icon-192.png
+    pub sizes: String,  // 图标尺寸，如 192x192
+}
+
+impl PwaConfig {
+    // 页面中 apple-touch-icon 使用的图标地址
+    fn touch_icon(&self) -> &'static str {
+        if self.icons.is_empty() { "/favicon.ico" } else { "/pwa-icon/0" }
+    }
+}
+
+// 连接超时配置，防止慢速客户端长期占用工作线程
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub client_timeout_secs: u64,     // 接收完整请求头的最长时间，超时返回 408，0 表示不限制
+    pub client_shutdown_secs: u64,    // 响应发送完毕后等待客户端关闭连接的时间，0 表示不限制
+    pub body_idle_timeout_secs: u64,  // WebDAV 上传时允许连续多久收不到数据，0 表示不限制
+    pub bind_attempts: u32,           // 端口被占用时最多尝试绑定的次数（含第一次），0 或 1 表示不重试
+    pub bind_retry_interval_ms: u64,  // 两次绑定尝试之间的间隔
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            client_timeout_secs: 10,
+            client_shutdown_secs: 5,
+            body_idle_timeout_secs: 60,
+            bind_attempts: 5,
+            bind_retry_interval_ms: 1000,
+        }
+    }
+}
+
+// 文件内容的发送方式：由程序自己发送，或交给前端代理发送
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AccelMode {
+    #[default]
+    None,
+    Nginx,   // 返回 X-Accel-Redirect，由 nginx 的 internal location 发送文件
+    Apache,  // 返回 X-Sendfile（mod_xsendfile），由 Apache 发送文件
+}
+
+// 前端反向代理配置
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ProxyConfig {
+    pub accel_mode: AccelMode,  // 文件下载交给代理发送，程序只负责路径检查与权限判断
+    pub accel_prefix: String,   // nginx 模式下内部 location 的路径前缀，对应 cwd
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        ProxyConfig {
+            accel_mode: AccelMode::None,
+            accel_prefix: "/internal/".to_string(),
+        }
+    }
+}
+
+// X-Sendfile 中的路径：mod_xsendfile 默认会解码百分号编码，% 本身与非 ASCII 字符需要编码
+const SENDFILE_PATH: &AsciiSet = &CONTROLS.add(b'%');
+
+impl ProxyConfig {
+    // 交给代理发送文件时使用的响应头，未开启时返回 None
+    fn accel_header(&self, cwd: &str, file: &Path) -> Option<(&'static str, String)> {
+        match self.accel_mode {
+            AccelMode::None => None,
+            AccelMode::Nginx => {
+                let rel = file.strip_prefix(cwd).ok()?.to_string_lossy().replace('\\', "/");
+                let prefix = self.accel_prefix.trim_end_matches('/');
+                Some(("X-Accel-Redirect", format!("{}/{}", prefix, encode_path(&rel))))
+            }
+            AccelMode::Apache => {
+                let absolute = std::path::absolute(file).ok()?;
+                Some(("X-Sendfile", utf8_percent_encode(&absolute.to_string_lossy(), SENDFILE_PATH).to_string()))
+            }
+        }
+    }
+}
+
+// 并发连接数限制，防止单个客户端开启大量并行下载占满服务器
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LimitsConfig {
+    pub max_connections: usize,         // 同时保持的连接总数上限，按工作线程平均分配，0 表示使用默认值
+    pub max_connections_per_ip: usize,  // 每个客户端 IP 同时保持的连接数上限，超出的连接返回 503，0 表示不限制
+}
+
+// 响应压缩配置，已压缩过的媒体与过小的响应不再压缩，避免白白消耗 CPU
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CompressionConfig {
+    pub enabled: bool,                 // 是否对响应进行 gzip 压缩
+    pub level: u32,                    // 压缩级别 1-9，越大压缩率越高、越耗 CPU
+    pub min_size: u64,                 // 长度已知且小于该字节数的响应不压缩
+    pub skip_extensions: Vec<String>,  // 不压缩的文件扩展名
+    pub skip_types: Vec<String>,       // 不压缩的 MIME 类型，支持 video/* 形式的通配
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        CompressionConfig {
+            enabled: true,
+            level: 1,
+            min_size: 1024,
+            skip_extensions: strings(&[
+                "zip", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar",
+                "jpg", "jpeg", "png", "gif", "webp", "avif",
+                "mp3", "aac", "flac", "ogg", "mp4", "mkv", "webm", "mov",
+            ]),
+            skip_types: strings(&[
+                "image/jpeg", "image/png", "image/gif", "image/webp", "image/avif",
+                "video/*", "audio/*", "application/zip", "application/gzip",
+            ]),
+        }
+    }
+}
+
+impl CompressionConfig {
+    // 根据请求路径的扩展名与响应的 MIME 类型判断是否跳过压缩
+    fn skips(&self, path: &str, content_type: Option<&str>) -> bool {
+        let name = path.rsplit('/').next().unwrap_or("");
+        if name.contains('.') {
+            let extension = file_extension(name);
+            if self.skip_extensions.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&extension)) {
+                return true;
+            }
+        }
+        let Some(mime) = content_type.and_then(|ct| ct.parse::<Mime>().ok()) else {
+            return false;
+        };
+        self.skip_types.iter().any(|pattern| match pattern.split_once('/') {
+            Some((kind, "*")) => mime.type_().as_str().eq_ignore_ascii_case(kind),
+            _ => mime.essence_str().eq_ignore_ascii_case(pattern),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebDAVConfig {
+    pub enabled: bool,
+    #[serde(with = "ordered_map")]  // 使用自定义序列化
+    pub users: BTreeMap<String, UserConfig>,
+    #[serde(default)]
+    pub locking: bool,  // 是否启用 LOCK/UNLOCK 锁管理
+    #[serde(default)]
+    pub read_only: bool,  // 全局只读，开启后拒绝所有写操作，不论用户权限如何
+    #[serde(default)]
+    pub allow_anonymous: bool,  // 允许未登录的客户端以只读方式访问（GET/HEAD/PROPFIND）
+    #[serde(default)]
+    pub pretty_index: bool,  // 浏览器访问 WebDAV 目录时使用与网页相同的列表页面
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,  // WebDAV 根目录，未设置时与网页共用 cwd
+    #[serde(default = "default_mount_path")]
+    pub mount_path: String,  // WebDAV 挂载路径，不能是根路径 "/"
+    #[serde(default)]
+    pub min_password_len: usize,  // 命令行设置密码时的最小长度，0 表示不限制
+    #[serde(default)]
+    pub require_mixed_classes: bool,  // 密码是否必须包含大写、小写、数字、符号中的至少三类
+    #[serde(default = "default_password_length")]
+    pub password_length: usize,  // 自动生成密码的长度
+    #[serde(default = "default_true")]
+    pub password_symbols: bool,  // 自动生成的密码是否包含符号
+}
+
+fn default_mount_path() -> String {
+    "/webdav".to_string()
+}
+
+// 内置路由，WebDAV 挂载路径不能与之重叠
+const RESERVED_ROUTES: &[&str] = &["/healthz", "/version", "/favicon.ico", "/zip", "/playlist", "/checksum", "/metrics", "/ws",
+                                   "/sitemap.xml", "/index.json", "/manifest.json", "/sw.js", "/pwa-icon", "/events"];
+
+impl WebDAVConfig {
+    // 去掉末尾斜杠的挂载前缀
+    pub fn mount_prefix(&self) -> &str {
+        self.mount_path.trim_end_matches('/')
+    }
+
+    fn validate_mount_path(&self) -> Result<(), String> {
+        if !self.mount_path.starts_with('/') {
+            return Err(format!("WebDAV 挂载路径 {} 必须以 / 开头", self.mount_path));
+        }
+        let prefix = self.mount_prefix();
+        // 挂载在根路径时所有 GET 都会交给 WebDAV 处理，网页界面将无法访问
+        if prefix.is_empty() {
+            return Err(format!("WebDAV 挂载路径 {} 不能是根路径", self.mount_path));
+        }
+        if let Some(route) = RESERVED_ROUTES.iter()
+            .find(|r| prefix == **r || prefix.starts_with(&format!("{}/", r)))
+        {
+            return Err(format!("WebDAV 挂载路径 {} 与内置路由 {} 冲突", self.mount_path, route));
+        }
+        Ok(())
+    }
+
+    // WebDAV 实际使用的根目录，用户独立目录在此基础上拼接
+    pub fn root_dir<'a>(&'a self, cwd: &'a str) -> &'a str {
+        self.root.as_deref().unwrap_or(cwd)
+    }
+
+    pub fn password_options(&self) -> PasswordOptions {
+        PasswordOptions {
+            len: self.password_length,
+            symbols: self.password_symbols,
+            readable: false,
+        }
+    }
+
+    // 按配置检查密码强度，未开启任何要求时总是通过
+    pub fn check_password(&self, password: &str) -> Result<(), String> {
+        let len = password.chars().count();
+        if len < self.min_password_len {
+            return Err(format!("密码长度为 {}，至少需要 {} 个字符", len, self.min_password_len));
+        }
+        if self.require_mixed_classes {
+            let classes = [
+                password.chars().any(|c| c.is_ascii_lowercase()),
+                password.chars().any(|c| c.is_ascii_uppercase()),
+                password.chars().any(|c| c.is_ascii_digit()),
+                password.chars().any(|c| !c.is_ascii_alphanumeric()),
+            ];
+            if classes.iter().filter(|&&has| has).count() < 3 {
+                return Err("密码需包含大写字母、小写字母、数字、符号中的至少三类".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserConfig {
+    pub password: String,  // Argon2 哈希（PHC 格式，以 $argon2 开头）；旧版本写入的明文密码仍可使用
+    pub permissions: String,  // "r" = read, "w" = write, "x" = execute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,  // 用户独立的根目录（相对于 WebDAV 根目录），未设置时使用共享的根目录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub landing_path: Option<String>,  // 浏览器打开 WebDAV 根地址时跳转到的目录（相对于用户的根目录）
+}
+
+// 以 Argon2id 哈希密码，结果为带随机盐的 PHC 字符串，可直接写入配置文件
+pub fn hash_password(password: &str) -> String {
+    use argon2::password_hash::{PasswordHasher, SaltString};
+    let salt = SaltString::generate(&mut rand::rngs::OsRng);
+    argon2::Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("默认参数下 Argon2 哈希不会失败")
+        .to_string()
+}
+
+// 验证通过的（哈希, 密码）组合的摘要。WebDAV 客户端每个请求都携带密码，
+// 缓存后只有首次验证需要计算 Argon2
+fn verified_passwords() -> &'static std::sync::Mutex<HashSet<[u8; 32]>> {
+    static CACHE: std::sync::OnceLock<std::sync::Mutex<HashSet<[u8; 32]>>> = std::sync::OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+fn argon2_matches(hash: &str, password: &str) -> bool {
+    use argon2::password_hash::{PasswordHash, PasswordVerifier};
+    PasswordHash::new(hash)
+        .is_ok_and(|hash| argon2::Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+// 用户名不存在时用于比对的哈希，使未知用户与密码错误花费同样的时间，无法据此判断用户名是否存在
+fn dummy_password_hash() -> &'static str {
+    static HASH: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    HASH.get_or_init(|| hash_password("webdisk-unknown-user"))
+}
+
+// 验证用户名与密码，通过时返回该用户。Argon2 计算放到线程池中执行，不阻塞处理其他请求的工作线程
+async fn authenticate<'a>(config: &'a Config, name: &str, password: &str) -> Option<&'a UserConfig> {
+    let user = config.webdav.users.get(name);
+    let (known, password) = (user.cloned(), password.to_string());
+    let verified = web::block(move || match known {
+        Some(user) => user.verify_password(&password),
+        None => {
+            argon2_matches(dummy_password_hash(), &password);
+            false
+        }
+    }).await;
+    user.filter(|_| verified.unwrap_or(false))
+}
+
+impl UserConfig {
+    // 密码是否已以哈希形式保存，false 表示旧版本遗留的明文密码
+    pub fn password_is_hashed(&self) -> bool {
+        self.password.starts_with("$argon2")
+    }
+
+    pub fn verify_password(&self, password: &str) -> bool {
+        const MAX_CACHED: usize = 1024;
+
+        if !self.password_is_hashed() {
+            return self.password == password;
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(self.password.as_bytes());
+        hasher.update([0]);
+        hasher.update(password.as_bytes());
+        let key: [u8; 32] = hasher.finalize().into();
+        if verified_passwords().lock().unwrap().contains(&key) {
+            return true;
+        }
+        let verified = argon2_matches(&self.password, password);
+        if verified {
+            let mut cache = verified_passwords().lock().unwrap();
+            if cache.len() >= MAX_CACHED {
+                cache.clear();
+            }
+            cache.insert(key);
+        }
+        verified
+    }
+
+    // 用户实际可访问的根目录
+    pub fn root_dir(&self, cwd: &str) -> PathBuf {
+        match &self.root {
+            Some(root) => PathBuf::from(cwd).join(root.trim_start_matches(['/', '\\'])),
+            None => PathBuf::from(cwd),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct FileEntry {
+    name: String,
+    display_name: String,
+    size_string: String,
+    size: u64,             // 原始字节数，目录为 0
+    modified_time: String,
+    modified_iso: String,  // RFC 3339 格式的修改时间
+    #[serde(skip)]
+    modified_secs: i64,    // 修改时间戳，用于排序
+    is_dir: bool,
+    icon: String,        // 添加图标字段
+    preview_url: String, // 添加预览URL字段
+    preview_deferred: bool,  // 文件较大，点击预览后先显示占位，确认后才加载
+    url: String,         // 从根路径开始逐段编码的链接，用于复制直链
+    viewable: bool,      // 浏览器能否直接打开
+    accessible: bool,    // 能否点击访问，无法读取或断开的链接为 false
+    note: String,        // 无法访问时的说明
+    symlink_target: Option<String>,  // 软链接指向的路径
+    permissions: String,   // 权限，仅在显示 permissions 列时填充
+    downloads: u64,        // 下载次数，仅在显示 downloads 列时填充
+    file_type: String,     // MIME 类型，仅在显示 type 列时填充
+}
+
+impl FileEntry {
+    // 无法访问的条目：仍然列出，但不提供链接与操作按钮
+    fn unavailable(name: String, icon: &str, note: &str) -> Self {
+        FileEntry {
+            display_name: name.clone(),
+            name,
+            size_string: String::new(),
+            size: 0,
+            modified_time: String::new(),
+            modified_iso: String::new(),
+            modified_secs: 0,
+            is_dir: false,
+            icon: icon.to_string(),
+            preview_url: String::new(),
+            preview_deferred: false,
+            url: String::new(),
+            viewable: false,
+            accessible: false,
+            note: note.to_string(),
+            symlink_target: None,
+            permissions: String::new(),
+            downloads: 0,
+            file_type: String::new(),
+        }
+    }
+}
+
+// 首页请求的查询参数
+#[derive(Debug, Deserialize, Default)]
+struct IndexQuery {
+    dl: Option<String>,  // dl=1 时强制以附件形式下载
+    #[serde(rename = "type")]
+    file_type: Option<String>,  // 按文件分类过滤列表
+    head: Option<u64>,  // 只返回文件开头的 N KB 文本
+    tail: Option<u64>,  // 只返回文件末尾的 N KB 文本
+    raw: Option<String>,  // raw=1 时以 UTF-8 纯文本返回整个文件，用于预览非 UTF-8 编码的文本
+    charset: Option<String>,  // 文本预览时指定文件的字符集，如 gbk、big5，未指定时自动识别
+    fmt: Option<String>,  // 图片转码格式：auto、webp、avif 或 orig
+    thumb: Option<String>,  // thumb=1 时返回缩略图，用于预览大图
+    stats: Option<String>,  // stats=1 时以 JSON 返回目录中各分类文件的数量与大小
+    recursive: Option<String>,  // 与 stats=1 或 q 同用，recursive=1 或 true 时包含子目录，层数受 max_recursion_depth 限制
+    sort: Option<String>,  // 排序字段：name、size、modified
+    order: Option<String>,  // 排序方向：asc、desc
+    view: Option<String>,  // 显示方式：list、grid
+    q: Option<String>,  // 只显示名称中包含该字符串的条目（不区分大小写）
+}
+
+impl IndexQuery {
+    fn recursive(&self) -> bool {
+        matches!(self.recursive.as_deref(), Some("1" | "true"))
+    }
+}
+
+// 用户在页面上选择的排序与显示方式，写入会话 Cookie 以便在目录之间保持
+const SORT_COOKIE: &str = "webdisk_sort";
+const ORDER_COOKIE: &str = "webdisk_order";
+const VIEW_COOKIE: &str = "webdisk_view";
+
+// 按 serde 的小写名称解析 sort、order、view 的取值，无效值返回 None
+fn parse_choice<T: serde::de::DeserializeOwned>(value: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(value.to_string())).ok()
+}
+
+fn cookie_value<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers().get_all(header::COOKIE)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+// 有效的查询参数优先并写回 Cookie，否则使用 Cookie 中保存的值
+fn listing_choice<T: serde::de::DeserializeOwned>(
+    req: &HttpRequest,
+    param: Option<&str>,
+    cookie: &str,
+    set_cookies: &mut Vec<String>,
+) -> Option<T> {
+    if let Some(choice) = param.and_then(parse_choice) {
+        // 能解析的取值只含小写字母，可以直接写入 Cookie
+        set_cookies.push(format!("{}={}; Path=/; HttpOnly; SameSite=Lax", cookie, param.unwrap_or_default()));
+        return Some(choice);
+    }
+    cookie_value(req, cookie).and_then(parse_choice)
+}
+
+// 排序与显示方式的优先级：查询参数 > Cookie > 目录配置 > 全局配置；
+// 返回需要设置的 Set-Cookie 值
+fn apply_listing_choices(req: &HttpRequest, query: &IndexQuery, settings: &mut ListingSettings) -> Vec<String> {
+    let mut set_cookies = Vec::new();
+    if let Some(sort) = listing_choice(req, query.sort.as_deref(), SORT_COOKIE, &mut set_cookies) {
+        settings.sort = sort;
+    }
+    if let Some(order) = listing_choice(req, query.order.as_deref(), ORDER_COOKIE, &mut set_cookies) {
+        settings.order = order;
+    }
+    if let Some(view) = listing_choice(req, query.view.as_deref(), VIEW_COOKIE, &mut set_cookies) {
+        settings.view = view;
+    }
+    set_cookies
+}
+
+// 列表上方的排序选项
+#[derive(Debug, Serialize)]
+struct SortChip {
+    key: &'static str,
+    label: &'static str,
+    active: bool,
+    next_order: &'static str,  // 点击后使用的排序方向，当前字段再次点击时反转
+}
+
+fn sort_chips(settings: &ListingSettings, t: &UiStrings) -> Vec<SortChip> {
+    [(SortKey::Name, "name", t.sort_name), (SortKey::Size, "size", t.sort_size), (SortKey::Modified, "modified", t.sort_modified)]
+        .into_iter()
+        .map(|(sort, key, label)| {
+            let active = settings.sort == sort;
+            SortChip {
+                key,
+                label,
+                active,
+                next_order: if active && settings.order == SortOrder::Asc { "desc" } else { "asc" },
+            }
+        })
+        .collect()
+}
+
+// 列表上方的分类过滤标签
+#[derive(Debug, Serialize)]
+struct FilterChip {
+    key: &'static str,
+    label: &'static str,
+    active: bool,
+}
+
+// 界面文本
+#[derive(Debug, Serialize)]
+struct UiStrings {
+    lang: &'static str,
+    directory: &'static str,
+    parent_dir: &'static str,
+    root_dir: &'static str,
+    root_crumb: &'static str,
+    preview: &'static str,
+    view: &'static str,
+    download: &'static str,
+    not_found_message: &'static str,
+    forbidden_message: &'static str,
+    render_error_title: &'static str,
+    render_error_message: &'static str,
+    filter_all: &'static str,
+    filter_clear: &'static str,
+    search: &'static str,
+    search_placeholder: &'static str,
+    search_clear: &'static str,
+    upload_submit: &'static str,
+    delete: &'static str,
+    rename: &'static str,
+    rename_prompt: &'static str,
+    rename_target_exists: &'static str,
+    invalid_rename_target: &'static str,
+    rename_failed: &'static str,
+    new_folder: &'static str,
+    new_folder_placeholder: &'static str,
+    invalid_folder_name: &'static str,
+    folder_exists: &'static str,
+    delete_confirm: &'static str,
+    delete_dir_confirm: &'static str,
+    upload_overwrite: &'static str,
+    search_results: &'static str,
+    search_recursive: &'static str,
+    search_truncated: &'static str,
+    empty_search: &'static str,
+    cat_image: &'static str,
+    cat_video: &'static str,
+    cat_audio: &'static str,
+    cat_archive: &'static str,
+    cat_doc: &'static str,
+    cat_code: &'static str,
+    select_all: &'static str,
+    selected: &'static str,
+    zip_download: &'static str,
+    unreadable: &'static str,
+    broken_link: &'static str,
+    playlist: &'static str,
+    checksum: &'static str,
+    copy_link: &'static str,
+    copied: &'static str,
+    load_preview: &'static str,
+    bookmarks: &'static str,
+    add_bookmark: &'static str,
+    remove_bookmark: &'static str,
+    downloads: &'static str,
+    empty_directory: &'static str,
+    listing_truncated: &'static str,
+    empty_filter: &'static str,
+    empty_upload_hint: &'static str,
+    write_only_notice: &'static str,
+    sort_by: &'static str,
+    sort_name: &'static str,
+    sort_size: &'static str,
+    sort_modified: &'static str,
+    view_list: &'static str,
+    view_grid: &'static str,
+}
+
+impl UiStrings {
+    fn category_label(&self, category: Category) -> &'static str {
+        match category {
+            Category::Image => self.cat_image,
+            Category::Video => self.cat_video,
+            Category::Audio => self.cat_audio,
+            Category::Archive => self.cat_archive,
+            Category::Doc => self.cat_doc,
+            Category::Code => self.cat_code,
+            _ => "",
+        }
+    }
+}
+
+const UI_ZH: UiStrings = UiStrings {
+    lang: "zh-CN",
+    directory: "目录",
+    parent_dir: "返回上级目录",
+    root_dir: "返回根目录",
+    root_crumb: "根目录",
+    preview: "预览",
+    view: "查看",
+    download: "下载",
+    not_found_message: "您访问的文件或目录不存在，可能已被移动或删除。",
+    forbidden_message: "服务器没有读取该文件或目录的权限。",
+    render_error_title: "页面渲染失败",
+    render_error_message: "服务器在生成页面时出错，请稍后重试或联系管理员。",
+    filter_all: "全部",
+    filter_clear: "清除过滤",
+    search: "搜索",
+    search_placeholder: "按文件名搜索当前目录",
+    search_clear: "清除搜索",
+    upload_submit: "上传",
+    delete: "删除",
+    rename: "重命名",
+    rename_prompt: "新名称（包含 / 时移动到对应目录，以 / 开头表示从根目录算起）",
+    rename_target_exists: "目标位置已存在同名的文件或文件夹，未做任何修改",
+    invalid_rename_target: "目标路径无效或所在目录不存在",
+    rename_failed: "重命名失败",
+    new_folder: "新建文件夹",
+    new_folder_placeholder: "文件夹名称",
+    invalid_folder_name: "文件夹名称不能为空，不能是 . 或 ..，也不能包含 / 或 \\",
+    folder_exists: "同名的文件或文件夹已存在",
+    delete_confirm: "确定删除 {name} 吗？删除后无法恢复",
+    delete_dir_confirm: "确定删除目录 {name} 及其中的全部内容吗？删除后无法恢复",
+    upload_overwrite: "覆盖同名文件",
+    search_results: "搜索",
+    search_recursive: "包含子目录",
+    search_truncated: "搜索结果过多，仅显示找到的前 {shown} 项，请使用更精确的关键字",
+    empty_search: "当前目录中没有名称包含该内容的文件",
+    cat_image: "图片",
+    cat_video: "视频",
+    cat_audio: "音频",
+    cat_archive: "压缩包",
+    cat_doc: "文档",
+    cat_code: "代码",
+    select_all: "全选",
+    selected: "已选 {count} 项，文件共 {size}",
+    zip_download: "打包下载",
+    unreadable: "无法读取文件信息",
+    broken_link: "链接目标不存在",
+    playlist: "生成播放列表",
+    checksum: "查看 SHA256 校验和",
+    copy_link: "复制链接",
+    copied: "已复制",
+    load_preview: "文件较大，点击加载预览",
+    bookmarks: "书签",
+    add_bookmark: "收藏此目录",
+    remove_bookmark: "取消收藏",
+    downloads: "下载次数",
+    empty_directory: "此目录为空",
+    listing_truncated: "列表过长，已截断：仅显示排序后的前 {shown} 项，共 {total} 项。可切换排序方式查看其余文件",
+    empty_filter: "此目录中没有该分类的文件",
+    empty_upload_hint: "可以通过 WebDAV 上传文件，挂载地址：",
+    write_only_notice: "此目录只接收上传，已有文件不可查看",
+    sort_by: "排序",
+    sort_name: "名称",
+    sort_size: "大小",
+    sort_modified: "修改时间",
+    view_list: "列表",
+    view_grid: "网格",
+};
+
+const UI_EN: UiStrings = UiStrings {
+    lang: "en",
+    directory: "Directory",
+    parent_dir: "Parent directory",
+    root_dir: "Back to root",
+    root_crumb: "root",
+    preview: "Preview",
+    view: "View",
+    download: "Download",
+    not_found_message: "The file or directory you requested does not exist. It may have been moved or deleted.",
+    forbidden_message: "The server does not have permission to read this file or directory.",
+    render_error_title: "Page rendering failed",
+    render_error_message: "The server failed to build this page. Please try again later or contact the administrator.",
+    filter_all: "All",
+    filter_clear: "Clear filter",
+    search: "Search",
+    search_placeholder: "Search this folder by name",
+    search_clear: "Clear search",
+    upload_submit: "Upload",
+    delete: "Delete",
+    rename: "Rename",
+    rename_prompt: "New name (include / to move into another folder; a leading / starts from the root)",
+    rename_target_exists: "A file or folder already exists at the destination; nothing was changed",
+    invalid_rename_target: "The destination is invalid or its folder does not exist",
+    rename_failed: "Rename failed",
+    new_folder: "New folder",
+    new_folder_placeholder: "Folder name",
+    invalid_folder_name: "Folder names must not be empty, . or .., or contain / or \\",
+    folder_exists: "A file or folder with that name already exists",
+    delete_confirm: "Delete {name}? This cannot be undone",
+    delete_dir_confirm: "Delete the folder {name} and everything in it? This cannot be undone",
+    upload_overwrite: "Overwrite existing files",
+    search_results: "Search",
+    search_recursive: "Include subfolders",
+    search_truncated: "Too many matches: only the first {shown} found are shown. Try a more specific search",
+    empty_search: "No matching names in this directory",
+    cat_image: "Images",
+    cat_video: "Videos",
+    cat_audio: "Audio",
+    cat_archive: "Archives",
+    cat_doc: "Documents",
+    cat_code: "Code",
+    select_all: "Select all",
+    selected: "{count} selected, {size} of files",
+    zip_download: "Download as ZIP",
+    unreadable: "Metadata unavailable",
+    broken_link: "Broken link",
+    playlist: "Playlist",
+    checksum: "Show SHA256 checksum",
+    copy_link: "Copy link",
+    copied: "Copied",
+    load_preview: "Large file, click to load preview",
+    bookmarks: "Bookmarks",
+    add_bookmark: "Bookmark this folder",
+    remove_bookmark: "Remove bookmark",
+    downloads: "Downloads",
+    empty_directory: "This directory is empty",
+    listing_truncated: "Listing too long and truncated: showing the first {shown} of {total} entries in the current order. Change the sort order to see the rest",
+    empty_filter: "No files of this type in this directory",
+    empty_upload_hint: "You can upload files over WebDAV at:",
+    write_only_notice: "This directory only accepts uploads; existing files are not visible",
+    sort_by: "Sort",
+    sort_name: "Name",
+    sort_size: "Size",
+    sort_modified: "Modified",
+    view_list: "List",
+    view_grid: "Grid",
+};
+
+// 根据配置和 Accept-Language 选择界面文本
+fn ui_strings(config: &Config, req: &HttpRequest) -> &'static UiStrings {
+    let lang = if config.lang == "auto" {
+        req.headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .unwrap_or("zh")
+            .trim()
+            .to_lowercase()
+    } else {
+        config.lang.to_lowercase()
+    };
+
+    if lang.starts_with("en") {
+        &UI_EN
+    } else {
+        &UI_ZH
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let mut users = BTreeMap::new();
+        users.insert("admin".to_string(), UserConfig {
+            password: "admin".to_string(),
+            permissions: "rwx".to_string(),
+            root: None,
+            landing_path: None,
+        });
+
+        Config {
+            ip: "0.0.0.0".to_string(),
+            ipv6: "::".to_string(),
+            port: 8080,
+            bind_mode: BindMode::default(),
+            cwd: "data/www".to_string(),
+            webdav: WebDAVConfig {
+                enabled: false,
+                users,
+                locking: false,
+                read_only: false,
+                allow_anonymous: false,
+                pretty_index: false,
+                root: None,
+                mount_path: default_mount_path(),
+                min_password_len: 0,
+                require_mixed_classes: false,
+                password_length: default_password_length(),
+                password_symbols: true,
+            },
+            server: ServerConfig::default(),
+            limits: LimitsConfig::default(),
+            proxy: ProxyConfig::default(),
+            compression: CompressionConfig::default(),
+            branding: BrandingConfig::default(),
+            lang: default_lang(),
+            group_dirs_first: true,
+            sort: SortKey::default(),
+            order: SortOrder::default(),
+            view: ViewMode::default(),
+            show_hidden: true,
+            show_symlink_targets: false,
+            cors: None,
+            timezone: None,
+            stream_threshold: default_stream_threshold(),
+            max_listing_entries: default_max_listing_entries(),
+            upload: UploadConfig::default(),
+            readme: ReadmeConfig::default(),
+            sitemap: SitemapConfig::default(),
+            hide: HideConfig::default(),
+            preview: PreviewConfig::default(),
+            log: LogConfig::default(),
+            web_auth: WebAuthConfig::default(),
+            pwa: PwaConfig::default(),
+            cache: CacheConfig::default(),
+            columns: default_columns(),
+            debug: false,
+            image_transcode: false,
+            trust_svg: false,
+            sniff_extensionless: false,
+            preview_charset: None,
+            normalize_paths: true,
+            expose_build_info: false,
+            live_updates: false,
+            allow_web_delete: false,
+            allow_web_mkdir: false,
+            allow_web_rename: false,
+            max_recursion_depth: default_max_recursion_depth(),
+        }
+    }
+}
+
+impl Config {
+    // 合并全局配置与目录覆盖设置
+    fn listing_settings(&self, overrides: &DirOverrides) -> ListingSettings {
+        ListingSettings {
+            sort: overrides.sort.unwrap_or(self.sort),
+            order: overrides.order.unwrap_or(self.order),
+            view: overrides.view.unwrap_or(self.view),
+            group_dirs_first: overrides.group_dirs_first.unwrap_or(self.group_dirs_first),
+            show_hidden: overrides.show_hidden.unwrap_or(self.show_hidden),
+            hide: self.hide.clone(),
+            preview_limits: self.preview.max_inline_bytes,
+            show_symlink_targets: self.show_symlink_targets,
+            timezone: self.display_timezone(),
+            columns: self.columns.clone(),
+            max_entries: self.max_listing_entries,
+            sniff_extensionless: self.sniff_extensionless,
+            search: None,
+            web_delete: self.allow_web_delete,
+            web_rename: self.allow_web_rename,
+        }
+    }
+
+    // 检查反序列化无法表达的约束
+    fn validate(&self) -> std::io::Result<()> {
+        let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, ConfigError(msg));
+        self.webdav.validate_mount_path().map_err(invalid)?;
+        // 用户的 root 拼接在 WebDAV 根目录之下，不能通过 .. 越出
+        for (name, user) in &self.webdav.users {
+            if let Some(root) = user.root.as_deref().filter(|root| join_in_cwd("", root).is_none()) {
+                return Err(invalid(format!("用户 {} 的 root {} 不能包含 ..", name, root)));
+            }
+        }
+        if self.web_auth.enabled && self.webdav.users.is_empty() {
+            return Err(invalid("开启 web_auth 时至少需要在 webdav.users 中配置一个用户".to_string()));
+        }
+        if self.bind_mode == BindMode::Ipv6 && self.ipv6.is_empty() {
+            return Err(invalid("bind_mode 为 ipv6 时必须设置 ipv6 地址".to_string()));
+        }
+        if !self.ipv6.is_empty() && ipv6_socket_addr(&self.ipv6, self.port).is_err() {
+            return Err(invalid(format!("无效的 IPv6 地址 {}", self.ipv6)));
+        }
+        if let Some(label) = &self.preview_charset {
+            if Encoding::for_label(label.as_bytes()).is_none() {
+                return Err(invalid(format!("未知的字符集 {}", label)));
+            }
+        }
+        if !(1..=9).contains(&self.compression.level) {
+            return Err(invalid(format!("无效的压缩级别 {}，应为 1-9", self.compression.level)));
+        }
+        if let Some(tz) = &self.timezone {
+            tz.parse::<Tz>().map_err(|_| invalid(format!("无效的时区 {}，应为 IANA 时区名，如 Asia/Shanghai", tz)))?;
+        }
+        Ok(())
+    }
+
+    // cwd 与数据目录相同或包含数据目录时返回数据目录的绝对路径。此时配置文件等虽然受保护，
+    // 但通常是误配置：一旦保护规则遗漏某个文件，WebDAV 密码等敏感信息就会对外提供
+    pub fn data_dir_overlap(&self) -> Option<PathBuf> {
+        let resolve = |p: &Path| fs::canonicalize(p).or_else(|_| std::path::absolute(p)).ok();
+        let data_dir = resolve(Path::new("data"))?;
+        let cwd = resolve(Path::new(&self.cwd))?;
+        data_dir.starts_with(&cwd).then_some(data_dir)
+    }
+
+    // 页面显示时间使用的时区，未配置时为 None，表示使用系统本地时区
+    fn display_timezone(&self) -> Option<Tz> {
+        self.timezone.as_deref().and_then(|tz| tz.parse().ok())
+    }
+
+    pub fn load() -> std::io::Result<Self> {
+        let data_dir = Path::new("data");
+        let config_path = data_dir.join("config.yaml");
+
+        if !data_dir.exists() {
+            fs::create_dir_all(data_dir)?;
+        }
+
+        if !config_path.exists() {
+            Self::create_default_config()?;
+        }
+        
+        let config_str = fs::read_to_string(&config_path)?;
+        let config = Self::from_yaml(&config_str, STRICT_CONFIG.load(Ordering::Relaxed))?;
+        
+        let cwd_path = Path::new(&config.cwd);
+        if !cwd_path.exists() {
+            fs::create_dir_all(cwd_path)?;
+        }
+        
+        Ok(config)
+    }
+
+    // 解析配置文本。未知字段默认逐个警告后忽略，便于在不同版本之间切换；
+    // 严格模式下视为错误，用于发现 prot: 8080 这类拼写错误
+    pub fn from_yaml(text: &str, strict: bool) -> std::io::Result<Self> {
+        let invalid = |e: String| std::io::Error::new(std::io::ErrorKind::InvalidData, ConfigError(e));
+        let config: Self = serde_yaml::from_str(text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let input: serde_yaml::Value = serde_yaml::from_str(text).map_err(|e| invalid(e.to_string()))?;
+        let known = serde_yaml::to_value(&config).map_err(|e| invalid(e.to_string()))?;
+        let mut unknown = Vec::new();
+        collect_unknown_keys(&input, &known, "", &mut unknown);
+        if strict && !unknown.is_empty() {
+            return Err(invalid(format!("配置文件中存在未知字段: {}", unknown.join(", "))));
+        }
+        for key in &unknown {
+            tracing::warn!(event = "config_unknown_field", field = %key, "忽略配置文件中的未知字段（可能是拼写错误或来自其他版本）");
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    // 添加创建默认配置的函数
+    pub fn create_default_config() -> std::io::Result<()> {
+        let mut config = Config::default();
+        // 默认账号的密码同样以哈希写入，配置文件中不出现明文
+        for user in config.webdav.users.values_mut() {
+            user.password = hash_password(&user.password);
+        }
+        let yaml_str = serde_yaml::to_string(&config)
+            .map_err(std::io::Error::other)?;
+        fs::write("data/config.yaml", yaml_str)?;
+        tracing::info!(event = "config_created", path = "data/config.yaml", "已创建默认配置文件");
+        Ok(())
+    }
+
+    // 添加从指定路径加载配置的方法
+    pub fn load_from(config_path: &Path) -> std::io::Result<Self> {
+        if !config_path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                ConfigError("指定的配置文件不存在".to_string())
+            ));
+        }
+        
+        let config_str = fs::read_to_string(config_path)?;
+        let config = Self::from_yaml(&config_str, STRICT_CONFIG.load(Ordering::Relaxed))?;
+        
+        let cwd_path = Path::new(&config.cwd);
+        if !cwd_path.exists() {
+            fs::create_dir_all(cwd_path)?;
+        }
+        
+        Ok(config)
+    }
+}
+
+
+// 是否以严格模式加载配置，由命令行参数 --strict-config 开启
+static STRICT_CONFIG: AtomicBool = AtomicBool::new(false);
+
+pub fn set_strict_config(strict: bool) {
+    STRICT_CONFIG.store(strict, Ordering::Relaxed);
+}
+
+// 对比原始配置与解析后重新序列化的结果，找出没有被任何字段接收的键。
+// 值为 null 的键视为已知，可选字段为空时不会被序列化
+fn collect_unknown_keys(input: &serde_yaml::Value, known: &serde_yaml::Value, prefix: &str, out: &mut Vec<String>) {
+    use serde_yaml::Value;
+    match (input, known) {
+        (Value::Mapping(input), Value::Mapping(known)) => {
+            for (key, value) in input {
+                let name = match key {
+                    Value::String(name) => name.clone(),
+                    other => serde_yaml::to_string(other).unwrap_or_default().trim().to_string(),
+                };
+                let path = if prefix.is_empty() { name } else { format!("{}.{}", prefix, name) };
+                match known.get(key) {
+                    Some(known) => collect_unknown_keys(value, known, &path, out),
+                    None if value.is_null() => {}
+                    None => out.push(path),
+                }
+            }
+        }
+        (Value::Sequence(input), Value::Sequence(known)) => {
+            for (i, (value, known)) in input.iter().zip(known).enumerate() {
+                collect_unknown_keys(value, known, &format!("{}[{}]", prefix, i), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+// 文件大小格式化
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
+    let mut size = size as f64;
+    let mut unit_index = 0;
+    
+    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_index += 1;
+    }
+    
+    if unit_index == 0 {
+        format!("{} {}", size as u64, UNITS[unit_index])
+    } else {
+        format!("{:.2} {}", size, UNITS[unit_index])
+    }
+}
+
+// 文件分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    DiskImage,
+    Image,
+    Video,
+    Audio,
+    Doc,
+    Archive,
+    Code,
+    Executable,
+    Config,
+    Font,
+    Other,
+}
+
+impl Category {
+    // 可用于 ?type= 过滤的分类
+    const FILTERABLE: [Category; 6] = [
+        Category::Image,
+        Category::Video,
+        Category::Audio,
+        Category::Archive,
+        Category::Doc,
+        Category::Code,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Category::DiskImage => "diskimage",
+            Category::Image => "image",
+            Category::Video => "video",
+            Category::Audio => "audio",
+            Category::Doc => "doc",
+            Category::Archive => "archive",
+            Category::Code => "code",
+            Category::Executable => "executable",
+            Category::Config => "config",
+            Category::Font => "font",
+            Category::Other => "other",
+        }
+    }
+
+    const ALL: [Category; 11] = [
+        Category::DiskImage,
+        Category::Image,
+        Category::Video,
+        Category::Audio,
+        Category::Doc,
+        Category::Archive,
+        Category::Code,
+        Category::Executable,
+        Category::Config,
+        Category::Font,
+        Category::Other,
+    ];
+
+    fn from_param(value: &str) -> Option<Self> {
+        Self::FILTERABLE.into_iter().find(|c| c.as_str() == value)
+    }
+
+    fn from_name(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_str() == value)
+    }
+}
+
+// 自定义图标配置文件，按扩展名覆盖内置的图标与分类
+const ICONS_FILE: &str = "data/icons.yaml";
+
+#[derive(Debug, Clone, Default)]
+struct IconOverride {
+    icon: Option<String>,
+    category: Option<Category>,
+}
+
+static ICON_OVERRIDES: std::sync::OnceLock<std::collections::HashMap<String, IconOverride>> = std::sync::OnceLock::new();
+
+fn icon_overrides() -> &'static std::collections::HashMap<String, IconOverride> {
+    ICON_OVERRIDES.get_or_init(Default::default)
+}
+
+// 启动时读取 ICONS_FILE 中的图标配置，需在处理请求之前调用
+pub fn init_icon_overrides() {
+    let _ = ICON_OVERRIDES.set(load_icon_overrides(Path::new(ICONS_FILE)));
+}
+
+// 解析单个扩展名的配置，格式为 { icon: "🎨", category: image }，也可直接写图标字符串
+fn parse_icon_override(value: &serde_yaml::Value) -> Result<IconOverride, String> {
+    let (icon, category) = match value {
+        serde_yaml::Value::String(icon) => (Some(icon.clone()), None),
+        serde_yaml::Value::Mapping(map) => {
+            if let Some(key) = map.keys().find(|k| !matches!(k.as_str(), Some("icon" | "category"))) {
+                return Err(format!("未知字段 {}", serde_yaml::to_string(key).unwrap_or_default().trim()));
+            }
+            let field = |name: &str| -> Result<Option<String>, String> {
+                match map.get(name) {
+                    None | Some(serde_yaml::Value::Null) => Ok(None),
+                    Some(serde_yaml::Value::String(s)) => Ok(Some(s.clone())),
+                    Some(_) => Err(format!("{} 必须是字符串", name)),
+                }
+            };
+            (field("icon")?, field("category")?)
+        }
+        _ => return Err("必须是图标字符串或包含 icon、category 的映射".to_string()),
+    };
+
+    // 图标原样输出到页面中，不允许包含 HTML 特殊字符
+    if let Some(icon) = &icon {
+        if icon.trim().is_empty() || icon.contains(['<', '>', '"', '\'', '&']) {
+            return Err(format!("无效的图标 {:?}", icon));
+        }
+    }
+    let category = match category {
+        Some(name) => Some(Category::from_name(&name)
+            .ok_or_else(|| format!("未知的分类 {:?}，可用分类: {}", name,
+                Category::ALL.map(|c| c.as_str()).join("、")))?),
+        None => None,
+    };
+    if icon.is_none() && category.is_none() {
+        return Err("至少需要设置 icon 或 category".to_string());
+    }
+    Ok(IconOverride { icon, category })
+}
+
+// 读取自定义图标配置，无效的条目给出警告后跳过
+fn load_icon_overrides(path: &Path) -> std::collections::HashMap<String, IconOverride> {
+    let mut overrides = std::collections::HashMap::new();
+    let Ok(content) = fs::read_to_string(path) else {
+        return overrides;
+    };
+    let mapping = match serde_yaml::from_str::<serde_yaml::Mapping>(&content) {
+        Ok(mapping) => mapping,
+        Err(e) => {
+            tracing::warn!(event = "icons_invalid", path = %path.display(), error = %e, "忽略格式错误的图标配置");
+            return overrides;
+        }
+    };
+
+    for (key, value) in &mapping {
+        let Some(ext) = key.as_str().map(|k| k.trim_start_matches('.').to_lowercase()) else {
+            tracing::warn!(event = "icons_invalid", extension = ?key, "忽略图标配置中的无效扩展名");
+            continue;
+        };
+        if ext.is_empty() || ext.contains(['.', '/', ' ']) {
+            tracing::warn!(event = "icons_invalid", extension = %ext, "忽略图标配置中的无效扩展名");
+            continue;
+        }
+        match parse_icon_override(value) {
+            Ok(rule) => {
+                overrides.insert(ext, rule);
+            }
+            Err(e) => tracing::warn!(event = "icons_invalid", extension = %ext, error = %e, "忽略图标配置"),
+        }
+    }
+    overrides
+}
+
+fn file_extension(name: &str) -> String {
+    name.rsplit('.').next().unwrap_or("").to_lowercase()
+}
+
+// 根据扩展名判断文件分类，自定义配置优先于内置规则
+fn file_category(name: &str) -> Category {
+    let extension = file_extension(name);
+    if let Some(category) = icon_overrides().get(&extension).and_then(|rule| rule.category) {
+        return category;
+    }
+    match extension.as_str() {
+        // 镜像文件
+        "iso" | "img" | "esd" | "wim" | "vhd" | "vmdk" => Category::DiskImage,
+        // 图片
+        "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" | "svg" => Category::Image,
+        // 视频
+        "mp4" | "mkv" | "avi" | "mov" | "wmv" | "flv" | "webm" => Category::Video,
+        // 音频
+        "mp3" | "wav" | "ogg" | "m4a" | "flac" | "aac" => Category::Audio,
+        // 文档
+        "pdf" | "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx" | "txt" | "md" | "log" => Category::Doc,
+        // 压缩文件
+        "zip" | "rar" | "7z" | "tar" | "gz" | "bz2" | "xz" => Category::Archive,
+        // 代码文件
+        "c" | "cpp" | "h" | "hpp" | "rs" | "go" | "py" | "js" | "html" | "css" | "java" => Category::Code,
+        // 可执行文件
+        "exe" | "msi" | "bat" | "sh" | "cmd" => Category::Executable,
+        // 配置文件
+        "json" | "yaml" | "yml" | "toml" | "ini" | "conf" => Category::Config,
+        // 字体文件
+        "ttf" | "otf" | "woff" | "woff2" => Category::Font,
+        _ => Category::Other,
+    }
+}
+
+// 获取文件图标：自定义图标优先，其次按分类选择
+fn get_file_icon(name: &str) -> &'static str {
+    let extension = file_extension(name);
+    if let Some(icon) = icon_overrides().get(&extension).and_then(|rule| rule.icon.as_deref()) {
+        return icon;
+    }
+    category_icon(file_category(name), &extension)
+}
+
+fn category_icon(category: Category, extension: &str) -> &'static str {
+    match category {
+        Category::DiskImage => "💿",
+        Category::Image => "🖼️",
+        Category::Video => "🎥",
+        Category::Audio => "🎵",
+        Category::Doc => {
+            match extension {
+                "pdf" => "📕",
+                "doc" | "docx" => "📘",
+                "xls" | "xlsx" => "📗",
+                "ppt" | "pptx" => "📙",
+                _ => "📄",
+            }
+        }
+        Category::Archive => "📦",
+        Category::Code => "📝",
+        Category::Executable | Category::Config => "⚙️",
+        Category::Font => "🔤",
+        // 默认文件图标
+        Category::Other => "📄",
+    }
+}
+
+// 判断文件是否可预览
+fn is_previewable(name: &str) -> bool {
+    let extension = name.rsplit('.').next().unwrap_or("").to_lowercase();
+    matches!(extension.as_str(), 
+        "jpg" | "jpeg" | "png" | "gif" | "webp" |
+        "mp4" | "webm" |
+        "mp3" | "wav" | "ogg"
+    )
+}
+
+// 内容嗅探时读取的最大字节数
+const SNIFF_LEN: usize = 8192;
+// 目录列表中嗅探无扩展名文件时读取的字节数，每个文件都要读取，取较小的值
+const LISTING_SNIFF_LEN: u64 = 512;
+
+// 检测文件的 Content-Type：优先按扩展名判断，无法判断时读取文件头识别
+// 不受信任的 SVG 直接打开时禁止执行脚本与加载外部资源；<img> 预览不受影响
+const SVG_CSP: &str = "default-src 'none'; style-src 'unsafe-inline'; sandbox";
+
+fn is_svg(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("svg") || e.eq_ignore_ascii_case("svgz"))
+}
+
+fn detect_content_type(path: &Path) -> Mime {
+    let guessed = mime_guess::from_path(path).first();
+    let mime = match guessed {
+        Some(m) if m != mime::APPLICATION_OCTET_STREAM => m,
+        _ => sniff_content_type(path).unwrap_or(mime::APPLICATION_OCTET_STREAM),
+    };
+
+    // 文本文件统一以 UTF-8 纯文本返回，便于浏览器内联预览
+    if mime.type_() == mime::TEXT
+        && !matches!(mime.subtype().as_str(), "html" | "css" | "csv" | "xml" | "javascript")
+    {
+        mime::TEXT_PLAIN_UTF_8
+    } else {
+        mime
+    }
+}
+
+// 读取文件头，通过魔数或文本特征识别类型
+fn sniff_content_type(path: &Path) -> Option<Mime> {
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    fs::File::open(path).ok()?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut buf)
+        .ok()?;
+
+    if let Some(kind) = infer::get(&buf) {
+        return kind.mime_type().parse().ok();
+    }
+
+    if looks_like_text(&buf) {
+        Some(mime::TEXT_PLAIN_UTF_8)
+    } else {
+        None
+    }
+}
+
+// 读取无扩展名文件的开头，按魔数识别分类与 MIME 类型；以 #! 开头的脚本归为代码，其余文本归为文档
+fn sniff_listing_entry(storage: &dyn Storage, path: &Path) -> Option<(Category, String)> {
+    let mut buf = Vec::with_capacity(LISTING_SNIFF_LEN as usize);
+    storage.open_read(path).ok()?
+        .take(LISTING_SNIFF_LEN)
+        .read_to_end(&mut buf)
+        .ok()?;
+
+    if let Some(kind) = infer::get(&buf) {
+        let category = match kind.matcher_type() {
+            infer::MatcherType::Image => Category::Image,
+            infer::MatcherType::Video => Category::Video,
+            infer::MatcherType::Audio => Category::Audio,
+            infer::MatcherType::Archive => Category::Archive,
+            infer::MatcherType::Doc | infer::MatcherType::Book => Category::Doc,
+            infer::MatcherType::App => Category::Executable,
+            infer::MatcherType::Font => Category::Font,
+            infer::MatcherType::Text => Category::Code,
+            infer::MatcherType::Custom => Category::Other,
+        };
+        return Some((category, kind.mime_type().to_string()));
+    }
+    looks_like_text(&buf).then(|| (Category::Doc, mime::TEXT_PLAIN.essence_str().to_string()))
+}
+
+// 判断数据是否为 UTF-8 文本（允许末尾被截断的字符）
+fn looks_like_text(buf: &[u8]) -> bool {
+    if buf.is_empty() || buf.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(buf) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && buf.len() - e.valid_up_to() < 4,
+    }
+}
+
+// 判断文件是否可由浏览器直接打开
+fn is_viewable(name: &str) -> bool {
+    let extension = name.rsplit('.').next().unwrap_or("").to_lowercase();
+    matches!(extension.as_str(),
+        "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" | "svg" |
+        "mp4" | "webm" |
+        "mp3" | "wav" | "ogg" | "m4a" | "flac" |
+        "pdf" | "txt" | "md" | "log" | "json"
+    )
+}
+
+// 与存储后端无关的文件元数据
+#[derive(Debug, Clone)]
+struct StorageMetadata {
+    is_dir: bool,
+    is_symlink: bool,
+    len: u64,
+    modified: Option<std::time::SystemTime>,
+    mode: u32,  // Unix 权限位，其他平台按只读属性近似为 0o444 或 0o666
+}
+
+impl From<fs::Metadata> for StorageMetadata {
+    fn from(md: fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let mode = std::os::unix::fs::PermissionsExt::mode(&md.permissions());
+        #[cfg(not(unix))]
+        let mode = if md.permissions().readonly() { 0o444 } else { 0o666 };
+        StorageMetadata {
+            is_dir: md.is_dir(),
+            is_symlink: md.file_type().is_symlink(),
+            len: md.len(),
+            modified: md.modified().ok(),
+            mode,
+        }
+    }
+}
+
+// 将权限位格式化为 rwxr-xr-x 形式
+fn format_mode(mode: u32) -> String {
+    (0..9).rev()
+        .map(|bit| if mode & (1 << bit) == 0 { '-' } else { ['x', 'w', 'r'][bit % 3] })
+        .collect()
+}
+
+// 文件下载次数，仅统计本次运行期间，启用 downloads 列时记录
+fn download_counts() -> &'static std::sync::Mutex<std::collections::HashMap<PathBuf, u64>> {
+    static COUNTS: std::sync::OnceLock<std::sync::Mutex<std::collections::HashMap<PathBuf, u64>>> = std::sync::OnceLock::new();
+    COUNTS.get_or_init(Default::default)
+}
+
+// 目录条目，元数据不跟随软链接；单个条目读取失败不影响整个目录
+struct StorageEntry {
+    name: String,
+    metadata: std::io::Result<StorageMetadata>,
+}
+
+// 文件存储后端。路径为 cwd 拼接请求路径后的结果，
+// 对象存储等后端可去掉 cwd 前缀后作为对象键使用
+trait Storage: Send + Sync {
+    // 列出目录内容
+    fn list(&self, dir: &Path) -> std::io::Result<Vec<StorageEntry>>;
+    // 读取元数据，跟随软链接
+    fn stat(&self, path: &Path) -> std::io::Result<StorageMetadata>;
+    fn open_read(&self, path: &Path) -> std::io::Result<Box<dyn Read + Send>>;
+    // 读取软链接指向的路径，不支持软链接的后端返回 Unsupported
+    fn read_link(&self, _path: &Path) -> std::io::Result<PathBuf> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+    }
+    // 网页上传与删除经由存储层写入；WebDAV 仍直接使用 LocalFs
+    fn open_write(&self, path: &Path) -> std::io::Result<Box<dyn std::io::Write + Send>>;
+    // 替换已有的目标，上传完成后把临时文件改名为目标文件
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()>;
+    // 删除文件、软链接（只删除链接本身）或目录，recursive 为 false 时只能删除空目录
+    fn delete(&self, path: &Path, recursive: bool) -> std::io::Result<()>;
+
+    // 文件在本地磁盘上的路径。本地后端返回 Some，以便使用 NamedFile 提供
+    // Range、ETag 等支持；其他后端返回 None，由调用方通过 open_read 流式输出
+    fn local_path(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+}
+
+// 默认的本地文件系统后端
+struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn list(&self, dir: &Path) -> std::io::Result<Vec<StorageEntry>> {
+        Ok(fs::read_dir(dir)?
+            .flatten()
+            .map(|entry| StorageEntry {
+                name: entry.file_name().to_string_lossy().to_string(),
+                metadata: entry.metadata().map(StorageMetadata::from),
+            })
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> std::io::Result<StorageMetadata> {
+        fs::metadata(path).map(StorageMetadata::from)
+    }
+
+    fn open_read(&self, path: &Path) -> std::io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(fs::File::open(path)?))
+    }
+
+    fn read_link(&self, path: &Path) -> std::io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn open_write(&self, path: &Path) -> std::io::Result<Box<dyn std::io::Write + Send>> {
+        Ok(Box::new(fs::File::create(path)?))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn delete(&self, path: &Path, recursive: bool) -> std::io::Result<()> {
+        if !fs::symlink_metadata(path)?.is_dir() {
+            fs::remove_file(path)
+        } else if recursive {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_dir(path)
+        }
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        Some(path.to_path_buf())
+    }
+}
+
+// 目录本身或 root 以下的某个上级目录设置了 write_only 时，其中的内容不能浏览或下载；
+// root 为 cwd 或 WebDAV 根目录，规范化后的路径按规范化后的 root 比较
+fn is_write_only(root: &str, dir: &Path) -> bool {
+    let root = if dir.starts_with(root) {
+        PathBuf::from(root)
+    } else {
+        fs::canonicalize(root).unwrap_or_else(|_| PathBuf::from(root))
+    };
+    dir.ancestors()
+        .take_while(|d| d.starts_with(&root))
+        .any(|d| load_dir_overrides(d).write_only)
+}
+
+// 读取目录下的 .webdisk.yaml，按修改时间缓存；格式错误时记录警告并忽略
+fn load_dir_overrides(dir: &Path) -> DirOverrides {
+    type Cache = std::sync::Mutex<std::collections::HashMap<PathBuf, (std::time::SystemTime, DirOverrides)>>;
+    static CACHE: std::sync::OnceLock<Cache> = std::sync::OnceLock::new();
+
+    let file = dir.join(DIR_CONFIG_FILE);
+    let Ok(modified) = fs::metadata(&file).and_then(|md| md.modified()) else {
+        return DirOverrides::default();
+    };
+
+    let cache = CACHE.get_or_init(Default::default);
+    if let Some((cached_time, overrides)) = cache.lock().unwrap().get(&file) {
+        if *cached_time == modified {
+            return overrides.clone();
+        }
+    }
+
+    let overrides = fs::read_to_string(&file)
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_yaml::from_str::<DirOverrides>(&s).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            tracing::warn!(event = "dir_config_invalid", path = %file.display(), error = %e, "忽略格式错误的目录配置");
+            DirOverrides::default()
+        });
+    cache.lock().unwrap().insert(file, (modified, overrides.clone()));
+    overrides
+}
+
+// 按配置的时区格式化修改时间，返回页面显示用的时间与带偏移的 RFC 3339 时间
+fn format_modified(modified: std::time::SystemTime, timezone: Option<Tz>) -> (String, String) {
+    const DISPLAY_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+    match timezone {
+        Some(tz) => {
+            let datetime = DateTime::<Utc>::from(modified).with_timezone(&tz);
+            (datetime.format(DISPLAY_FORMAT).to_string(), datetime.to_rfc3339())
+        }
+        None => {
+            let datetime: DateTime<Local> = modified.into();
+            (datetime.format(DISPLAY_FORMAT).to_string(), datetime.to_rfc3339())
+        }
+    }
+}
+
+// 按设置对条目排序
+// 按大小或修改时间排序时，取值相同的条目始终按名称升序排列，不随排序方向颠倒
+fn sort_entries(entries: &mut [FileEntry], settings: &ListingSettings) {
+    let name = |e: &FileEntry| e.display_name.to_lowercase();
+    entries.sort_by(|a, b| {
+        let primary = match settings.sort {
+            SortKey::Name => name(a).cmp(&name(b)),
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified_secs.cmp(&b.modified_secs),
+        };
+        let primary = if settings.order == SortOrder::Desc { primary.reverse() } else { primary };
+        primary.then_with(|| name(a).cmp(&name(b)))
+    });
+}
+
+// 目录列表中是否显示该名称
+fn is_listed(name: &str, settings: &ListingSettings) -> bool {
+    // 跳过 favicon.ico 文件与目录配置文件的显示
+    if name == "favicon.ico" || name == DIR_CONFIG_FILE {
+        return false;
+    }
+    (settings.show_hidden || !name.starts_with('.')) && !settings.hide.hides_name(name)
+}
+
+// 由目录项的元数据构造列表条目，整页列表与实时更新共用
+fn build_entry(
+    storage: &dyn Storage,
+    dir: &Path,
+    name: String,
+    metadata: std::io::Result<StorageMetadata>,
+    settings: &ListingSettings,
+    t: &UiStrings,
+) -> FileEntry {
+    // 无法读取元数据（如权限不足）时仍然列出，避免文件"凭空消失"
+    let metadata = match metadata {
+        Ok(metadata) => metadata,
+        Err(_) => return FileEntry::unavailable(name, "🚫", t.unreadable),
+    };
+
+    let size = metadata.len;
+    
+    // 检查是否为软链接，并读取其指向的路径
+    let is_symlink = metadata.is_symlink;
+    let symlink_target = (is_symlink && settings.show_symlink_targets)
+        .then(|| storage.read_link(&dir.join(&name)).ok())
+        .flatten()
+        .map(|target| target.to_string_lossy().to_string());
+    let is_dir = if is_symlink {
+        // 如果是软链接，获取目标文件的元数据
+        match storage.stat(&dir.join(&name)) {
+            Ok(target_metadata) => target_metadata.is_dir,
+            Err(_) => {
+                // 链接目标不存在，标记为不可点击
+                let mut entry = FileEntry::unavailable(name, "🔗", t.broken_link);
+                entry.symlink_target = symlink_target;
+                return entry;
+            }
+        }
+    } else {
+        metadata.is_dir
+    };
+
+    let size_string = if is_dir {
+        t.directory.to_string()
+    } else {
+        format_size(size)
+    };
+    
+    let modified = metadata.modified.unwrap_or_else(std::time::SystemTime::now);
+    let (modified_time, modified_iso) = format_modified(modified, settings.timezone);
+    let preview_deferred = !is_dir && settings.preview_limits.defers(&name, size);
+    // 无扩展名的文件（含 .bashrc 这类隐藏文件）按文件头识别，自定义图标配置优先，空文件不读取
+    let sniffed = (settings.sniff_extensionless && !is_dir && size > 0
+        && Path::new(&name).extension().is_none() && file_category(&name) == Category::Other)
+        .then(|| sniff_listing_entry(storage, &dir.join(&name)))
+        .flatten();
+    
+    FileEntry {
+        name: name.clone(),
+        display_name: name.clone(),
+        size_string,
+        size: if is_dir { 0 } else { size },
+        modified_time,
+        modified_iso,
+        modified_secs: DateTime::<Utc>::from(modified).timestamp(),
+        is_dir,
+        icon: if is_dir {
+            "📁".to_string()  // 文件夹图标
+        } else if is_symlink {
+            "🔗".to_string()  // 软链接图标
+        } else if let Some((category, _)) = &sniffed {
+            category_icon(*category, "").to_string()
+        } else {
+            get_file_icon(&name).to_string()
+        },
+        // 超过大小上限的图片改为加载缩略图
+        preview_url: match is_previewable(&name) && !is_dir {
+            false => String::new(),
+            true if preview_deferred && is_thumbnailable(Path::new(&name)) => format!("./{}?thumb=1", name),
+            true => format!("./{}", name),
+        },
+        preview_deferred,
+        url: String::new(),
+        viewable: !is_dir && is_viewable(&name),
+        accessible: true,
+        note: String::new(),
+        symlink_target,
+        // 以下各列开销较大，只在配置显示时计算
+        permissions: if settings.has_column(Column::Permissions) {
+            format_mode(metadata.mode)
+        } else {
+            String::new()
+        },
+        downloads: if settings.has_column(Column::Downloads) && !is_dir {
+            download_counts().lock().unwrap().get(&dir.join(&name)).copied().unwrap_or(0)
+        } else {
+            0
+        },
+        file_type: match (settings.has_column(Column::Type), is_dir) {
+            (false, _) => String::new(),
+            (true, true) => t.directory.to_string(),
+            (true, false) => match sniffed {
+                Some((_, mime)) => mime,
+                None => mime_guess::from_path(&name).first_or_octet_stream().essence_str().to_string(),
+            },
+        },
+    }
+}
+
+// 读取到的目录列表
+struct DirectoryListing {
+    entries: Vec<FileEntry>,
+    modified: Option<std::time::SystemTime>,  // 目录本身与所有条目中最晚的修改时间
+    capped: bool,  // 递归搜索的结果达到上限，未遍历完
+}
+
+async fn get_directory_entries(
+    storage: &dyn Storage,
+    path: &Path,
+    root: &Path,
+    settings: &ListingSettings,
+    t: &UiStrings,
+) -> std::io::Result<DirectoryListing> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    // 只取实际读到的修改时间，读取失败的条目不参与，保证多次请求结果一致
+    let mut modified = storage.stat(path)?.modified;
+    
+    for entry in storage.list(path)? {
+        if !is_listed(&entry.name, settings)
+            || entry.metadata.as_ref().is_ok_and(|md| settings.hide.hides_metadata(md))
+            || settings.search.as_ref().is_some_and(|q| !entry.name.to_lowercase().contains(q.as_str()))
+        {
+            continue;
+        }
+        if let Some(time) = entry.metadata.as_ref().ok().and_then(|md| md.modified) {
+            modified = modified.max(Some(time));
+        }
+        let file_entry = build_entry(storage, path, entry.name, entry.metadata, settings, t);
+        if file_entry.is_dir {
+            dirs.push(file_entry);
+        } else {
+            files.push(file_entry);
+        }
+    }
+    
+    let entries = arrange_entries(dirs, files, path, root, settings, t);
+    Ok(DirectoryListing { entries, modified, capped: false })
+}
+
+// 排序并截断列表条目，不在顶层目录时在最前面加入返回上级的入口；返回条目与截断前的条目数
+fn arrange_entries(
+    mut dirs: Vec<FileEntry>,
+    mut files: Vec<FileEntry>,
+    path: &Path,
+    root: &Path,
+    settings: &ListingSettings,
+    t: &UiStrings,
+) -> Vec<FileEntry> {
+    let mut entries = Vec::new();
+    if settings.group_dirs_first {
+        sort_entries(&mut dirs, settings);
+        sort_entries(&mut files, settings);
+        entries.extend(dirs);
+        entries.extend(files);
+    } else {
+        // 目录与文件混合排序
+        entries.extend(dirs);
+        entries.extend(files);
+        sort_entries(&mut entries, settings);
+    }
+
+    // 解析符号链接与 . / .. 后再比较，与 cwd 的写法无关；无法解析时退回按路径组件比较
+    let at_root = match (fs::canonicalize(path), fs::canonicalize(root)) {
+        (Ok(path), Ok(root)) => path == root,
+        _ => path == root,
+    };
+    if path.parent().is_some() && !at_root {
+        entries.insert(0, FileEntry {
+            name: "..".to_string(),
+            display_name: t.parent_dir.to_string(),
+            size_string: "".to_string(),
+            size: 0,
+            modified_time: "".to_string(),
+            modified_iso: "".to_string(),
+            modified_secs: 0,
+            is_dir: true,
+            icon: "📁".to_string(),
+            preview_url: String::new(),
+            preview_deferred: false,
+            url: String::new(),
+            viewable: false,
+            accessible: true,
+            note: String::new(),
+            symlink_target: None,
+            permissions: String::new(),
+            downloads: 0,
+            file_type: String::new(),
+        });
+    }
+    entries
+}
+
+// 超大目录只保留排序后的前若干项（返回上级的入口不计），避免整页渲染占用过多内存，返回截断前的条目数。
+// 须在隐藏、分类等过滤之后调用，否则过滤后的结果只来自前若干项
+fn truncate_entries(entries: &mut Vec<FileEntry>, max_entries: usize) -> usize {
+    let parent = usize::from(entries.first().is_some_and(|e| e.name == ".."));
+    let total = entries.len() - parent;
+    if max_entries > 0 {
+        entries.truncate(parent + max_entries);
+    }
+    total
+}
+
+// 递归搜索最多返回的结果数，达到后停止遍历
+const MAX_SEARCH_RESULTS: usize = 500;
+
+// 在目录及其子目录中按名称搜索。结果条目的 name 为相对于起始目录的路径，页面上的相对链接可以直接使用；
+// 可见性规则按各自所在目录的设置判断，不进入投递箱目录，指向顶层目录之外的软链接既不列出也不进入
+struct DirectorySearch<'a> {
+    config: &'a Config,
+    storage: &'a dyn Storage,
+    protected: &'a ProtectedPaths,
+    root: &'a str,
+    bound: Option<PathBuf>,  // 规范化后的顶层目录
+    settings: &'a ListingSettings,
+    query: &'a str,
+    t: &'a UiStrings,
+    guard: WalkGuard,
+    dirs: Vec<FileEntry>,
+    files: Vec<FileEntry>,
+    capped: bool,
+}
+
+impl DirectorySearch<'_> {
+    fn walk(&mut self, dir: &Path, prefix: &str, depth: usize) {
+        if self.capped || is_write_only(self.root, dir) || !self.guard.enter(dir, depth) {
+            return;
+        }
+        let visibility = self.config.listing_settings(&load_dir_overrides(dir));
+        let Ok(entries) = self.storage.list(dir) else { return };
+        for entry in entries {
+            let path = dir.join(&entry.name);
+            if !is_listed(&entry.name, &visibility)
+                || self.protected.contains(&path)
+                || entry.metadata.as_ref().is_ok_and(|md| visibility.hide.hides_metadata(md))
+            {
+                continue;
+            }
+            let descend = match &entry.metadata {
+                Ok(md) if md.is_symlink => {
+                    if !self.inside_root(&path) {
+                        continue;
+                    }
+                    self.storage.stat(&path).is_ok_and(|target| target.is_dir)
+                }
+                Ok(md) => md.is_dir,
+                Err(_) => false,
+            };
+            let relative = format!("{}{}", prefix, entry.name);
+            if entry.name.to_lowercase().contains(self.query) {
+                if self.dirs.len() + self.files.len() >= MAX_SEARCH_RESULTS {
+                    self.capped = true;
+                    return;
+                }
+                let mut found = build_entry(self.storage, dir, entry.name, entry.metadata, self.settings, self.t);
+                if !found.preview_url.is_empty() {
+                    found.preview_url = found.preview_url.replacen("./", &format!("./{}", prefix), 1);
+                }
+                found.display_name = relative.clone();
+                found.name = relative.clone();
+                if found.is_dir {
+                    self.dirs.push(found);
+                } else {
+                    self.files.push(found);
+                }
+            }
+            if descend {
+                self.walk(&path, &format!("{}/", relative), depth + 1);
+            }
+        }
+    }
+
+    fn inside_root(&self, path: &Path) -> bool {
+        match (&self.bound, fs::canonicalize(path)) {
+            (Some(bound), Ok(target)) => target.starts_with(bound),
+            _ => false,
+        }
+    }
+}
+
+// 递归搜索 path 下名称包含 query（已转为小写）的条目，排序与截断规则与目录列表相同
+#[allow(clippy::too_many_arguments)]
+fn search_directory(
+    config: &Config,
+    storage: &dyn Storage,
+    protected: &ProtectedPaths,
+    path: &Path,
+    root: &str,
+    settings: &ListingSettings,
+    query: &str,
+    t: &UiStrings,
+) -> std::io::Result<DirectoryListing> {
+    // 起始目录本身无法读取时与普通列表一样返回错误
+    storage.list(path)?;
+    let mut search = DirectorySearch {
+        config,
+        storage,
+        protected,
+        root,
+        bound: fs::canonicalize(root).ok(),
+        settings,
+        query,
+        t,
+        guard: WalkGuard::new(config.max_recursion_depth),
+        dirs: Vec::new(),
+        files: Vec::new(),
+        capped: false,
+    };
+    search.walk(path, "", 0);
+    let capped = search.capped;
+    let entries = arrange_entries(search.dirs, search.files, path, Path::new(root), settings, t);
+    // 子目录中的变化不会反映到起始目录的修改时间上，搜索结果不提供 Last-Modified
+    Ok(DirectoryListing { entries, modified: None, capped })
+}
+
+// URL 路径段中需要编码的字符
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ').add(b'"').add(b'#').add(b'%').add(b'<').add(b'>')
+    .add(b'?').add(b'`').add(b'{').add(b'}').add(b'/');
+
+// 将相对路径逐段编码为 URL 路径
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| utf8_percent_encode(s, PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// 条目的直链：当前目录（请求中的形式，可能仍含百分号编码）与文件名拼接后重新编码
+fn entry_url(current_path: &str, name: &str) -> String {
+    let dir = percent_decode_str(current_path).decode_utf8_lossy();
+    format!("/{}", encode_path(&format!("{}/{}", dir, name)))
+}
+
+// 构建页面公共的模板上下文
+fn base_context(config: &Config, t: &UiStrings, current_path: &str) -> tera::Context {
+    let mut context = tera::Context::new();
+    context.insert("current_path", current_path);
+    context.insert("branding", &config.branding);
+    context.insert("pwa", &config.pwa);
+    context.insert("pwa_icon", config.pwa.touch_icon());
+    context.insert("t", t);
+    context
+}
+
+// 渲染 404 页面，cwd 下存在 404.html 时优先使用
+fn not_found_page(config: &Config, t: &UiStrings, path: &str) -> Result<HttpResponse> {
+    let custom_page = Path::new(&config.cwd).join("404.html");
+    if let Ok(body) = fs::read_to_string(&custom_page) {
+        return Ok(HttpResponse::NotFound()
+            .content_type("text/html; charset=utf-8")
+            .body(body));
+    }
+    error_page(config, t, path, StatusCode::NOT_FOUND, t.not_found_message)
+}
+
+// 渲染错误页面，提供返回上级与根目录的链接
+fn error_page(config: &Config, t: &UiStrings, path: &str, status: StatusCode, message: &str) -> Result<HttpResponse> {
+    let decoded = percent_decode_str(path).decode_utf8_lossy();
+    let trimmed = decoded.trim_end_matches('/');
+    let parent_url = match trimmed.rsplit_once('/') {
+        Some((parent, _)) if !parent.is_empty() => format!("/{}/", encode_path(parent)),
+        _ => "/".to_string(),
+    };
+
+    let mut context = base_context(config, t, trimmed);
+    context.insert("entries", &Vec::<FileEntry>::new());
+    context.insert("error_title", &format!("{} {}", status.as_u16(), status.canonical_reason().unwrap_or_default()));
+    context.insert("error_message", message);
+    context.insert("parent_url", &parent_url);
+
+    let rendered = match templates().render("index.html", &context) {
+        Ok(rendered) => rendered,
+        Err(e) => return Ok(render_error_response(config, t, &e)),
+    };
+
+    Ok(HttpResponse::build(status)
+        .content_type("text/html; charset=utf-8")
+        .body(rendered))
+}
+
+// 文件或目录存在但没有读取权限
+fn forbidden_page(config: &Config, t: &UiStrings, path: &str) -> Result<HttpResponse> {
+    error_page(config, t, path, StatusCode::FORBIDDEN, t.forbidden_message)
+}
+
+// 程序自身的数据文件（配置、PID、日志、去重内容），无论 cwd 如何设置都不允许通过网页访问；
+// 路径在启动时解析为绝对路径，与解析后的请求路径比较
+#[derive(Debug, Clone)]
+struct ProtectedPaths(Vec<PathBuf>);
+
+impl ProtectedPaths {
+    fn new(cwd: &str) -> Self {
+        let data_dir = fs::canonicalize("data").unwrap_or_else(|_| PathBuf::from("data"));
+        let mut paths: Vec<PathBuf> = ["config.yaml", "yunxi-webdisk.pid", "yunxi-webdisk.log", "blobs", "cache"]
+            .iter()
+            .map(|name| data_dir.join(name))
+            .collect();
+
+        // cwd 位于数据目录之外（如项目根目录）时，整个数据目录都不对外提供
+        let root = fs::canonicalize(cwd).unwrap_or_else(|_| PathBuf::from(cwd));
+        if !root.starts_with(&data_dir) {
+            paths.push(data_dir);
+        }
+        ProtectedPaths(paths)
+    }
+
+    // 解析符号链接后判断是否指向受保护的路径；尚不存在的路径（如重命名、复制的目标）
+    // 解析其所在目录后再拼回文件名，避免相对 cwd 下的原始路径绕过比较
+    fn contains(&self, path: &Path) -> bool {
+        let resolved = fs::canonicalize(path)
+            .or_else(|e| match (path.parent(), path.file_name()) {
+                (Some(parent), Some(name)) => fs::canonicalize(parent).map(|parent| parent.join(name)),
+                _ => Err(e),
+            })
+            .unwrap_or_else(|_| path.to_path_buf());
+        self.0.iter().any(|p| resolved.starts_with(p))
+    }
+
+    // 目录下是否可能包含受保护的路径，用于避免逐个条目解析
+    fn overlaps(&self, dir: &Path) -> bool {
+        match fs::canonicalize(dir) {
+            Ok(dir) => self.0.iter().any(|p| p.starts_with(&dir) || dir.starts_with(p)),
+            Err(_) => false,
+        }
+    }
+}
+
+// 规范化请求路径（解码后）：合并重复的 /，解析 . 与 ..；.. 越过根目录时返回 None
+fn normalize_request_path(path: &str) -> Option<String> {
+    let decoded = percent_decode_str(path).decode_utf8().ok()?;
+    let mut segments = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+    let mut normalized = format!("/{}", segments.join("/"));
+    // 末尾的 /、. 或 .. 都表示目录
+    let last = decoded.rsplit('/').next().unwrap_or("");
+    if !segments.is_empty() && matches!(last, "" | "." | "..") {
+        normalized.push('/');
+    }
+    Some(normalized)
+}
+
+// 301 重定向到规范路径（解码形式，末尾的 / 会保留），查询参数原样带上
+fn redirect_canonical(req: &HttpRequest, path: &str) -> HttpResponse {
+    let mut location = format!("/{}", encode_path(path));
+    if path.ends_with('/') && location != "/" {
+        location.push('/');
+    }
+    if !req.query_string().is_empty() {
+        location.push('?');
+        location.push_str(req.query_string());
+    }
+    HttpResponse::MovedPermanently()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+// 解析 COPY/MOVE 的 Destination 头（绝对 URL 或路径），返回挂载路径下规范化后的相对路径
+fn dav_destination(req: &HttpRequest, mount_prefix: &str) -> Option<PathBuf> {
+    let uri = req.headers().get("Destination")?.to_str().ok()?.parse::<actix_web::http::Uri>().ok()?;
+    let path = percent_decode_str(uri.path()).decode_utf8().ok()?;
+    let rel = path.strip_prefix(mount_prefix)?;
+    if !rel.is_empty() && !rel.starts_with('/') {
+        return None;
+    }
+    join_in_cwd("", rel)
+}
+
+// 将请求路径映射为 cwd 下的文件系统路径
+fn request_fs_path(config: &Config, path: &str) -> Option<PathBuf> {
+    join_in_cwd(&config.cwd, &percent_decode_str(path).decode_utf8().ok()?)
+}
+
+// 将已解码的相对路径拼接到 cwd 下
+fn join_in_cwd(cwd: &str, rel: &str) -> Option<PathBuf> {
+    // 只接受普通的路径段；.. 与绝对路径（如 //etc/passwd）会越出 cwd，按不存在处理
+    let mut full = PathBuf::from(cwd);
+    for component in Path::new(rel).components() {
+        match component {
+            std::path::Component::Normal(part) => full.push(part),
+            std::path::Component::RootDir | std::path::Component::CurDir => {}
+            std::path::Component::ParentDir | std::path::Component::Prefix(_) => return None,
+        }
+    }
+    Some(full)
+}
+
+// 按 actix-files 的规则计算文件的强 ETag
+fn file_etag(md: &fs::Metadata) -> Option<String> {
+    #[cfg(unix)]
+    let ino = std::os::unix::fs::MetadataExt::ino(md);
+    #[cfg(not(unix))]
+    let ino = 0;
+
+    let dur = md.modified().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(format!("\"{:x}:{:x}:{:x}:{:x}\"", ino, md.len(), dur.as_secs(), dur.subsec_nanos()))
+}
+
+// 判断 If-Range 是否与文件当前版本一致（ETag 需强匹配，日期需与修改时间相同）
+fn if_range_matches(value: &str, md: &fs::Metadata) -> bool {
+    let value = value.trim();
+    if value.starts_with("W/") {
+        return false;
+    }
+    if value.starts_with('"') {
+        return file_etag(md).as_deref() == Some(value);
+    }
+    match (value.parse::<header::HttpDate>(), md.modified()) {
+        (Ok(date), Ok(modified)) => {
+            let since: std::time::SystemTime = date.into();
+            let secs = |t: std::time::SystemTime| {
+                t.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).ok()
+            };
+            secs(since).is_some() && secs(since) == secs(modified)
+        }
+        _ => false,
+    }
+}
+
+// 每个客户端 IP 当前保持的连接数
+fn connections_per_ip() -> &'static std::sync::Mutex<std::collections::HashMap<std::net::IpAddr, usize>> {
+    static COUNTS: std::sync::OnceLock<std::sync::Mutex<std::collections::HashMap<std::net::IpAddr, usize>>> = std::sync::OnceLock::new();
+    COUNTS.get_or_init(Default::default)
+}
+
+// 建立连接时登记的占位，随连接关闭一起释放计数
+pub struct ConnectionSlot {
+    ip: std::net::IpAddr,
+    over_limit: bool,  // 建立时该 IP 的连接数已超过上限
+}
+
+impl ConnectionSlot {
+    pub fn acquire(ip: std::net::IpAddr, limit: usize) -> Self {
+        let mut counts = connections_per_ip().lock().unwrap_or_else(|e| e.into_inner());
+        let count = counts.entry(ip).or_insert(0);
+        *count += 1;
+        ConnectionSlot { ip, over_limit: *count > limit }
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        let mut counts = connections_per_ip().lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = counts.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.ip);
+            }
+        }
+    }
+}
+
+// 访问日志：请求处理完成后记录方法、路径、状态码与耗时，流式响应只计算到响应头发出为止
+async fn access_log(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = std::time::Instant::now();
+    let method = req.method().to_string();
+    let path = req.uri().to_string();
+    let addr = req.connection_info().realip_remote_addr().unwrap_or("-").to_string();
+    let res = next.call(req).await?;
+    tracing::info!(
+        event = "access",
+        addr,
+        method,
+        path,
+        status = res.status().as_u16(),
+        elapsed_ms = started.elapsed().as_millis() as u64,
+    );
+    Ok(res)
+}
+
+// 开启 web_auth 时检查网页请求的 Basic 认证，不通过时返回应答，通过时返回 None。
+// WebDAV 挂载路径由 webdav_handler 自行认证，健康检查与 CORS 预检请求不需要登录
+async fn web_auth_denial(req: &HttpRequest, config: &Config) -> Option<HttpResponse> {
+    let path = req.path();
+    if req.method() == actix_web::http::Method::OPTIONS || path == "/healthz" || is_webdav_path(path, config) {
+        return None;
+    }
+    let challenge = || HttpResponse::Unauthorized()
+        .append_header((header::WWW_AUTHENTICATE, "Basic realm=\"WebDAV Server\""))
+        .finish();
+    let Some((name, password)) = basic_credentials(req) else {
+        return Some(challenge());
+    };
+    let log_denied = |reason: &str| tracing::warn!(
+        event = "web_auth_denied",
+        addr = req.connection_info().realip_remote_addr().unwrap_or("-"),
+        user = name.as_str(),
+        method = %req.method(),
+        path,
+        reason,
+        "网页请求被拒绝",
+    );
+    let Some(user) = authenticate(config, &name, &password).await else {
+        log_denied(if config.webdav.users.contains_key(&name) { "invalid_password" } else { "unknown_user" });
+        return Some(challenge());
+    };
+    // 只有 w 权限的用户只能使用网页上传等写操作，不能浏览或下载（包括 POST /zip 打包下载）；
+    // 写操作的权限由各自的处理函数检查
+    let write_endpoint = req.method() == actix_web::http::Method::POST
+        && (["/upload/", "/delete/", "/mkdir/"].iter().any(|prefix| path.starts_with(prefix)) || path == "/rename");
+    if !write_endpoint && !user.permissions.contains('r') {
+        log_denied("read_permission");
+        return Some(HttpResponse::Forbidden().body("Read permission required"));
+    }
+    None
+}
+
+async fn web_auth_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    // 只在开启 web_auth 时注册，取不到配置时按未通过处理
+    let denial = match req.app_data::<web::Data<Config>>() {
+        Some(config) => web_auth_denial(req.request(), config).await,
+        None => Some(HttpResponse::InternalServerError().finish()),
+    };
+    if let Some(response) = denial {
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+// 超出单 IP 连接数上限的连接上的请求一律返回 503，并在响应后关闭连接
+async fn connection_limit_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    if req.conn_data::<ConnectionSlot>().is_some_and(|slot| slot.over_limit) {
+        let response = HttpResponse::ServiceUnavailable()
+            .force_close()
+            .body("Too many connections from this address");
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+// 边读取边压缩的响应体，每收到一块数据就输出已压缩好的部分
+struct GzipBody {
+    inner: BoxBody,
+    encoder: Option<flate2::write::GzEncoder<Vec<u8>>>,
+}
+
+impl MessageBody for GzipBody {
+    type Error = Box<dyn std::error::Error>;
+
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<web::Bytes, Self::Error>>> {
+        use std::io::Write;
+        use std::task::Poll;
+        let this = self.get_mut();
+        loop {
+            let Some(encoder) = this.encoder.as_mut() else {
+                return Poll::Ready(None);
+            };
+            match std::pin::Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    encoder.write_all(&chunk)?;
+                    let output = std::mem::take(encoder.get_mut());
+                    if !output.is_empty() {
+                        return Poll::Ready(Some(Ok(output.into())));
+                    }
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => {
+                    let output = this.encoder.take().map(|encoder| encoder.finish()).transpose()?;
+                    return Poll::Ready(output.map(|bytes| Ok(bytes.into())));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+// 按配置压缩响应：客户端接受 gzip、响应未编码、类型不在跳过列表且长度不低于阈值时才压缩
+async fn compress_response(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let settings = req.app_data::<web::Data<Config>>()
+        .map(|config| config.compression.clone())
+        .unwrap_or_default();
+    use actix_web::HttpMessage;
+    let accepts_gzip = req.get_header::<header::AcceptEncoding>()
+        .and_then(|accept| accept.negotiate([header::Encoding::gzip(), header::Encoding::identity()].iter()))
+        .is_some_and(|encoding| encoding == header::Encoding::gzip());
+    let path = req.path().to_string();
+    let res = next.call(req).await?.map_into_boxed_body();
+
+    let head = res.response().head();
+    let content_type = res.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok());
+    let eligible = settings.enabled
+        && head.status == StatusCode::OK
+        && !res.headers().contains_key(header::CONTENT_ENCODING)
+        && !settings.skips(&path, content_type)
+        && match res.response().body().size() {
+            BodySize::None => false,
+            BodySize::Sized(len) => len >= settings.min_size,
+            BodySize::Stream => true,
+        };
+    if !eligible {
+        return Ok(res);
+    }
+
+    let level = flate2::Compression::new(settings.level);
+    Ok(res.map_body(|head, body| {
+        head.headers.append(header::VARY, header::HeaderValue::from_static("accept-encoding"));
+        if !accepts_gzip {
+            return body;
+        }
+        head.headers.insert(header::CONTENT_ENCODING, header::HeaderValue::from_static("gzip"));
+        head.headers.remove(header::CONTENT_LENGTH);
+        BoxBody::new(GzipBody {
+            inner: body,
+            encoder: Some(flate2::write::GzEncoder::new(Vec::new(), level)),
+        })
+    }))
+}
+
+// Range 请求的 If-Range 与文件当前版本不一致时去掉 Range，返回完整内容
+async fn if_range_guard(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if req.headers().contains_key(header::RANGE) {
+        let if_range = req.headers()
+            .get(header::IF_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        if let (Some(if_range), Some(config)) = (if_range, req.app_data::<web::Data<Config>>()) {
+            let full_path = request_fs_path(config, req.match_info().query("path"));
+            let fresh = full_path.and_then(|p| fs::metadata(p).ok())
+                .map(|md| if_range_matches(&if_range, &md))
+                .unwrap_or(false);
+            if !fresh {
+                req.headers_mut().remove(header::RANGE);
+            }
+        }
+    }
+    next.call(req).await
+}
+
+// HEAD 请求与 GET 返回相同的响应头，响应体由 actix 自动省略
+#[actix_web::route(
+    "/{path:.*}",
+    method = "GET",
+    method = "HEAD",
+    wrap = "actix_web::middleware::from_fn(if_range_guard)"
+)]
+async fn index(
+    req: actix_web::HttpRequest,
+    config: web::Data<Config>,
+    protected: web::Data<ProtectedPaths>,
+    storage: web::Data<dyn Storage>,
+) -> Result<HttpResponse> {
+    let path = req.match_info().query("path").to_string();
+    let t = ui_strings(&config, &req);
+    let query = web::Query::<IndexQuery>::from_query(req.query_string())
+        .map(|q| q.into_inner())
+        .unwrap_or_default();
+
+    // 非规范路径统一重定向，保证页面中的相对链接按预期解析
+    if config.normalize_paths {
+        let Some(normalized) = normalize_request_path(&path) else {
+            return not_found_page(&config, t, &path);
+        };
+        if normalized != format!("/{}", percent_decode_str(&path).decode_utf8_lossy()) {
+            return Ok(redirect_canonical(&req, &normalized));
+        }
+    }
+    let Some(full_path) = request_fs_path(&config, &path) else {
+        return not_found_page(&config, t, &path);
+    };
+
+    // 受保护的数据文件按不存在处理，不暴露其存在
+    if protected.contains(&full_path) {
+        return not_found_page(&config, t, &path);
+    }
+    
+    match storage.stat(&full_path) {
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => forbidden_page(&config, t, &path),
+        Err(_) => not_found_page(&config, t, &path),
+        Ok(metadata) if metadata.is_dir && config.normalize_paths && !path.is_empty() && !path.ends_with('/') => {
+            let slashed = format!("/{}/", percent_decode_str(&path).decode_utf8_lossy());
+            Ok(redirect_canonical(&req, &slashed))
+        }
+        Ok(metadata) if !metadata.is_dir => {
+            if full_path.parent().is_some_and(|dir| is_write_only(&config.cwd, dir)) {
+                return forbidden_page(&config, t, &path);
+            }
+
+            // 默认内联显示，由浏览器根据 MIME 类型决定；dl=1 时强制下载
+            let untrusted_svg = !config.trust_svg && is_svg(&full_path);
+            let disposition_type = if query.dl.as_deref() == Some("1") || untrusted_svg {
+                DispositionType::Attachment
+            } else {
+                DispositionType::Inline
+            };
+
+            // 预览大文件时只读取开头或末尾的一段
+            if query.head.is_some() || query.tail.is_some() || query.raw.as_deref() == Some("1") {
+                let fallback = config.preview_charset.as_deref().and_then(|l| Encoding::for_label(l.as_bytes()));
+                return text_slice_response(storage.into_inner(), full_path, metadata.len, &query, fallback).await;
+            }
+
+            // 预览大图时返回缩略图，无法生成时返回原图
+            if query.thumb.as_deref() == Some("1") && is_thumbnailable(&full_path) {
+                if let Some(source) = storage.local_path(&full_path) {
+                    let thumbnail = web::block(move || thumbnail_image(&source, Path::new(IMAGE_CACHE_DIR)))
+                        .await
+                        .unwrap_or(None);
+                    if let Some(cached) = thumbnail {
+                        let file_name = full_path.file_name()
+                            .map(|n| n.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                        return Ok(NamedFile::open(&cached)?
+                            .set_content_disposition(header::ContentDisposition {
+                                disposition: DispositionType::Inline,
+                                parameters: vec![header::DispositionParam::Filename(file_name)],
+                            })
+                            .into_response(&req));
+                    }
+                }
+            }
+
+            // 只统计完整下载，断点续传的后续分段不重复计数
+            if config.columns.contains(&Column::Downloads)
+                && req.method() == actix_web::http::Method::GET
+                && !req.headers().contains_key(header::RANGE)
+            {
+                *download_counts().lock().unwrap().entry(full_path.clone()).or_insert(0) += 1;
+            }
+
+            // 图片转码：浏览器支持时返回缓存的 WebP/AVIF，转码失败或体积没有减小时返回原图
+            let transcodable = config.image_transcode
+                && matches!(disposition_type, DispositionType::Inline)
+                && is_transcodable_image(&full_path);
+            if transcodable {
+                let accept = req.headers().get(header::ACCEPT)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("");
+                let target = negotiate_image_format(query.fmt.as_deref(), accept);
+                let source = storage.local_path(&full_path);
+                if let (Some(target), Some(source)) = (target, source) {
+                    let converted = web::block(move || transcode_image(&source, target, Path::new(IMAGE_CACHE_DIR)))
+                        .await
+                        .unwrap_or(None);
+                    if let Some(cached) = converted {
+                        let file_name = full_path.with_extension(target.extension())
+                            .file_name()
+                            .map(|n| n.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                        let mut response = NamedFile::open(&cached)?
+                            .set_content_type(target.mime())
+                            .set_content_disposition(header::ContentDisposition {
+                                disposition: DispositionType::Inline,
+                                parameters: vec![header::DispositionParam::Filename(file_name)],
+                            })
+                            .into_response(&req);
+                        response.headers_mut().insert(header::VARY, header::HeaderValue::from_static("Accept"));
+                        return Ok(response);
+                    }
+                }
+            }
+
+            let cache_policy = full_path.file_name()
+                .and_then(|name| config.cache.file_policy(
+                    &name.to_string_lossy(),
+                    config.web_auth.enabled || req.headers().contains_key(header::AUTHORIZATION),
+                ));
+
+            let mut response = match storage.local_path(&full_path) {
+                None => stream_from_storage(storage.into_inner(), full_path, disposition_type),
+                Some(local_path) => {
+                    // 元数据可读但内容不可读（如权限为 000）时返回 403，而不是笼统的 500
+                    let file = match NamedFile::open(&local_path) {
+                        Ok(file) => file,
+                        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                            return forbidden_page(&config, t, &path);
+                        }
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                            return not_found_page(&config, t, &path);
+                        }
+                        Err(e) => return Err(e.into()),
+                    };
+                    let mut file = file.set_content_type(detect_content_type(&local_path));
+                    let mut disposition = file.content_disposition().clone();
+                    disposition.disposition = disposition_type;
+                    // 交给代理发送时只返回响应头，范围请求与条件请求由代理处理
+                    if let Some(accel) = config.proxy.accel_header(&config.cwd, &full_path) {
+                        HttpResponse::Ok()
+                            .content_type(file.content_type().clone())
+                            .insert_header((header::CONTENT_DISPOSITION, disposition))
+                            .insert_header(accel)
+                            .finish()
+                    } else {
+                        file = file.set_content_disposition(disposition);
+                        file.into_response(&req)
+                    }
+                }
+            };
+
+            // 与 ETag 配合：缓存过期后浏览器携带 If-None-Match 重新验证，未变化时返回 304
+            if let Some(value) = cache_policy.and_then(|p| header::HeaderValue::from_str(&p).ok()) {
+                response.headers_mut().insert(header::CACHE_CONTROL, value);
+            }
+            // 自动协商时同一地址可能返回不同格式，提示缓存按 Accept 区分
+            if transcodable && matches!(query.fmt.as_deref(), None | Some("auto")) {
+                response.headers_mut().insert(header::VARY, header::HeaderValue::from_static("Accept"));
+            }
+            if untrusted_svg {
+                response.headers_mut().insert(header::CONTENT_SECURITY_POLICY, header::HeaderValue::from_static(SVG_CSP));
+            }
+            Ok(response)
+        }
+        Ok(_) if query.stats.as_deref() == Some("1") => {
+            let recursive = query.recursive();
+            let dir = full_path.clone();
+            let (stats, truncated) = web::block(move || {
+                let mut stats = DirectoryStats::default();
+                let mut guard = WalkGuard::new(if recursive { config.max_recursion_depth } else { 0 });
+                collect_stats(&config, storage.get_ref(), &protected, &dir, 0, recursive, &mut guard, &mut stats);
+                (stats, guard.truncated)
+            }).await?;
+            Ok(HttpResponse::Ok()
+                .insert_header((header::CACHE_CONTROL, LISTING_CACHE_POLICY))
+                .json(serde_json::json!({
+                    "path": format!("/{}", percent_decode_str(&path).decode_utf8_lossy()),
+                    "recursive": recursive,
+                    "truncated": truncated,
+                    "files": stats.files,
+                    "dirs": stats.dirs,
+                    "total_size": stats.total_size,
+                    "categories": stats.categories,
+                })))
+        }
+        Ok(_) => {
+            let page = ListingPage { dir: &full_path, root: &config.cwd, current_path: &path, webdav: false, bookmarks: None };
+            render_listing(&req, &config, storage.into_inner(), &protected, &query, t, page).await
+        }
+    }
+}
+
+// 某一分类文件的数量与总大小
+#[derive(Debug, Default, Serialize)]
+struct CategoryStats {
+    count: u64,
+    total_size: u64,
+}
+
+// ?stats=1 返回的目录统计，只包含列表中可见的条目
+#[derive(Debug, Default)]
+struct DirectoryStats {
+    files: u64,
+    dirs: u64,
+    total_size: u64,
+    categories: BTreeMap<&'static str, CategoryStats>,
+}
+
+// 按与目录列表相同的可见性规则统计目录内容；不进入投递箱目录与指向目录的软链接
+#[allow(clippy::too_many_arguments)]
+fn collect_stats(
+    config: &Config,
+    storage: &dyn Storage,
+    protected: &ProtectedPaths,
+    dir: &Path,
+    depth: usize,
+    recursive: bool,
+    guard: &mut WalkGuard,
+    stats: &mut DirectoryStats,
+) {
+    if is_write_only(&config.cwd, dir) || !guard.enter(dir, depth) {
+        return;
+    }
+    let settings = config.listing_settings(&load_dir_overrides(dir));
+    let Ok(entries) = storage.list(dir) else { return };
+    for entry in entries {
+        let path = dir.join(&entry.name);
+        if !is_listed(&entry.name, &settings) || protected.contains(&path) {
+            continue;
+        }
+        // 列出目录时的元数据不跟随软链接，按链接目标统计
+        let md = match entry.metadata {
+            Ok(md) if md.is_symlink => storage.stat(&path).map(|target| StorageMetadata { is_symlink: true, ..target }),
+            md => md,
+        };
+        let Ok(md) = md else { continue };
+        if settings.hide.hides_metadata(&md) {
+            continue;
+        }
+        if md.is_dir {
+            stats.dirs += 1;
+            if recursive && !md.is_symlink {
+                collect_stats(config, storage, protected, &path, depth + 1, recursive, guard, stats);
+            }
+        } else {
+            stats.files += 1;
+            stats.total_size += md.len;
+            let category = stats.categories.entry(file_category(&entry.name).as_str()).or_default();
+            category.count += 1;
+            category.total_size += md.len;
+        }
+    }
+}
+
+// 目录页面对应的位置，网页浏览与浏览器访问 WebDAV 目录共用同一套列表页面
+struct ListingPage<'a> {
+    dir: &'a Path,
+    root: &'a str,          // 顶层目录，到达后不再显示返回上级
+    current_path: &'a str,  // 请求中的目录路径（相对于 root，可能仍含百分号编码）
+    webdav: bool,           // 由 WebDAV 挂载路径访问，打包下载等按 cwd 定位的功能不可用
+    bookmarks: Option<&'a [String]>,  // 已登录用户的书签，None 时不显示书签栏
+}
+
+// 书签栏中的一项
+#[derive(Serialize)]
+struct BookmarkLink {
+    name: String,
+    url: String,
+}
+
+// 面包屑导航中的一级
+#[derive(Serialize)]
+struct Breadcrumb {
+    name: String,
+    url: String,
+}
+
+// 将当前目录拆分为逐级的链接，首项为顶层目录；空路径段（多余或结尾的 /）忽略，链接逐段编码并以 / 结尾
+fn breadcrumbs(mount: &str, current_path: &str, root_label: &str) -> Vec<Breadcrumb> {
+    let decoded = percent_decode_str(current_path).decode_utf8_lossy();
+    let mut url = format!("{}/", mount);
+    let mut crumbs = vec![Breadcrumb { name: root_label.to_string(), url: url.clone() }];
+    for part in decoded.split('/').filter(|p| !p.is_empty()) {
+        url.push_str(&utf8_percent_encode(part, PATH_SEGMENT).to_string());
+        url.push('/');
+        crumbs.push(Breadcrumb { name: part.to_string(), url: url.clone() });
+    }
+    crumbs
+}
+
+// 渲染目录列表页面
+async fn render_listing(
+    req: &HttpRequest,
+    config: &web::Data<Config>,
+    storage: Arc<dyn Storage>,
+    protected: &ProtectedPaths,
+    query: &IndexQuery,
+    t: &'static UiStrings,
+    page: ListingPage<'_>,
+) -> Result<HttpResponse> {
+    let ListingPage { dir: full_path, root, current_path: path, webdav, bookmarks } = page;
+    let mut settings = config.listing_settings(&load_dir_overrides(full_path));
+    let set_cookies = apply_listing_choices(req, query, &mut settings);
+    let search = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    settings.search = search.map(str::to_lowercase);
+    let recursive = search.is_some() && query.recursive();
+    let listing = match &settings.search {
+        // 递归搜索会遍历整个子目录树，放到线程池中执行，不阻塞处理其他请求的工作线程
+        Some(q) if recursive => {
+            let (config, storage, protected) = (config.clone(), storage.clone(), protected.clone());
+            let (dir, root, settings, q) = (full_path.to_path_buf(), root.to_string(), settings.clone(), q.clone());
+            web::block(move || search_directory(&config, storage.as_ref(), &protected, &dir, &root, &settings, &q, t)).await?
+        }
+        _ => get_directory_entries(storage.as_ref(), full_path, Path::new(root), &settings, t).await,
+    };
+    let DirectoryListing { mut entries, modified, capped } = match listing {
+        Ok(listing) => listing,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            return forbidden_page(config, t, path);
+        }
+        Err(_) => return not_found_page(config, t, path),
+    };
+    if protected.overlaps(full_path) {
+        entries.retain(|e| e.name == ".." || !protected.contains(&full_path.join(&e.name)));
+    }
+    // 投递箱目录只显示返回上级的入口
+    let write_only = is_write_only(root, full_path);
+    if write_only {
+        entries.retain(|e| e.name == "..");
+    }
+
+    // 目录内容没有变化时返回 304，轮询的客户端无需重新获取整个页面；投递箱目录不透露修改时间
+    let last_modified = modified.filter(|_| !write_only).map(header::HttpDate::from);
+    if let Some(last_modified) = last_modified {
+        if not_modified_since(req, last_modified) {
+            return Ok(HttpResponse::NotModified()
+                .insert_header((header::LAST_MODIFIED, last_modified.to_string()))
+                .insert_header((header::CACHE_CONTROL, LISTING_CACHE_POLICY))
+                .insert_header((header::VARY, LISTING_VARY))
+                .finish());
+        }
+    }
+    // WebDAV 页面中的直链指向挂载路径下的地址
+    let mount = if webdav { config.webdav.mount_prefix() } else { "" };
+    for entry in entries.iter_mut().filter(|e| e.accessible && e.name != "..") {
+        entry.url = format!("{}{}", mount, entry_url(path, &entry.name));
+    }
+
+    // 音视频文件占多数时显示生成播放列表的按钮
+    let file_count = entries.iter().filter(|e| !e.is_dir).count();
+    let media_count = entries.iter()
+        .filter(|e| !e.is_dir && matches!(file_category(&e.name), Category::Audio | Category::Video))
+        .count();
+    let show_playlist = !webdav && media_count > 0 && media_count * 2 >= file_count;
+
+    // 按分类过滤，仅保留匹配的文件和返回上级入口
+    let filter = query.file_type.as_deref().and_then(Category::from_param);
+    if let Some(category) = filter {
+        entries.retain(|e| e.name == ".." || (!e.is_dir && file_category(&e.name) == category));
+    }
+    let total = truncate_entries(&mut entries, settings.max_entries);
+    let filters: Vec<FilterChip> = Category::FILTERABLE.iter()
+        .map(|&c| FilterChip {
+            key: c.as_str(),
+            label: t.category_label(c),
+            active: filter == Some(c),
+        })
+        .collect();
+
+    let streaming = entries.len() > config.stream_threshold;
+    let entry_count = entries.iter().filter(|e| e.name != "..").count();
+    let mut context = base_context(config, t, path);
+    context.insert("entry_count", &entry_count);
+    context.insert("is_empty", &(entry_count == 0));
+    // 允许写入时在空目录提示通过 WebDAV 上传；投递箱目录在 WebDAV 与网页共用根目录时、以及 WebDAV 页面直接给出该目录的地址
+    if config.webdav.enabled && !config.webdav.read_only {
+        let conn = req.connection_info();
+        let here = webdav || (write_only && config.webdav.root.is_none());
+        let dir = if here { encode_path(&percent_decode_str(path).decode_utf8_lossy()) } else { String::new() };
+        let dir = if dir.is_empty() { dir } else { format!("{}/", dir) };
+        context.insert("upload_url", &format!("{}://{}{}/{}", conn.scheme(), conn.host(), config.webdav.mount_prefix(), dir));
+    }
+    context.insert("write_only", &write_only);
+    context.insert("webdav", &webdav);
+    context.insert("breadcrumbs", &breadcrumbs(mount, path, t.root_crumb));
+    if config.allow_web_mkdir && !webdav {
+        context.insert("mkdir_action", &format!("/mkdir/{}", encode_path(&percent_decode_str(path).decode_utf8_lossy())));
+    }
+    if config.upload.web_form && !webdav {
+        context.insert("upload_action", &format!("/upload/{}", encode_path(&percent_decode_str(path).decode_utf8_lossy())));
+        context.insert("upload_progress", &config.upload.progress_events);
+    }
+    // 书签链接指向挂载路径下的地址，已删除的目录不再显示
+    if let Some(bookmarks) = bookmarks {
+        let links: Vec<BookmarkLink> = bookmarks.iter()
+            .filter(|p| Path::new(root).join(p).is_dir())
+            .map(|p| BookmarkLink {
+                name: p.rsplit('/').next().filter(|n| !n.is_empty()).unwrap_or("/").to_string(),
+                url: match encode_path(p).as_str() {
+                    "" => format!("{}/", mount),
+                    encoded => format!("{}/{}/", mount, encoded),
+                },
+            })
+            .collect();
+        let here = percent_decode_str(path).decode_utf8_lossy();
+        context.insert("show_bookmarks", &true);
+        context.insert("bookmarks", &links);
+        context.insert("bookmarked", &bookmarks.iter().any(|p| p == here.trim_matches('/')));
+    }
+    if settings.max_entries > 0 && total > settings.max_entries && !write_only {
+        let notice = t.listing_truncated
+            .replace("{shown}", &settings.max_entries.to_string())
+            .replace("{total}", &total.to_string());
+        context.insert("truncated_notice", &notice);
+    } else if capped && !write_only {
+        context.insert("truncated_notice", &t.search_truncated.replace("{shown}", &MAX_SEARCH_RESULTS.to_string()));
+    }
+    context.insert("filters", &filters);
+    context.insert("filter_active", &filter.is_some());
+    context.insert("filter_key", &filter.map(Category::as_str).unwrap_or_default());
+    context.insert("sort_options", &sort_chips(&settings, t));
+    // 切换分类、排序或显示方式时保留当前的搜索词与分类过滤
+    let search_query = search
+        .map(|q| format!("&q={}{}", utf8_percent_encode(q, NON_ALPHANUMERIC), if recursive { "&recursive=true" } else { "" }))
+        .unwrap_or_default();
+    context.insert("search", &search.unwrap_or_default());
+    context.insert("search_recursive", &recursive);
+    context.insert("search_query", &search_query);
+    context.insert("filter_query", &format!("{}{}", filter.map(|c| format!("&type={}", c.as_str())).unwrap_or_default(), search_query));
+    context.insert("streaming", &streaming);
+    context.insert("settings", &settings);
+    context.insert("show_playlist", &show_playlist);
+    if config.readme.enabled && !write_only {
+        context.insert("directory_readme", &render_readme(storage.as_ref(), full_path, &config.readme.files, protected));
+    }
+    // 按分类过滤或搜索时新增的文件不一定符合条件，不订阅实时更新
+    context.insert("live_updates", &(config.live_updates && !webdav && filter.is_none() && search.is_none()));
+
+    let mut response = if streaming {
+        stream_listing(context, entries, t, path.to_string(), settings, webdav, config)?
+    } else {
+        context.insert("entries", &entries);
+        match templates().render("index.html", &context) {
+            Ok(rendered) => HttpResponse::Ok()
+                .content_type("text/html; charset=utf-8")
+                .insert_header((header::CACHE_CONTROL, LISTING_CACHE_POLICY))
+                .body(rendered),
+            Err(e) => return Ok(render_error_response(config, t, &e)),
+        }
+    };
+    for cookie in set_cookies {
+        if let Ok(value) = header::HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+    if let Some(value) = last_modified.and_then(|date| header::HeaderValue::from_str(&date.to_string()).ok()) {
+        response.headers_mut().insert(header::LAST_MODIFIED, value);
+    }
+    response.headers_mut().append(header::VARY, header::HeaderValue::from_static(LISTING_VARY));
+    Ok(response)
+}
+
+// If-Modified-Since 不早于目录的修改时间时返回 true；HTTP 日期精确到秒，比较前同样截断。
+// 请求带有 If-None-Match 时按规范忽略 If-Modified-Since，目录列表没有 ETag，直接按已修改处理
+fn not_modified_since(req: &HttpRequest, modified: header::HttpDate) -> bool {
+    if req.headers().contains_key(header::IF_NONE_MATCH) {
+        return false;
+    }
+    req.headers().get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<header::HttpDate>().ok())
+        .is_some_and(|since| {
+            let secs = |date: header::HttpDate| std::time::SystemTime::from(date)
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            secs(modified) <= secs(since)
+        })
+}
+
+// 说明文件最多读取的字节数，超出部分不显示
+const MAX_README_BYTES: u64 = 256 * 1024;
+
+// 将 Markdown 转换为 HTML；原始 HTML 与 javascript: 链接按文本处理，避免说明文件注入脚本
+fn markdown_to_html(text: &str) -> String {
+    use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+
+    // 只保留 http、https、mailto 与相对地址（含 #锚点），其他协议一律替换为 #。
+    // 浏览器解析前会去掉地址中的制表符、换行与开头的空白，判断协议前同样去掉
+    fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+        let cleaned: String = url.trim_start_matches(|c: char| c.is_ascii_control() || c == ' ')
+            .chars()
+            .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+            .collect();
+        let scheme = cleaned.split_once(':')
+            .map(|(scheme, _)| scheme)
+            .filter(|scheme| !scheme.contains(['/', '?', '#']));
+        match scheme {
+            Some(scheme) if !["http", "https", "mailto"].iter().any(|s| scheme.eq_ignore_ascii_case(s)) => {
+                CowStr::Borrowed("#")
+            }
+            _ => url,
+        }
+    }
+
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let parser = Parser::new_ext(text, options).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+        Event::Start(Tag::Link { link_type, dest_url, title, id }) => {
+            Event::Start(Tag::Link { link_type, dest_url: safe_url(dest_url), title, id })
+        }
+        Event::Start(Tag::Image { link_type, dest_url, title, id }) => {
+            Event::Start(Tag::Image { link_type, dest_url: safe_url(dest_url), title, id })
+        }
+        other => other,
+    });
+    let mut out = String::new();
+    html::push_html(&mut out, parser);
+    out
+}
+
+// 查找并渲染目录中的说明文件，Markdown 转为 HTML，其他文本原样放入 <pre>
+fn render_readme(storage: &dyn Storage, dir: &Path, files: &[String], protected: &ProtectedPaths) -> Option<String> {
+    let path = files.iter()
+        .map(|name| dir.join(name))
+        .find(|path| !protected.contains(path) && storage.stat(path).is_ok_and(|md| !md.is_dir))?;
+
+    let mut buf = Vec::new();
+    storage.open_read(&path).ok()?
+        .take(MAX_README_BYTES)
+        .read_to_end(&mut buf)
+        .ok()?;
+    let text = String::from_utf8_lossy(&buf);
+
+    let is_markdown = path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"));
+    Some(if is_markdown {
+        markdown_to_html(&text)
+    } else {
+        format!("<pre>{}</pre>", tera::escape_html(&text))
+    })
+}
+
+// 模板渲染失败时返回不依赖模板的错误页面，完整错误写入日志
+fn render_error_response(config: &Config, t: &UiStrings, e: &tera::Error) -> HttpResponse {
+    // Tera 的错误信息分多层，逐层拼接才能看到具体原因
+    let mut detail = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        detail.push_str(": ");
+        detail.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    tracing::error!(event = "template_error", error = %detail, "模板渲染失败");
+
+    let message = if config.debug {
+        format!("<pre>{}</pre>", tera::escape_html(&detail))
+    } else {
+        format!("<p>{}</p>", t.render_error_message)
+    };
+    HttpResponse::InternalServerError()
+        .content_type("text/html; charset=utf-8")
+        .body(RENDER_ERROR_PAGE
+            .replace("{lang}", t.lang)
+            .replace("{title}", t.render_error_title)
+            .replace("{message}", &message))
+}
+
+// 供自行渲染模板的调用方在失败时返回同样的错误页面
+pub fn template_error_response(config: &Config, req: &HttpRequest, e: &tera::Error) -> HttpResponse {
+    render_error_response(config, ui_strings(config, req), e)
+}
+
+// 模板渲染失败时使用的静态页面
+const RENDER_ERROR_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="{lang}">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>500 {title}</title>
+    <style>
+        body { font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif; max-width: 720px; margin: 60px auto; padding: 0 20px; color: #333; }
+        .error-box { border: 1px solid #f5c6cb; background-color: #fff5f5; border-radius: 4px; padding: 20px; }
+        h2 { margin-top: 0; color: #c0392b; }
+        pre { white-space: pre-wrap; word-break: break-all; font-size: 13px; }
+        a { color: #2196F3; }
+    </style>
+</head>
+<body>
+    <div class="error-box">
+        <h2>500 {title}</h2>
+        {message}
+        <a href="/">/</a>
+    </div>
+</body>
+</html>
+"#;
+
+// 渲染单个列表条目的 HTML 片段
+fn render_entry(entry: &FileEntry, t: &UiStrings, current_path: &str, settings: &ListingSettings, webdav: bool) -> tera::Result<String> {
+    let mut context = tera::Context::new();
+    context.insert("t", t);
+    context.insert("settings", settings);
+    context.insert("current_path", current_path);
+    context.insert("webdav", &webdav);
+    context.insert("entry", entry);
+    templates().render("entry.html", &context)
+}
+
+// 流式输出目录列表：先发送页头，再逐条渲染条目，最后发送页脚
+fn stream_listing(
+    mut context: tera::Context,
+    entries: Vec<FileEntry>,
+    t: &'static UiStrings,
+    current_path: String,
+    settings: ListingSettings,
+    webdav: bool,
+    config: &Config,
+) -> Result<HttpResponse> {
+    context.insert("entries", &Vec::<FileEntry>::new());
+    let page = match templates().render("index.html", &context) {
+        Ok(page) => page,
+        Err(e) => return Ok(render_error_response(config, t, &e)),
+    };
+    let (head, tail) = page.split_once(ENTRIES_MARKER)
+        .map(|(h, t)| (h.to_string(), t.to_string()))
+        .unwrap_or((page, String::new()));
+
+    let rows = entries.into_iter().map(move |entry| {
+        render_entry(&entry, t, &current_path, &settings, webdav)
+            .map(web::Bytes::from)
+            .map_err(|e| Error::from(std::io::Error::other(e)))
+    });
+
+    let body = futures_util::stream::iter(
+        std::iter::once(Ok(web::Bytes::from(head)))
+            .chain(rows)
+            .chain(std::iter::once(Ok(web::Bytes::from(tail))))
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .insert_header((header::CACHE_CONTROL, LISTING_CACHE_POLICY))
+        .streaming(body))
+}
+
+// 目录的唯一标识（设备号与 inode），用于发现通过挂载或链接形成的环路
+#[cfg(unix)]
+type DirId = (u64, u64);
+
+#[cfg(unix)]
+fn dir_id(path: &Path) -> Option<DirId> {
+    use std::os::unix::fs::MetadataExt;
+    let md = fs::metadata(path).ok()?;
+    Some((md.dev(), md.ino()))
+}
+
+// 其他平台退回使用解析后的绝对路径
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+#[cfg(not(unix))]
+fn dir_id(path: &Path) -> Option<DirId> {
+    fs::canonicalize(path).ok()
+}
+
+// 递归遍历目录时的深度限制与环路保护，所有递归操作共用
+struct WalkGuard {
+    max_depth: usize,
+    visited: HashSet<DirId>,
+    truncated: bool,  // 是否因超过深度或遇到环路而跳过了部分目录
+}
+
+impl WalkGuard {
+    fn new(max_depth: usize) -> Self {
+        WalkGuard { max_depth, visited: HashSet::new(), truncated: false }
+    }
+
+    // 进入目录前调用，返回 false 时应跳过该目录的内容
+    fn enter(&mut self, path: &Path, depth: usize) -> bool {
+        if depth > self.max_depth {
+            self.truncated = true;
+            return false;
+        }
+        match dir_id(path) {
+            Some(id) if self.visited.insert(id) => true,
+            _ => {
+                self.truncated = true;
+                false
+            }
+        }
+    }
+}
+
+const ZIP_TRUNCATED_COMMENT: &str = "部分目录超过最大递归深度或存在循环链接，未被打包";
+
+// 将相对路径解析到 cwd 下，路径不存在或越界时返回 None
+fn resolve_in_cwd(cwd: &str, rel: &str) -> Option<PathBuf> {
+    let root = fs::canonicalize(cwd).ok()?;
+    let full = fs::canonicalize(root.join(rel.trim_start_matches('/'))).ok()?;
+    full.starts_with(&root).then_some(full)
+}
+
+// 打包下载请求，names 为 JSON 编码的文件名数组
+#[derive(Debug, Deserialize)]
+struct ZipRequest {
+    path: String,
+    names: String,
+}
+
+// 将写入的数据经通道转发给响应流
+struct ChannelWriter(mpsc::Sender<std::io::Result<web::Bytes>>);
+
+impl std::io::Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.blocking_send(Ok(web::Bytes::copy_from_slice(buf)))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "客户端已断开连接"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// head/tail/raw 预览单次最多返回的大小（KB）
+const MAX_SLICE_KB: u64 = 16 * 1024;
+
+// 读取文件中 [start, start + len) 的内容，本地文件直接定位，其他后端跳过前面的数据
+fn read_slice(storage: &dyn Storage, path: &Path, start: u64, len: u64) -> std::io::Result<Vec<u8>> {
+    // 按文件实际剩余的大小预留空间，小文件不会按单次上限分配内存
+    let remaining = storage.stat(path).map_or(0, |md| md.len.saturating_sub(start));
+    let mut buf = Vec::with_capacity(len.min(remaining) as usize);
+    match storage.local_path(path) {
+        Some(local) => {
+            let mut file = fs::File::open(local)?;
+            file.seek(std::io::SeekFrom::Start(start))?;
+            file.take(len).read_to_end(&mut buf)?;
+        }
+        None => {
+            let mut reader = storage.open_read(path)?;
+            std::io::copy(&mut (&mut reader).take(start), &mut std::io::sink())?;
+            reader.take(len).read_to_end(&mut buf)?;
+        }
+    }
+    Ok(buf)
+}
+
+// 去掉切片两端被截断的 UTF-8 字符，返回丢弃的开头字节数
+fn trim_utf8_boundaries(buf: &mut Vec<u8>) -> usize {
+    // 开头最多有 3 个延续字节（0b10xxxxxx）属于上一个字符
+    let skip = buf.iter().take(3).take_while(|&&b| b & 0xC0 == 0x80).count();
+    buf.drain(..skip);
+    // 末尾不完整的字符在 from_utf8 中表现为 error_len() 为 None 的错误
+    if let Err(e) = std::str::from_utf8(buf) {
+        if e.error_len().is_none() {
+            buf.truncate(e.valid_up_to());
+        }
+    }
+    skip
+}
+
+// 以纯文本返回文件开头（head）、末尾（tail）的 N KB 或整个文件（raw），响应头注明切片在文件中的位置；
+// 非 UTF-8 编码的内容转换为 UTF-8 后返回
+async fn text_slice_response(
+    storage: Arc<dyn Storage>,
+    path: PathBuf,
+    size: u64,
+    query: &IndexQuery,
+    fallback: Option<&'static Encoding>,
+) -> Result<HttpResponse> {
+    let (start, len) = match (query.head, query.tail) {
+        (Some(_), Some(_)) => return Ok(HttpResponse::BadRequest().body("head 与 tail 不能同时使用")),
+        (Some(kb), None) => (0, kb.min(MAX_SLICE_KB) * 1024),
+        (None, Some(kb)) => {
+            let len = (kb.min(MAX_SLICE_KB) * 1024).min(size);
+            (size - len, len)
+        }
+        (None, None) => (0, size.min(MAX_SLICE_KB * 1024)),
+    };
+    let requested = match query.charset.as_deref() {
+        None => None,
+        Some(label) => match Encoding::for_label(label.as_bytes()) {
+            Some(encoding) => Some(encoding),
+            None => return Ok(HttpResponse::BadRequest().body(format!("未知的字符集 {}", label))),
+        },
+    };
+
+    let (text, encoding, offset, len) = web::block(move || {
+        let buf = read_slice(storage.as_ref(), &path, start, len)?;
+        let mut trimmed = buf.clone();
+        let skipped = trim_utf8_boundaries(&mut trimmed);
+        let encoding = requested.unwrap_or_else(|| {
+            if std::str::from_utf8(&trimmed).is_ok() { UTF_8 } else { guess_charset(&buf, fallback) }
+        });
+        // 只有 UTF-8 能可靠地对齐字符边界，其他编码在切片两端可能出现一个无法解码的字符
+        let (buf, offset) = if encoding == UTF_8 { (trimmed, start + skipped as u64) } else { (buf, start) };
+        let text = encoding.decode_without_bom_handling(&buf).0.into_owned();
+        Ok::<_, std::io::Error>((text, encoding, offset, buf.len()))
+    })
+    .await??;
+
+    Ok(HttpResponse::Ok()
+        .content_type(mime::TEXT_PLAIN_UTF_8)
+        .insert_header(("X-Slice-Offset", offset.to_string()))
+        .insert_header(("X-Slice-Length", len.to_string()))
+        .insert_header(("X-File-Size", size.to_string()))
+        .insert_header(("X-Source-Charset", encoding.name()))
+        .body(text))
+}
+
+// 猜测非 UTF-8 文本的编码；猜测结果不可靠且配置了默认字符集时使用默认字符集
+fn guess_charset(buf: &[u8], fallback: Option<&'static Encoding>) -> &'static Encoding {
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(buf, true);
+    match detector.guess_assess(None, true) {
+        (encoding, true) => encoding,
+        (encoding, false) => fallback.unwrap_or(encoding),
+    }
+}
+
+// 非本地存储后端的文件无法使用 NamedFile，经通道流式输出
+fn stream_from_storage(storage: Arc<dyn Storage>, path: PathBuf, disposition: DispositionType) -> HttpResponse {
+    let file_name = path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let content_type = mime_guess::from_path(&path).first_or_octet_stream();
+
+    let (tx, rx) = mpsc::channel::<std::io::Result<web::Bytes>>(16);
+    actix_web::rt::task::spawn_blocking(move || {
+        let result = storage.open_read(&path)
+            .and_then(|mut reader| std::io::copy(&mut reader, &mut ChannelWriter(tx)));
+        if let Err(e) = result {
+            tracing::error!(event = "read_failed", path = %path.display(), error = %e, "读取文件失败");
+        }
+    });
+
+    let body = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(header::ContentDisposition {
+            disposition,
+            parameters: vec![header::DispositionParam::Filename(file_name)],
+        })
+        .streaming(body)
+}
+
+// 将文件或目录（递归）写入 ZIP，不跟随指向目录的软链接
+fn zip_add_path<W: std::io::Write>(
+    zip: &mut ZipWriter<StreamWriter<W>>,
+    path: &Path,
+    name: &str,
+    protected: &ProtectedPaths,
+    guard: &mut WalkGuard,
+    depth: usize,
+) -> zip::result::ZipResult<()> {
+    if protected.contains(path) {
+        return Ok(());
+    }
+    let metadata = fs::metadata(path)?;
+    if metadata.is_dir() {
+        if fs::symlink_metadata(path)?.file_type().is_symlink() || load_dir_overrides(path).write_only {
+            return Ok(());
+        }
+        if !guard.enter(path, depth) {
+            return Ok(());
+        }
+        zip.add_directory(format!("{}/", name), SimpleFileOptions::default())?;
+        let mut children: Vec<_> = fs::read_dir(path)?.flatten().collect();
+        children.sort_by_key(|e| e.file_name());
+        for child in children {
+            let child_name = format!("{}/{}", name, child.file_name().to_string_lossy());
+            zip_add_path(zip, &child.path(), &child_name, protected, guard, depth + 1)?;
+        }
+    } else {
+        let options = SimpleFileOptions::default().large_file(metadata.len() > u32::MAX as u64);
+        zip.start_file(name, options)?;
+        std::io::copy(&mut fs::File::open(path)?, zip)?;
+    }
+    Ok(())
+}
+
+// 将选中的文件和目录打包为 ZIP 流式下载
+#[actix_web::post("/zip")]
+async fn zip_download(
+    form: web::Form<ZipRequest>,
+    config: web::Data<Config>,
+    protected: web::Data<ProtectedPaths>,
+) -> Result<HttpResponse> {
+    let rel = percent_decode_str(&form.path).decode_utf8_lossy().to_string();
+    let dir = match resolve_in_cwd(&config.cwd, &rel) {
+        Some(dir) if dir.is_dir() => dir,
+        _ => return Ok(HttpResponse::NotFound().body("目录不存在")),
+    };
+    if is_write_only(&config.cwd, &dir) {
+        return Ok(HttpResponse::Forbidden().body("此目录只接收上传"));
+    }
+
+    let names: Vec<String> = serde_json::from_str(&form.names)
+        .map_err(actix_web::error::ErrorBadRequest)?;
+    let mut targets = Vec::new();
+    for name in names {
+        // 只接受当前目录下的单个文件名
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Ok(HttpResponse::BadRequest().body("无效的文件名"));
+        }
+        match resolve_in_cwd(&config.cwd, &format!("{}/{}", rel, name)) {
+            Some(target) if !protected.contains(&target) => targets.push((target, name)),
+            _ => return Ok(HttpResponse::NotFound().body("文件不存在")),
+        }
+    }
+    if targets.is_empty() {
+        return Ok(HttpResponse::BadRequest().body("未选择任何文件"));
+    }
+
+    let archive_name = dir.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "download".to_string());
+
+    let protected = protected.into_inner();
+    let mut guard = WalkGuard::new(config.max_recursion_depth);
+    let (tx, rx) = mpsc::channel::<std::io::Result<web::Bytes>>(16);
+    actix_web::rt::task::spawn_blocking(move || {
+        let writer = std::io::BufWriter::with_capacity(64 * 1024, ChannelWriter(tx));
+        let mut zip = ZipWriter::new_stream(writer);
+        let result = targets.iter()
+            .try_for_each(|(path, name)| zip_add_path(&mut zip, path, name, &protected, &mut guard, 0))
+            .and_then(|_| {
+                // 响应头已经发出，通过压缩包注释说明内容不完整
+                if guard.truncated {
+                    zip.set_comment(ZIP_TRUNCATED_COMMENT)?;
+                }
+                zip.finish().map(|_| ())
+            });
+        if let Err(e) = result {
+            tracing::error!(event = "zip_failed", error = %e, "打包下载失败");
+        }
+    });
+
+    let body = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .append_header((header::ACCEPT_RANGES, "none"))
+        .append_header((
+            header::CONTENT_DISPOSITION,
+            header::ContentDisposition::attachment(format!("{}.zip", archive_name)),
+        ))
+        .streaming(body))
+}
+
+// 路径是否位于已启用的 WebDAV 挂载路径之下
+fn is_webdav_path(path: &str, config: &Config) -> bool {
+    let prefix = config.webdav.mount_prefix();
+    config.webdav.enabled
+        && (prefix.is_empty() || path == prefix || path.starts_with(&format!("{}/", prefix)))
+}
+
+// 计算指定路径支持的请求方法
+fn allowed_methods(path: &str, config: &Config) -> String {
+    let methods: &[&str] = if is_webdav_path(path, config) {
+        if config.webdav.locking {
+            &["OPTIONS", "GET", "HEAD", "PUT", "DELETE", "COPY", "MOVE", "MKCOL",
+              "PROPFIND", "PROPPATCH", "LOCK", "UNLOCK"]
+        } else {
+            &["OPTIONS", "GET", "HEAD", "PUT", "DELETE", "COPY", "MOVE", "MKCOL",
+              "PROPFIND", "PROPPATCH"]
+        }
+    } else if path == "/zip" {
+        &["OPTIONS", "POST"]
+    } else {
+        &["OPTIONS", "GET", "HEAD"]
+    };
+    methods.join(", ")
+}
+
+// 统一应答 OPTIONS 请求，通过 Allow 头告知路径支持的方法
+#[actix_web::route("/{path:.*}", method = "OPTIONS")]
+async fn options_handler(req: HttpRequest, config: web::Data<Config>) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    response.append_header((header::ALLOW, allowed_methods(req.path(), &config)));
+    // WebDAV 客户端（Windows 资源管理器、rclone 等）依据 DAV 头判断服务器能力，
+    // 只有启用锁管理时才声明支持 class 2
+    if is_webdav_path(req.path(), &config) {
+        response
+            .append_header(("DAV", if config.webdav.locking { "1, 2" } else { "1" }))
+            .append_header(("MS-Author-Via", "DAV"));
+    }
+    response.finish()
+}
+
+// 内置的网站图标
+const FAVICON: &[u8] = include_bytes!("../assets/favicon.ico");
+
+// 网站图标，data 目录下存在 favicon.ico 时优先使用；替换的图标经由 NamedFile 异步读取，
+// 带有 ETag 与 Last-Modified，浏览器可以条件请求
+#[get("/favicon.ico")]
+async fn favicon(req: HttpRequest) -> HttpResponse {
+    let cache_control = "public, max-age=86400";
+    match NamedFile::open_async("data/favicon.ico").await {
+        Ok(file) => {
+            let mut res = file
+                .set_content_type("image/x-icon".parse().expect("合法的 MIME 类型"))
+                .disable_content_disposition()
+                .into_response(&req);
+            res.headers_mut().insert(header::CACHE_CONTROL, header::HeaderValue::from_static(cache_control));
+            res
+        }
+        Err(_) => HttpResponse::Ok()
+            .content_type("image/x-icon")
+            .append_header((header::CACHE_CONTROL, cache_control))
+            .body(FAVICON),
+    }
+}
+
+// Web 应用清单，仅在 pwa.enabled 开启时注册
+#[get("/manifest.json")]
+async fn pwa_manifest(config: web::Data<Config>) -> HttpResponse {
+    let pwa = &config.pwa;
+    let name = pwa.name.as_deref().unwrap_or(&config.branding.site_title);
+    let icons: Vec<_> = if pwa.icons.is_empty() {
+        vec![serde_json::json!({ "src": "/favicon.ico", "sizes": "32x32", "type": "image/x-icon" })]
+    } else {
+        pwa.icons.iter().enumerate()
+            .map(|(i, icon)| serde_json::json!({
+                "src": format!("/pwa-icon/{}", i),
+                "sizes": icon.sizes,
+                "type": mime_guess::from_path(&icon.path).first_or_octet_stream().essence_str(),
+            }))
+            .collect()
+    };
+    let manifest = serde_json::json!({
+        "name": name,
+        "short_name": pwa.short_name.as_deref().unwrap_or(name),
+        "start_url": "/",
+        "scope": "/",
+        "display": "standalone",
+        "theme_color": pwa.theme_color,
+        "background_color": pwa.background_color,
+        "icons": icons,
+    });
+    HttpResponse::Ok()
+        .content_type("application/manifest+json")
+        .append_header((header::CACHE_CONTROL, "no-cache"))
+        .body(manifest.to_string())
+}
+
+// 按配置中的序号返回应用图标
+#[get("/pwa-icon/{index}")]
+async fn pwa_icon(config: web::Data<Config>, position: web::Path<usize>) -> HttpResponse {
+    let Some(icon) = config.pwa.icons.get(position.into_inner()) else {
+        return HttpResponse::NotFound().finish();
+    };
+    match fs::read(&icon.path) {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(mime_guess::from_path(&icon.path).first_or_octet_stream())
+            .append_header((header::CACHE_CONTROL, "public, max-age=86400"))
+            .body(body),
+        Err(_) => HttpResponse::NotFound().finish(),
+    }
+}
+
+// 离线时显示缓存的首页；只处理页面导航，文件下载与 WebDAV 请求不经过缓存
+const SERVICE_WORKER: &str = r#"const CACHE = 'webdisk-shell-{{version}}';
+const SHELL = ['/', '/manifest.json', '/favicon.ico'];
+
+self.addEventListener('install', event => {
+    event.waitUntil(caches.open(CACHE).then(cache => cache.addAll(SHELL)));
+    self.skipWaiting();
+});
+
+self.addEventListener('activate', event => {
+    event.waitUntil(caches.keys().then(keys =>
+        Promise.all(keys.filter(key => key !== CACHE).map(key => caches.delete(key)))
+    ));
+    self.clients.claim();
+});
+
+self.addEventListener('fetch', event => {
+    if (event.request.mode !== 'navigate') {
+        return;
+    }
+    event.respondWith(
+        fetch(event.request).catch(() =>
+            caches.match(event.request).then(cached => cached || caches.match('/'))
+        )
+    );
+});
+"#;
+
+// 用于离线缓存页面框架的 Service Worker，仅在 pwa.enabled 开启时注册
+#[get("/sw.js")]
+async fn service_worker() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/javascript; charset=utf-8")
+        .append_header((header::CACHE_CONTROL, "no-cache"))
+        .body(SERVICE_WORKER.replace("{{version}}", VERSION))
+}
+
+// 清理上传文件名：去除路径部分、控制字符以及首尾的点和空白
+fn sanitize_upload_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or("");
+    let cleaned: String = base.chars().filter(|c| !c.is_control()).collect();
+    let cleaned = cleaned
+        .trim_start_matches(|c: char| c == '.' || c.is_whitespace())
+        .trim_end_matches(|c: char| c == '.' || c.is_whitespace());
+    if cleaned.is_empty() {
+        "upload".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+// 按冲突策略确定上传文件在目录中的最终文件名，拒绝上传时返回 None
+fn resolve_upload_name(dir: &Path, name: &str, policy: ConflictPolicy) -> Option<String> {
+    if !dir.join(name).exists() {
+        return Some(name.to_string());
+    }
+    match policy {
+        ConflictPolicy::Error => None,
+        ConflictPolicy::Overwrite => Some(name.to_string()),
+        ConflictPolicy::Rename => {
+            let (stem, ext) = match name.rsplit_once('.') {
+                Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+                _ => (name, String::new()),
+            };
+            (1..).map(|i| format!("{} ({}){}", stem, i, ext))
+                .find(|candidate| !dir.join(candidate).exists())
+        }
+    }
+}
+
+// 检查磁盘剩余空间能否容纳即将写入的数据（并保留 min_free_bytes）
+fn has_space_for(dir: &Path, incoming: u64, min_free: u64) -> bool {
+    match fs2::available_space(dir) {
+        Ok(available) => available >= incoming.saturating_add(min_free),
+        // 无法获取磁盘信息时不阻止写入，写满时由底层返回错误
+        Err(_) => true,
+    }
+}
+
+// 取出 Basic 认证中的用户名与密码
+fn basic_credentials(req: &HttpRequest) -> Option<(String, String)> {
+    let encoded = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(BASE64.decode(encoded).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+// 网页写操作的账号范围
+struct WebWriteScope {
+    root: PathBuf,    // 该用户可写的根目录
+    can_read: bool,   // 只有 w 权限的用户按投递箱处理，不能覆盖、删除或移动已有内容
+}
+
+// 网页上的写操作（上传、删除）使用 WebDAV 用户的账号，需要 w 权限，WebDAV 只读模式下一律拒绝
+async fn web_write_scope(req: &HttpRequest, config: &Config) -> Result<WebWriteScope, Box<HttpResponse>> {
+    if config.webdav.read_only {
+        return Err(Box::new(text_error(StatusCode::FORBIDDEN, "WebDAV 处于只读模式")));
+    }
+    let user = match basic_credentials(req) {
+        Some((name, password)) => authenticate(config, &name, &password).await,
+        None => None,
+    };
+    let Some(user) = user else {
+        return Err(Box::new(HttpResponse::Unauthorized()
+            .append_header((header::WWW_AUTHENTICATE, "Basic realm=\"WebDAV Server\""))
+            .finish()));
+    };
+    if !user.permissions.contains('w') {
+        return Err(Box::new(text_error(StatusCode::FORBIDDEN, "没有写入权限")));
+    }
+    if !same_origin(req) {
+        return Err(Box::new(text_error(StatusCode::FORBIDDEN, "不接受跨站提交的请求")));
+    }
+    Ok(WebWriteScope {
+        root: user.root_dir(config.webdav.root_dir(&config.cwd)),
+        can_read: user.permissions.contains('r'),
+    })
+}
+
+// 解析软链接后 path 是否位于 root 之内（含 root 本身）
+fn canonical_within(path: &Path, root: &Path) -> bool {
+    matches!((fs::canonicalize(path), fs::canonicalize(root)), (Ok(path), Ok(root)) if path.starts_with(&root))
+}
+
+// 网页上传的查询参数，overwrite 也可以作为表单字段提交（须位于文件之前）
+#[derive(Debug, Deserialize, Default)]
+struct UploadQuery {
+    overwrite: Option<String>,
+}
+
+// 以纯文本返回网页写操作的错误
+fn text_error(status: StatusCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type("text/plain; charset=utf-8")
+        .body(message.to_string())
+}
+
+// 将一个上传字段写入临时文件，完成后再重命名为目标文件，中途失败不会留下残缺的文件
+async fn save_upload_field(
+    field: &mut actix_multipart::Field,
+    storage: &web::Data<dyn Storage>,
+    temp: &Path,
+    progress: Option<&UploadTracker>,
+) -> Result<(), Error> {
+    let (store, path) = (storage.clone(), temp.to_path_buf());
+    let mut file = web::block(move || store.open_write(&path)).await??;
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk?;
+        if let Some(progress) = progress {
+            progress.advance(chunk.len() as u64);
+        }
+        // 存储层的写入是同步的，逐块交给线程池，避免阻塞工作线程
+        file = web::block(move || file.write_all(&chunk).map(|_| file)).await??;
+    }
+    web::block(move || file.flush()).await??;
+    Ok(())
+}
+
+// 删除上传失败或被放弃的临时文件
+async fn discard_upload_temp(storage: &web::Data<dyn Storage>, temp: &Path) {
+    let (storage, temp) = (storage.clone(), temp.to_path_buf());
+    let _ = web::block(move || storage.delete(&temp, false)).await;
+}
+
+// 网页上传：以 multipart/form-data 提交到 /upload/<目录>，可同时上传多个文件，完成后返回该目录。
+// 网页本身没有登录，上传需要 WebDAV 用户的账号与 w 权限，且目标目录须位于该用户的 WebDAV 根目录内
+#[post("/upload/{path:.*}")]
+async fn web_upload(
+    req: HttpRequest,
+    mut payload: Multipart,
+    config: web::Data<Config>,
+    protected: web::Data<ProtectedPaths>,
+    storage: web::Data<dyn Storage>,
+) -> Result<HttpResponse> {
+    if !config.upload.web_form {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let scope = match web_write_scope(&req, &config).await {
+        Ok(scope) => scope,
+        Err(response) => return Ok(*response),
+    };
+
+    let path = req.match_info().query("path").to_string();
+    let Some(dir) = request_fs_path(&config, &path).filter(|d| d.is_dir() && !protected.contains(d)) else {
+        return Ok(text_error(StatusCode::NOT_FOUND, "目录不存在"));
+    };
+    // 解析软链接后再比较，经由链接指向可写范围之外的目录同样拒绝
+    if !canonical_within(&dir, &scope.root) {
+        return Ok(text_error(StatusCode::FORBIDDEN, "不能上传到该目录"));
+    }
+    let incoming = req.headers().get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if incoming.is_some_and(|len| !has_space_for(&dir, len, config.upload.min_free_bytes)) {
+        return Ok(text_error(StatusCode::INSUFFICIENT_STORAGE, "磁盘空间不足"));
+    }
+
+    // 请求带有 X-Upload-Session 与 X-Upload-Id 时与 WebDAV 上传一样推送进度，total 为整个表单的大小
+    let tracker = if config.upload.progress_events {
+        UploadTracker::from_request(&req, incoming)
+    } else {
+        None
+    };
+    let query = web::Query::<UploadQuery>::from_query(req.query_string()).map(|q| q.into_inner()).unwrap_or_default();
+    let upload = WebUpload { dir: &dir, path: &path, overwrite: query.overwrite.as_deref() == Some("true") };
+    let result = receive_upload(&mut payload, &config, &protected, &storage, &scope, upload, tracker.as_deref()).await;
+    if let Some(tracker) = &tracker {
+        tracker.finish(match &result {
+            Ok(response) => response.status(),
+            Err(e) => e.as_response_error().status_code(),
+        });
+    }
+    result
+}
+
+// 网页上传的目标目录
+struct WebUpload<'a> {
+    dir: &'a Path,     // 本地路径
+    path: &'a str,     // 请求中的路径，完成后返回该目录
+    overwrite: bool,   // 查询参数中的 overwrite
+}
+
+// 逐个保存表单中的文件，完成后返回所在目录
+async fn receive_upload(
+    payload: &mut Multipart,
+    config: &Config,
+    protected: &ProtectedPaths,
+    storage: &web::Data<dyn Storage>,
+    scope: &WebWriteScope,
+    upload: WebUpload<'_>,
+    progress: Option<&UploadTracker>,
+) -> Result<HttpResponse> {
+    let WebUpload { dir, path, overwrite } = upload;
+    // 投递箱与只有 w 权限的用户不能覆盖看不到的已有文件
+    let drop_box = is_write_only(&config.cwd, dir) || !scope.can_read;
+    let mut overwrite = overwrite && !drop_box;
+    let mut saved = 0;
+    while let Some(field) = payload.next().await {
+        let mut field = field?;
+        let filename = field.content_disposition().and_then(|cd| cd.get_filename()).map(str::to_string);
+        let Some(filename) = filename else {
+            if field.name() == Some("overwrite") {
+                let mut value = Vec::new();
+                while let Some(chunk) = field.next().await {
+                    value.extend_from_slice(&chunk?);
+                }
+                overwrite = value == b"true" && !drop_box;
+            }
+            continue;
+        };
+        // 未选择文件时浏览器仍会提交一个文件名为空的字段
+        if filename.is_empty() {
+            continue;
+        }
+        let name = sanitize_upload_name(&filename);
+        let target = dir.join(&name);
+        if protected.contains(&target) {
+            return Ok(text_error(StatusCode::FORBIDDEN, &format!("不能写入 {}", name)));
+        }
+        if !overwrite && fs::symlink_metadata(&target).is_ok() {
+            return Ok(text_error(StatusCode::CONFLICT, &format!("文件已存在：{}", name)));
+        }
+        let temp = dir.join(format!(".{}.upload-{:016x}", name, thread_rng().gen::<u64>()));
+        if let Err(e) = save_upload_field(&mut field, storage, &temp, progress).await {
+            discard_upload_temp(storage, &temp).await;
+            return Err(e);
+        }
+        // 写入期间可能有其他请求创建了同名文件
+        if !overwrite && fs::symlink_metadata(&target).is_ok() {
+            discard_upload_temp(storage, &temp).await;
+            return Ok(text_error(StatusCode::CONFLICT, &format!("文件已存在：{}", name)));
+        }
+        let (store, from, to) = (storage.clone(), temp.clone(), target.clone());
+        if let Err(e) = web::block(move || store.rename(&from, &to)).await? {
+            discard_upload_temp(storage, &temp).await;
+            return Err(e.into());
+        }
+        if config.upload.dedup {
+            let result = web::block(move || dedup_file(&target, Path::new(BLOB_DIR))).await;
+            if let Ok(Err(e)) | Err(e) = result.map_err(std::io::Error::other) {
+                tracing::warn!(event = "dedup_failed", error = %e, "去重存储失败，已保留为普通文件");
+            }
+        }
+        saved += 1;
+    }
+    if saved == 0 {
+        return Ok(text_error(StatusCode::BAD_REQUEST, "没有选择要上传的文件"));
+    }
+
+    let dir_url = match encode_path(&percent_decode_str(path).decode_utf8_lossy()).as_str() {
+        "" => "/".to_string(),
+        encoded => format!("/{}/", encoded),
+    };
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, dir_url))
+        .finish())
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct DeleteQuery {
+    recursive: Option<String>,  // recursive=true 时删除非空目录及其全部内容
+}
+
+// 网页删除：POST /delete/<路径> 删除文件、软链接或空目录，非空目录需要 ?recursive=true，完成后返回上级目录。
+// 需开启 allow_web_delete，账号要求与网页上传相同且需要 r 权限；不能删除 cwd 与用户的根目录本身
+#[post("/delete/{path:.*}")]
+async fn web_delete(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    protected: web::Data<ProtectedPaths>,
+    storage: web::Data<dyn Storage>,
+) -> Result<HttpResponse> {
+    if !config.allow_web_delete {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let scope = match web_write_scope(&req, &config).await {
+        Ok(scope) => scope,
+        Err(response) => return Ok(*response),
+    };
+    // 只有 w 权限的用户看不到已有文件，也不能删除
+    if !scope.can_read {
+        return Ok(text_error(StatusCode::FORBIDDEN, "没有读取权限"));
+    }
+    let path = req.match_info().query("path").to_string();
+    let Some(target) = request_fs_path(&config, &path) else {
+        return Ok(text_error(StatusCode::NOT_FOUND, "文件不存在"));
+    };
+    let (Some(parent), Ok(metadata)) = (target.parent(), fs::symlink_metadata(&target)) else {
+        return Ok(text_error(StatusCode::NOT_FOUND, "文件不存在"));
+    };
+    if protected.contains(&target) {
+        return Ok(text_error(StatusCode::NOT_FOUND, "文件不存在"));
+    }
+    // 软链接只删除链接本身，按其所在目录判断范围；目录按自身解析后的位置判断，不能是根目录本身
+    let inside = canonical_within(parent, &scope.root) && (metadata.is_symlink() || canonical_within(&target, &scope.root));
+    let is_root = [Path::new(&config.cwd), scope.root.as_path()].iter()
+        .any(|root| matches!((fs::canonicalize(&target), fs::canonicalize(root)), (Ok(target), Ok(root)) if target == root));
+    if !inside || (is_root && !metadata.is_symlink()) || is_write_only(&config.cwd, parent) {
+        return Ok(text_error(StatusCode::FORBIDDEN, "不能删除该路径"));
+    }
+
+    let query = web::Query::<DeleteQuery>::from_query(req.query_string()).map(|q| q.into_inner()).unwrap_or_default();
+    let recursive = query.recursive.as_deref() == Some("true");
+    let is_dir = metadata.is_dir();
+    let removal = target.clone();
+    let result = web::block(move || storage.delete(&removal, recursive)).await?;
+    if let Err(e) = result {
+        if is_dir && !recursive && fs::read_dir(&target).is_ok_and(|mut d| d.next().is_some()) {
+            return Ok(text_error(StatusCode::CONFLICT, "目录不为空，需确认后递归删除"));
+        }
+        return Err(e.into());
+    }
+    tracing::info!(event = "web_delete", path = %target.display(), recursive, "已通过网页删除");
+
+    let decoded = percent_decode_str(&path).decode_utf8_lossy().trim_end_matches('/').to_string();
+    let parent_url = match decoded.rsplit_once('/') {
+        Some((parent, _)) if !encode_path(parent).is_empty() => format!("/{}/", encode_path(parent)),
+        _ => "/".to_string(),
+    };
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, parent_url))
+        .finish())
+}
+
+// 新建文件夹表单，name 为相对于当前目录的单级名称
+#[derive(Debug, Deserialize)]
+struct MkdirForm {
+    name: String,
+}
+
+// 网页新建文件夹：POST /mkdir/<目录> 在该目录下创建 name 指定的文件夹，完成后返回该目录。
+// 需开启 allow_web_mkdir，账号要求与网页上传相同；名称只能是一级，不能包含路径分隔符
+#[post("/mkdir/{path:.*}")]
+async fn web_mkdir(
+    req: HttpRequest,
+    form: web::Form<MkdirForm>,
+    config: web::Data<Config>,
+    protected: web::Data<ProtectedPaths>,
+) -> Result<HttpResponse> {
+    if !config.allow_web_mkdir {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let scope = match web_write_scope(&req, &config).await {
+        Ok(scope) => scope,
+        Err(response) => return Ok(*response),
+    };
+    let t = ui_strings(&config, &req);
+    let path = req.match_info().query("path").to_string();
+    let Some(dir) = request_fs_path(&config, &path).filter(|d| d.is_dir() && !protected.contains(d)) else {
+        return not_found_page(&config, t, &path);
+    };
+    if !canonical_within(&dir, &scope.root) {
+        return forbidden_page(&config, t, &path);
+    }
+
+    let name = form.name.trim();
+    let current = percent_decode_str(&path).decode_utf8_lossy().trim_end_matches('/').to_string();
+    // 错误页面的返回上级链接指向当前目录
+    let page_path = format!("{}/{}", current, name);
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) || name.chars().any(char::is_control) {
+        return error_page(&config, t, &page_path, StatusCode::BAD_REQUEST, t.invalid_folder_name);
+    }
+    let target = dir.join(name);
+    if protected.contains(&target) {
+        return forbidden_page(&config, t, &page_path);
+    }
+    // 父目录已确认存在，逐级创建与直接创建结果相同；已存在时 create_dir_all 不报错，先行检查
+    if fs::symlink_metadata(&target).is_ok() {
+        return error_page(&config, t, &page_path, StatusCode::CONFLICT, t.folder_exists);
+    }
+    let created = target.clone();
+    web::block(move || fs::create_dir_all(&created)).await??;
+    tracing::info!(event = "web_mkdir", path = %target.display(), "已通过网页新建文件夹");
+
+    let dir_url = match encode_path(&current).as_str() {
+        "" => "/".to_string(),
+        encoded => format!("/{}/", encoded),
+    };
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, dir_url))
+        .finish())
+}
+
+// 重命名表单，from 与 to 均为相对于 cwd 的路径（不含百分号编码）
+#[derive(Debug, Deserialize)]
+struct RenameForm {
+    from: String,
+    to: String,
+}
+
+// 网页重命名：POST /rename 将 from 重命名或移动到 to，to 已存在时拒绝，完成后返回 to 所在的目录。
+// 需开启 allow_web_rename，账号要求与网页上传相同且需要 r 权限；两者都须位于该用户的 WebDAV 根目录内
+#[post("/rename")]
+async fn web_rename(
+    req: HttpRequest,
+    form: web::Form<RenameForm>,
+    config: web::Data<Config>,
+    protected: web::Data<ProtectedPaths>,
+) -> Result<HttpResponse> {
+    if !config.allow_web_rename {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let scope = match web_write_scope(&req, &config).await {
+        Ok(scope) => scope,
+        Err(response) => return Ok(*response),
+    };
+    let t = ui_strings(&config, &req);
+    // 只有 w 权限的用户不能移动看不到的已有内容
+    if !scope.can_read {
+        return forbidden_page(&config, t, &encode_path(form.to.trim_matches('/')));
+    }
+    let RenameForm { from, to } = form.into_inner();
+    let (from_rel, to_rel) = (from.trim_matches('/'), to.trim_matches('/'));
+    let page_path = encode_path(to_rel);
+
+    let source = join_in_cwd(&config.cwd, from_rel)
+        .filter(|p| !from_rel.is_empty() && !protected.contains(p) && fs::symlink_metadata(p).is_ok());
+    let Some(source) = source else {
+        return not_found_page(&config, t, &encode_path(from_rel));
+    };
+    let Some(target) = join_in_cwd(&config.cwd, to_rel).filter(|_| !to_rel.is_empty()) else {
+        return error_page(&config, t, &page_path, StatusCode::BAD_REQUEST, t.invalid_rename_target);
+    };
+    // 软链接按所在目录判断范围，移动的是链接本身；不能移动根目录，也不能移入投递箱之外看不到的位置
+    let (Some(source_dir), Some(target_dir)) = (source.parent(), target.parent()) else {
+        return forbidden_page(&config, t, &page_path);
+    };
+    let is_root = [Path::new(&config.cwd), scope.root.as_path()].iter()
+        .any(|root| matches!((fs::canonicalize(&source), fs::canonicalize(root)), (Ok(source), Ok(root)) if source == root));
+    if is_root
+        || !canonical_within(source_dir, &scope.root)
+        || !canonical_within(target_dir, &scope.root)
+        || protected.contains(target_dir)
+        || protected.contains(&target)
+        || is_write_only(&config.cwd, source_dir)
+    {
+        return forbidden_page(&config, t, &page_path);
+    }
+    if !target_dir.is_dir() {
+        return error_page(&config, t, &page_path, StatusCode::BAD_REQUEST, t.invalid_rename_target);
+    }
+    if fs::symlink_metadata(&target).is_ok() {
+        return error_page(&config, t, &page_path, StatusCode::CONFLICT, t.rename_target_exists);
+    }
+
+    // 失败时（如目标位于另一文件系统、目录中的文件被占用）如实显示系统返回的错误
+    let (from_path, to_path) = (source.clone(), target.clone());
+    if let Err(e) = web::block(move || fs::rename(&from_path, &to_path)).await? {
+        let status = match e.kind() {
+            std::io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+            _ => StatusCode::CONFLICT,
+        };
+        return error_page(&config, t, &page_path, status, &format!("{}：{}", t.rename_failed, e));
+    }
+    tracing::info!(event = "web_rename", from = %source.display(), to = %target.display(), "已通过网页重命名");
+
+    let dir_url = match to_rel.rsplit_once('/') {
+        Some((parent, _)) if !encode_path(parent).is_empty() => format!("/{}/", encode_path(parent)),
+        _ => "/".to_string(),
+    };
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, dir_url))
+        .finish())
+}
+
+// 播放列表请求参数，type 为 audio 或 video，省略时两者都包含
+#[derive(Debug, Deserialize)]
+struct PlaylistQuery {
+    #[serde(default)]
+    path: String,
+    #[serde(rename = "type")]
+    media_type: Option<String>,
+}
+
+// 使用 ffprobe 读取媒体时长（秒）；ffprobe 不可用时后续文件不再尝试
+fn probe_durations(paths: &[PathBuf]) -> Vec<Option<u64>> {
+    let mut available = true;
+    paths.iter()
+        .map(|path| {
+            if !available {
+                return None;
+            }
+            let output = Command::new("ffprobe")
+                .args(["-v", "error", "-show_entries", "format=duration", "-of", "csv=p=0"])
+                .arg(path)
+                .output();
+            match output {
+                Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .map(|secs| secs.round() as u64),
+                Ok(_) => None,
+                Err(_) => {
+                    available = false;
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+// 将目录中的音视频文件生成为 M3U8 播放列表
+#[get("/playlist")]
+async fn playlist(
+    req: HttpRequest,
+    query: web::Query<PlaylistQuery>,
+    config: web::Data<Config>,
+    protected: web::Data<ProtectedPaths>,
+) -> Result<HttpResponse> {
+    let wanted: &[Category] = match query.media_type.as_deref() {
+        None | Some("") => &[Category::Audio, Category::Video],
+        Some("audio") => &[Category::Audio],
+        Some("video") => &[Category::Video],
+        Some(_) => return Ok(HttpResponse::BadRequest().body("type 只能是 audio 或 video")),
+    };
+    let dir = match resolve_in_cwd(&config.cwd, &query.path) {
+        Some(dir) if dir.is_dir() && !protected.contains(&dir) => dir,
+        _ => return Ok(HttpResponse::NotFound().body("目录不存在")),
+    };
+    if is_write_only(&config.cwd, &dir) {
+        return Ok(HttpResponse::Forbidden().body("此目录只接收上传"));
+    }
+
+    let mut names: Vec<String> = fs::read_dir(&dir)?
+        .flatten()
+        .filter(|e| e.path().is_file() && !protected.contains(&e.path()))
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| wanted.contains(&file_category(name)))
+        .collect();
+    names.sort_by_key(|name| name.to_lowercase());
+
+    let paths: Vec<PathBuf> = names.iter().map(|name| dir.join(name)).collect();
+    let durations = web::block(move || probe_durations(&paths)).await?;
+
+    // 播放器通常在下载后打开播放列表，因此使用绝对地址
+    let conn = req.connection_info();
+    let dir_path = encode_path(&query.path);
+    let base = if dir_path.is_empty() {
+        format!("{}://{}/", conn.scheme(), conn.host())
+    } else {
+        format!("{}://{}/{}/", conn.scheme(), conn.host(), dir_path)
+    };
+
+    let mut body = String::from("#EXTM3U\n");
+    for (name, duration) in names.iter().zip(durations) {
+        if let Some(secs) = duration {
+            // 文件名中的换行会让后续内容成为播放列表中的新条目
+            body.push_str(&format!("#EXTINF:{},{}\n", secs, name.replace(['\r', '\n'], " ")));
+        }
+        body.push_str(&format!("{}{}\n", base, utf8_percent_encode(name, PATH_SEGMENT)));
+    }
+
+    let title = dir.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "playlist".to_string());
+    Ok(HttpResponse::Ok()
+        .content_type("audio/x-mpegurl; charset=utf-8")
+        .insert_header(header::ContentDisposition::attachment(format!("{}.m3u8", title)))
+        .body(body))
+}
+
+// sitemap 协议规定单个文件最多包含的地址数
+const MAX_SITEMAP_URLS: usize = 50_000;
+
+struct SitemapEntry {
+    path: String,  // 相对于 cwd 的路径，未编码
+    size: u64,
+    modified: Option<DateTime<Utc>>,
+}
+
+// 一次完整遍历的结果，连同遍历到的目录及其目录配置的修改时间，用于判断缓存是否过期
+struct SitemapIndex {
+    entries: Vec<SitemapEntry>,
+    truncated: bool,
+    stamps: Vec<(PathBuf, Option<std::time::SystemTime>)>,
+}
+
+impl SitemapIndex {
+    // 目录增删或重命名条目会更新其修改时间；文件内容改动不影响目录，需等目录变化后才会刷新
+    fn is_fresh(&self) -> bool {
+        self.stamps.iter().all(|(path, stamp)| fs::metadata(path).and_then(|md| md.modified()).ok() == *stamp)
+    }
+}
+
+// 按与目录列表相同的可见性规则遍历 cwd，跳过受保护路径、隐藏文件与排除的子目录
+fn walk_sitemap(
+    config: &Config,
+    protected: &ProtectedPaths,
+    dir: &Path,
+    rel: &str,
+    depth: usize,
+    guard: &mut WalkGuard,
+    sitemap: &mut SitemapIndex,
+) {
+    if !guard.enter(dir, depth) {
+        sitemap.truncated = true;
+        return;
+    }
+    let stamp = |path: &Path| fs::metadata(path).and_then(|md| md.modified()).ok();
+    sitemap.stamps.push((dir.to_path_buf(), stamp(dir)));
+    sitemap.stamps.push((dir.join(DIR_CONFIG_FILE), stamp(&dir.join(DIR_CONFIG_FILE))));
+
+    let overrides = load_dir_overrides(dir);
+    if overrides.write_only {
+        return;
+    }
+    let settings = config.listing_settings(&overrides);
+    let Ok(read_dir) = fs::read_dir(dir) else { return };
+    let mut names: Vec<String> = read_dir.flatten()
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| is_listed(name, &settings))
+        .collect();
+    names.sort();
+
+    for name in names {
+        let path = dir.join(&name);
+        let rel_path = if rel.is_empty() { name } else { format!("{}/{}", rel, name) };
+        if protected.contains(&path) || config.sitemap.excludes(&rel_path) {
+            continue;
+        }
+        let Ok(md) = fs::metadata(&path) else { continue };
+        if settings.hide.empty_files && md.is_file() && md.len() == 0 {
+            continue;
+        }
+        if md.is_dir() {
+            // 与打包下载一致，不跟随指向目录的软链接
+            if fs::symlink_metadata(&path).is_ok_and(|md| md.file_type().is_symlink()) {
+                continue;
+            }
+            walk_sitemap(config, protected, &path, &rel_path, depth + 1, guard, sitemap);
+        } else if sitemap.entries.len() >= MAX_SITEMAP_URLS {
+            sitemap.truncated = true;
+            return;
+        } else {
+            sitemap.entries.push(SitemapEntry {
+                path: rel_path,
+                size: md.len(),
+                modified: md.modified().ok().map(DateTime::<Utc>::from),
+            });
+        }
+    }
+}
+
+// 返回缓存的索引，目录有变化时重新遍历
+fn sitemap_index(config: &Config, protected: &ProtectedPaths) -> Arc<SitemapIndex> {
+    static CACHE: std::sync::Mutex<Option<Arc<SitemapIndex>>> = std::sync::Mutex::new(None);
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(sitemap) = cache.as_ref().filter(|sitemap| sitemap.is_fresh()) {
+        return sitemap.clone();
+    }
+    let mut sitemap = SitemapIndex { entries: Vec::new(), truncated: false, stamps: Vec::new() };
+    let mut guard = WalkGuard::new(config.max_recursion_depth);
+    walk_sitemap(config, protected, Path::new(&config.cwd), "", 0, &mut guard, &mut sitemap);
+    let sitemap = Arc::new(sitemap);
+    *cache = Some(sitemap.clone());
+    sitemap
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+        .replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+// 当前请求对应的站点根地址，sitemap 要求使用完整地址
+fn site_base(req: &HttpRequest) -> String {
+    let conn = req.connection_info();
+    format!("{}://{}/", conn.scheme(), conn.host())
+}
+
+// 全站文件的 sitemap，仅在 sitemap.enabled 开启时注册
+#[get("/sitemap.xml")]
+async fn sitemap_xml(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    protected: web::Data<ProtectedPaths>,
+) -> Result<HttpResponse> {
+    let sitemap = web::block(move || sitemap_index(&config, &protected)).await?;
+    let base = site_base(&req);
+    let mut body = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for entry in &sitemap.entries {
+        body.push_str(&format!("  <url><loc>{}</loc>", xml_escape(&format!("{}{}", base, encode_path(&entry.path)))));
+        if let Some(modified) = entry.modified {
+            body.push_str(&format!("<lastmod>{}</lastmod>", modified.format("%Y-%m-%dT%H:%M:%SZ")));
+        }
+        body.push_str("</url>\n");
+    }
+    body.push_str("</urlset>\n");
+    Ok(HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .append_header((header::CACHE_CONTROL, LISTING_CACHE_POLICY))
+        .body(body))
+}
+
+// 与 sitemap 内容相同的 JSON 索引，额外包含文件大小
+#[get("/index.json")]
+async fn index_json(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    protected: web::Data<ProtectedPaths>,
+) -> Result<HttpResponse> {
+    let sitemap = web::block(move || sitemap_index(&config, &protected)).await?;
+    let base = site_base(&req);
+    let files: Vec<_> = sitemap.entries.iter()
+        .map(|entry| serde_json::json!({
+            "path": format!("/{}", entry.path),
+            "url": format!("{}{}", base, encode_path(&entry.path)),
+            "size": entry.size,
+            "modified": entry.modified.map(|t| t.to_rfc3339()),
+        }))
+        .collect();
+    Ok(HttpResponse::Ok()
+        .append_header((header::CACHE_CONTROL, LISTING_CACHE_POLICY))
+        .json(serde_json::json!({
+            "truncated": sitemap.truncated,
+            "files": files,
+        })))
+}
+
+// 以 JSON 返回 /api/list/ 下的错误
+fn api_error(status: StatusCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type("application/json; charset=utf-8")
+        .body(serde_json::json!({ "error": message }).to_string())
+}
+
+// 以 JSON 返回目录列表，条目与网页列表相同（不含返回上级），供同步客户端等工具使用
+#[get("/api/list/{path:.*}")]
+async fn api_list(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    protected: web::Data<ProtectedPaths>,
+    storage: web::Data<dyn Storage>,
+) -> Result<HttpResponse> {
+    let path = req.match_info().query("path").to_string();
+    let t = ui_strings(&config, &req);
+    let Some(full_path) = request_fs_path(&config, &path).filter(|p| !protected.contains(p)) else {
+        return Ok(api_error(StatusCode::NOT_FOUND, "目录不存在"));
+    };
+    match storage.stat(&full_path) {
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            return Ok(api_error(StatusCode::FORBIDDEN, "没有权限读取该目录"));
+        }
+        Ok(metadata) if metadata.is_dir => {}
+        _ => return Ok(api_error(StatusCode::NOT_FOUND, "目录不存在")),
+    }
+    if is_write_only(&config.cwd, &full_path) {
+        return Ok(api_error(StatusCode::FORBIDDEN, "投递箱目录不允许列出"));
+    }
+
+    let settings = config.listing_settings(&load_dir_overrides(&full_path));
+    let listing = match get_directory_entries(storage.get_ref(), &full_path, Path::new(&config.cwd), &settings, t).await {
+        Ok(listing) => listing,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            return Ok(api_error(StatusCode::FORBIDDEN, "没有权限读取该目录"));
+        }
+        Err(_) => return Ok(api_error(StatusCode::NOT_FOUND, "目录不存在")),
+    };
+    let mut entries = listing.entries;
+    entries.retain(|e| e.name != ".." && !protected.contains(&full_path.join(&e.name)));
+    let total = truncate_entries(&mut entries, settings.max_entries);
+    for entry in entries.iter_mut().filter(|e| e.accessible) {
+        entry.url = entry_url(&path, &entry.name);
+    }
+    let body = serde_json::json!({
+        "path": format!("/{}", percent_decode_str(&path).decode_utf8_lossy()),
+        "truncated": settings.max_entries > 0 && total > settings.max_entries,
+        "total": total,
+        "entries": entries,
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("application/json; charset=utf-8")
+        .insert_header((header::CACHE_CONTROL, LISTING_CACHE_POLICY))
+        .body(body.to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+struct ChecksumQuery {
+    path: String,
+    algo: Option<String>,    // sha256（默认）、sha1 或 md5
+    format: Option<String>,  // text 时返回与 sha256sum 相同格式的纯文本
+}
+
+// 以流式读取的方式计算文件摘要，返回十六进制字符串
+fn hash_file<D: Digest + std::io::Write>(path: &Path) -> std::io::Result<String> {
+    let mut hasher = D::new();
+    std::io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// 计算文件校验和，按路径与修改时间缓存结果，文件变化后自动失效
+fn cached_checksum(path: &Path, algo: &'static str, md: &fs::Metadata) -> std::io::Result<String> {
+    type Cache = std::sync::Mutex<std::collections::HashMap<(PathBuf, &'static str), (std::time::SystemTime, u64, String)>>;
+    static CACHE: std::sync::OnceLock<Cache> = std::sync::OnceLock::new();
+    const MAX_CACHED: usize = 1024;
+
+    let modified = md.modified()?;
+    let key = (path.to_path_buf(), algo);
+    let cache = CACHE.get_or_init(Default::default);
+    if let Some((cached_time, cached_len, digest)) = cache.lock().unwrap().get(&key) {
+        if *cached_time == modified && *cached_len == md.len() {
+            return Ok(digest.clone());
+        }
+    }
+
+    let digest = match algo {
+        "md5" => hash_file::<md5::Md5>(path)?,
+        "sha1" => hash_file::<sha1::Sha1>(path)?,
+        _ => hash_file::<Sha256>(path)?,
+    };
+    let mut cache = cache.lock().unwrap();
+    if cache.len() >= MAX_CACHED {
+        cache.clear();
+    }
+    cache.insert(key, (modified, md.len(), digest.clone()));
+    Ok(digest)
+}
+
+// 计算文件校验和，便于下载后核对完整性
+#[get("/checksum")]
+async fn checksum(
+    query: web::Query<ChecksumQuery>,
+    config: web::Data<Config>,
+    protected: web::Data<ProtectedPaths>,
+) -> Result<HttpResponse> {
+    let algo = match query.algo.as_deref() {
+        None | Some("") | Some("sha256") => "sha256",
+        Some("sha1") => "sha1",
+        Some("md5") => "md5",
+        Some(_) => return Ok(HttpResponse::BadRequest().body("algo 只能是 sha256、sha1 或 md5")),
+    };
+    let path = match resolve_in_cwd(&config.cwd, &query.path) {
+        Some(path) if !protected.contains(&path) => path,
+        _ => return Ok(HttpResponse::NotFound().body("文件不存在")),
+    };
+    let md = fs::metadata(&path)?;
+    if md.is_dir() {
+        return Ok(HttpResponse::BadRequest().body("不能计算目录的校验和"));
+    }
+    if path.parent().is_some_and(|dir| is_write_only(&config.cwd, dir)) {
+        return Ok(HttpResponse::Forbidden().body("此目录只接收上传"));
+    }
+
+    let target = path.clone();
+    let digest = web::block(move || cached_checksum(&target, algo, &md)).await??;
+    let name = path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    if query.format.as_deref() == Some("text") {
+        return Ok(HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body(format!("{}  {}\n", digest, name)));
+    }
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "name": name,
+        "algo": algo,
+        "digest": digest,
+    })))
+}
+
+// 图片转码结果的缓存目录
+const IMAGE_CACHE_DIR: &str = "data/cache/images";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TranscodeFormat {
+    WebP,
+    Avif,
+}
+
+impl TranscodeFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            TranscodeFormat::WebP => "webp",
+            TranscodeFormat::Avif => "avif",
+        }
+    }
+
+    fn mime(self) -> Mime {
+        self.mime_str().parse().unwrap_or(mime::APPLICATION_OCTET_STREAM)
+    }
+
+    fn mime_str(self) -> &'static str {
+        match self {
+            TranscodeFormat::WebP => "image/webp",
+            TranscodeFormat::Avif => "image/avif",
+        }
+    }
+}
+
+fn is_transcodable_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext.to_ascii_lowercase().as_str(), "jpg" | "jpeg" | "png"))
+}
+
+// 根据 ?fmt= 与 Accept 请求头选择转码格式，None 表示返回原图
+fn negotiate_image_format(fmt: Option<&str>, accept: &str) -> Option<TranscodeFormat> {
+    match fmt {
+        Some("orig") => None,
+        Some("webp") => Some(TranscodeFormat::WebP),
+        Some("avif") => Some(TranscodeFormat::Avif),
+        _ => [TranscodeFormat::Avif, TranscodeFormat::WebP]
+            .into_iter()
+            .find(|f| accept.contains(f.mime_str())),
+    }
+}
+
+fn encode_image(source: &Path, format: TranscodeFormat) -> Result<Vec<u8>, image::ImageError> {
+    let decoded = image::open(source)?;
+    // 编码器只接受 8 位 RGB/RGBA
+    let decoded = if decoded.color().has_alpha() {
+        image::DynamicImage::ImageRgba8(decoded.to_rgba8())
+    } else {
+        image::DynamicImage::ImageRgb8(decoded.to_rgb8())
+    };
+    let mut buf = Vec::new();
+    match format {
+        TranscodeFormat::WebP => decoded.write_with_encoder(image::codecs::webp::WebPEncoder::new_lossless(&mut buf))?,
+        TranscodeFormat::Avif => decoded.write_with_encoder(image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut buf, 8, 75))?,
+    }
+    Ok(buf)
+}
+
+// 图片缓存的键，由路径、修改时间与大小计算，原图变化后自动失效
+fn image_cache_key(source: &Path) -> Option<String> {
+    let md = fs::metadata(source).ok()?;
+    let modified = md.modified().ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let source_key = fs::canonicalize(source).unwrap_or_else(|_| source.to_path_buf());
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}:{}:{}", source_key.display(), modified, md.len()));
+    Some(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// 转码图片并缓存到磁盘，原图变化后自动失效；
+// 转码失败或结果不比原图小时记录标记，之后直接返回原图
+fn transcode_image(source: &Path, format: TranscodeFormat, cache_dir: &Path) -> Option<PathBuf> {
+    let key = image_cache_key(source)?;
+    let md = fs::metadata(source).ok()?;
+
+    let cached = cache_dir.join(format!("{}.{}", key, format.extension()));
+    let skipped = cache_dir.join(format!("{}.{}.orig", key, format.extension()));
+    if cached.exists() {
+        return Some(cached);
+    }
+    if skipped.exists() || fs::create_dir_all(cache_dir).is_err() {
+        return None;
+    }
+
+    match encode_image(source, format) {
+        Ok(buf) if (buf.len() as u64) < md.len() => {
+            // 先写入临时文件再重命名，避免并发请求读到写了一半的结果
+            let tmp = cache_dir.join(format!(".{}.{:x}.tmp", key, thread_rng().gen::<u64>()));
+            fs::write(&tmp, &buf).and_then(|_| fs::rename(&tmp, &cached)).ok()?;
+            Some(cached)
+        }
+        Ok(_) => {
+            let _ = fs::write(&skipped, b"");
+            None
+        }
+        Err(e) => {
+            tracing::warn!(event = "transcode_failed", path = %source.display(), error = %e, "图片转码失败，返回原图");
+            let _ = fs::write(&skipped, b"");
+            None
+        }
+    }
+}
+
+// 缩略图的最大宽高（像素）
+const THUMBNAIL_SIZE: u32 = 320;
+
+fn is_thumbnailable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext.to_ascii_lowercase().as_str(), "jpg" | "jpeg" | "png" | "webp"))
+}
+
+// 生成缩略图并缓存，有透明通道时保存为 PNG，否则为 JPEG；生成失败时记录标记，之后直接返回原图
+fn thumbnail_image(source: &Path, cache_dir: &Path) -> Option<PathBuf> {
+    let key = image_cache_key(source)?;
+    let jpeg = cache_dir.join(format!("{}.thumb.jpg", key));
+    let png = cache_dir.join(format!("{}.thumb.png", key));
+    let skipped = cache_dir.join(format!("{}.thumb.orig", key));
+    if let Some(cached) = [&jpeg, &png].into_iter().find(|p| p.exists()) {
+        return Some(cached.clone());
+    }
+    if skipped.exists() || fs::create_dir_all(cache_dir).is_err() {
+        return None;
+    }
+
+    let decoded = match image::open(source) {
+        Ok(decoded) => decoded.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE),
+        Err(e) => {
+            tracing::warn!(event = "thumbnail_failed", path = %source.display(), error = %e, "缩略图生成失败，返回原图");
+            let _ = fs::write(&skipped, b"");
+            return None;
+        }
+    };
+    // JPEG 编码器只接受 8 位 RGB
+    let (cached, thumbnail, format) = if decoded.color().has_alpha() {
+        (png, image::DynamicImage::ImageRgba8(decoded.to_rgba8()), image::ImageFormat::Png)
+    } else {
+        (jpeg, image::DynamicImage::ImageRgb8(decoded.to_rgb8()), image::ImageFormat::Jpeg)
+    };
+    let mut buf = std::io::Cursor::new(Vec::new());
+    thumbnail.write_to(&mut buf, format).ok()?;
+    let tmp = cache_dir.join(format!(".{}.{:x}.tmp", key, thread_rng().gen::<u64>()));
+    fs::write(&tmp, buf.into_inner()).and_then(|_| fs::rename(&tmp, &cached)).ok()?;
+    Some(cached)
+}
+
+// 同时存在的目录监听数量上限，超出时拒绝新的连接
+const MAX_WATCHERS: usize = 64;
+// 合并短时间内连续发生的文件变更
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+static ACTIVE_WATCHERS: AtomicUsize = AtomicUsize::new(0);
+
+// 占用一个长连接名额（目录监听、进度推送），连接关闭时自动归还
+struct StreamSlot(&'static AtomicUsize);
+
+impl StreamSlot {
+    fn acquire(active: &'static AtomicUsize, max: usize) -> Option<Self> {
+        active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| (n < max).then_some(n + 1))
+            .ok()
+            .map(|_| StreamSlot(active))
+    }
+}
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchQuery {
+    #[serde(default)]
+    path: String,
+}
+
+// 推送给浏览器的变更事件
+#[derive(Debug, Serialize)]
+struct WatchEvent {
+    event: &'static str,  // created、modified 或 deleted
+    name: String,
+    entry: Option<FileEntry>,
+    html: Option<String>,  // 渲染好的条目片段，前端直接插入
+}
+
+// 单个连接所监听目录的状态，记录已知条目以区分新增与修改
+struct DirWatch {
+    storage: Arc<dyn Storage>,
+    protected: ProtectedPaths,
+    dir: PathBuf,
+    current_path: String,
+    settings: ListingSettings,
+    t: &'static UiStrings,
+    known: HashSet<String>,
+}
+
+impl DirWatch {
+    fn is_watched(&self, name: &str) -> bool {
+        is_listed(name, &self.settings) && !self.protected.contains(&self.dir.join(name))
+    }
+
+    fn snapshot(&mut self) {
+        if let Ok(listing) = self.storage.list(&self.dir) {
+            self.known = listing.into_iter()
+                .map(|entry| entry.name)
+                .filter(|name| self.is_watched(name))
+                .collect();
+        }
+    }
+
+    // 重新读取目录，将发生变化的名称转换为事件
+    fn changes(&mut self, names: HashSet<String>) -> Vec<WatchEvent> {
+        let Ok(listing) = self.storage.list(&self.dir) else {
+            return Vec::new();
+        };
+        let mut current: BTreeMap<String, std::io::Result<StorageMetadata>> = listing.into_iter()
+            .filter(|entry| names.contains(&entry.name))
+            .map(|entry| (entry.name, entry.metadata))
+            .collect();
+
+        let mut events = Vec::new();
+        for name in names {
+            if !self.is_watched(&name) {
+                continue;
+            }
+            match current.remove(&name) {
+                Some(metadata) => {
+                    let mut entry = build_entry(self.storage.as_ref(), &self.dir, name.clone(), metadata, &self.settings, self.t);
+                    if entry.accessible {
+                        entry.url = entry_url(&self.current_path, &name);
+                    }
+                    let event = if self.known.insert(name.clone()) { "created" } else { "modified" };
+                    events.push(WatchEvent {
+                        event,
+                        name,
+                        html: render_entry(&entry, self.t, &self.current_path, &self.settings, false).ok(),
+                        entry: Some(entry),
+                    });
+                }
+                None if self.known.remove(&name) => {
+                    events.push(WatchEvent { event: "deleted", name, entry: None, html: None });
+                }
+                None => {}
+            }
+        }
+        events
+    }
+}
+
+#[get("/ws/watch")]
+async fn watch_socket(
+    req: HttpRequest,
+    body: web::Payload,
+    query: web::Query<WatchQuery>,
+    config: web::Data<Config>,
+    protected: web::Data<ProtectedPaths>,
+    storage: web::Data<dyn Storage>,
+) -> Result<HttpResponse> {
+    if !config.live_updates {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let Some(dir) = request_fs_path(&config, &query.path) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    if protected.contains(&dir) || !storage.stat(&dir).is_ok_and(|m| m.is_dir) {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    if is_write_only(&config.cwd, &dir) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+    // 仅本地文件系统支持变更通知
+    let Some(local_dir) = storage.local_path(&dir).and_then(|p| fs::canonicalize(p).ok()) else {
+        return Ok(HttpResponse::NotImplemented().finish());
+    };
+    let Some(slot) = StreamSlot::acquire(&ACTIVE_WATCHERS, MAX_WATCHERS) else {
+        return Ok(HttpResponse::ServiceUnavailable()
+            .insert_header((header::RETRY_AFTER, "30"))
+            .finish());
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let watched_dir = local_dir.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else { return };
+        for path in event.paths {
+            if path.parent() == Some(watched_dir.as_path()) {
+                if let Some(name) = path.file_name() {
+                    let _ = tx.send(name.to_string_lossy().to_string());
+                }
+            }
+        }
+    })
+    .map_err(std::io::Error::other)?;
+    watcher.watch(&local_dir, RecursiveMode::NonRecursive)
+        .map_err(std::io::Error::other)?;
+
+    let (response, mut session, mut messages) = actix_ws::handle(&req, body)?;
+
+    let mut state = DirWatch {
+        storage: storage.into_inner(),
+        protected: protected.get_ref().clone(),
+        settings: config.listing_settings(&load_dir_overrides(&dir)),
+        dir,
+        current_path: query.into_inner().path,
+        t: ui_strings(&config, &req),
+        known: HashSet::new(),
+    };
+
+    actix_web::rt::spawn(async move {
+        let _slot = slot;
+        let _watcher = watcher;
+        state = match web::block(move || { state.snapshot(); state }).await {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+
+        loop {
+            tokio::select! {
+                message = messages.next() => match message {
+                    Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                        // 发送失败说明连接已断开，随后读取消息时会结束循环
+                        let _ = session.pong(&bytes).await;
+                    }
+                    Some(Ok(actix_ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                    _ => {}
+                },
+                changed = rx.recv() => {
+                    let Some(first) = changed else { break };
+                    // 等待一小段时间，把连续的变更合并为一次推送
+                    tokio::time::sleep(WATCH_DEBOUNCE).await;
+                    let mut names = HashSet::from([first]);
+                    while let Ok(name) = rx.try_recv() {
+                        names.insert(name);
+                    }
+
+                    let (next, events) = match web::block(move || {
+                        let events = state.changes(names);
+                        (state, events)
+                    }).await {
+                        Ok(result) => result,
+                        Err(_) => break,
+                    };
+                    state = next;
+
+                    for event in events {
+                        let Ok(text) = serde_json::to_string(&event) else { continue };
+                        if session.text(text).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
+
+// 同时存在的上传进度订阅数量上限
+const MAX_EVENT_STREAMS: usize = 64;
+// 两次进度推送之间的最短间隔，避免每个数据块都推送一次
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+// 没有事件时定期发送注释行，及时发现已断开的连接
+const EVENTS_KEEPALIVE: Duration = Duration::from_secs(15);
+
+static ACTIVE_EVENT_STREAMS: AtomicUsize = AtomicUsize::new(0);
+
+// 单个上传的进度
+#[derive(Debug, Clone, Serialize)]
+struct UploadProgress {
+    id: String,
+    received: u64,       // 已收到的字节数
+    total: Option<u64>,  // Content-Length 给出的总大小，分块传输时未知
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<u16>,  // 上传结束时的响应状态码，中断时为空
+    #[serde(skip)]
+    finished: bool,
+}
+
+// 进行中的上传，按会话分组；会话标识由前端生成并随上传请求发送，订阅时只能看到同一会话的上传
+struct ProgressHub {
+    uploads: std::sync::Mutex<std::collections::HashMap<String, BTreeMap<String, UploadProgress>>>,
+    events: tokio::sync::broadcast::Sender<(String, UploadProgress)>,
+}
+
+fn progress_hub() -> &'static ProgressHub {
+    static HUB: std::sync::OnceLock<ProgressHub> = std::sync::OnceLock::new();
+    HUB.get_or_init(|| ProgressHub {
+        uploads: Default::default(),
+        events: tokio::sync::broadcast::channel(256).0,
+    })
+}
+
+impl ProgressHub {
+    fn publish(&self, session: &str, progress: UploadProgress) {
+        let mut uploads = self.uploads.lock().unwrap();
+        if progress.finished {
+            if let Some(session_uploads) = uploads.get_mut(session) {
+                session_uploads.remove(&progress.id);
+                if session_uploads.is_empty() {
+                    uploads.remove(session);
+                }
+            }
+        } else {
+            uploads.entry(session.to_string()).or_default().insert(progress.id.clone(), progress.clone());
+        }
+        // 没有订阅者时发送失败，忽略即可
+        let _ = self.events.send((session.to_string(), progress));
+    }
+
+    fn snapshot(&self, session: &str) -> Vec<UploadProgress> {
+        self.uploads.lock().unwrap()
+            .get(session)
+            .map(|uploads| uploads.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+// 记录一个上传的进度，由读取请求体的任务与处理函数共同持有，全部释放时推送结束事件
+struct UploadTracker {
+    session: String,
+    id: String,
+    total: Option<u64>,
+    received: AtomicU64,
+    last_sent: std::sync::Mutex<std::time::Instant>,
+    status: std::sync::Mutex<Option<u16>>,
+}
+
+impl UploadTracker {
+    // 请求同时带有 X-Upload-Session 与 X-Upload-Id 时才记录进度
+    fn from_request(req: &HttpRequest, total: Option<u64>) -> Option<Arc<Self>> {
+        let header = |name: &str| req.headers().get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty() && v.len() <= 128)
+            .map(str::to_string);
+        let tracker = Arc::new(UploadTracker {
+            session: header("x-upload-session")?,
+            id: header("x-upload-id")?,
+            total,
+            received: AtomicU64::new(0),
+            last_sent: std::sync::Mutex::new(std::time::Instant::now()),
+            status: std::sync::Mutex::new(None),
+        });
+        tracker.publish(false);
+        Some(tracker)
+    }
+
+    fn progress(&self, finished: bool) -> UploadProgress {
+        UploadProgress {
+            id: self.id.clone(),
+            received: self.received.load(Ordering::SeqCst),
+            total: self.total,
+            status: *self.status.lock().unwrap(),
+            finished,
+        }
+    }
+
+    fn publish(&self, finished: bool) {
+        progress_hub().publish(&self.session, self.progress(finished));
+    }
+
+    fn advance(&self, len: u64) {
+        self.received.fetch_add(len, Ordering::SeqCst);
+        let mut last_sent = self.last_sent.lock().unwrap();
+        if last_sent.elapsed() >= PROGRESS_INTERVAL {
+            *last_sent = std::time::Instant::now();
+            drop(last_sent);
+            self.publish(false);
+        }
+    }
+
+    fn finish(&self, status: StatusCode) {
+        *self.status.lock().unwrap() = Some(status.as_u16());
+    }
+}
+
+impl Drop for UploadTracker {
+    fn drop(&mut self) {
+        self.publish(true);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    session: String,
+}
+
+// 一条 SSE 消息
+fn sse_event(progress: &UploadProgress) -> web::Bytes {
+    let event = if progress.finished { "end" } else { "progress" };
+    let data = serde_json::to_string(progress).unwrap_or_default();
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+// 以 Server-Sent Events 推送指定会话中进行中的上传进度；连接建立时先发送当前进度
+#[get("/events")]
+async fn upload_events(query: web::Query<EventsQuery>, config: web::Data<Config>) -> HttpResponse {
+    if !config.upload.progress_events {
+        return HttpResponse::NotFound().finish();
+    }
+    let Some(slot) = StreamSlot::acquire(&ACTIVE_EVENT_STREAMS, MAX_EVENT_STREAMS) else {
+        return HttpResponse::ServiceUnavailable()
+            .insert_header((header::RETRY_AFTER, "30"))
+            .finish();
+    };
+
+    let hub = progress_hub();
+    let session = query.into_inner().session;
+    // 先订阅再取当前进度，两者之间发生的更新不会丢失
+    let events = hub.events.subscribe();
+    let pending: std::collections::VecDeque<web::Bytes> = hub.snapshot(&session).iter().map(sse_event).collect();
+
+    // 客户端断开后响应体被丢弃，订阅与连接名额随之释放
+    let stream = futures_util::stream::unfold(
+        (slot, events, pending, session),
+        |(slot, mut events, mut pending, session)| async move {
+            if let Some(chunk) = pending.pop_front() {
+                return Some((Ok::<_, Error>(chunk), (slot, events, pending, session)));
+            }
+            loop {
+                tokio::select! {
+                    received = events.recv() => match received {
+                        Ok((owner, progress)) if owner == session => {
+                            return Some((Ok(sse_event(&progress)), (slot, events, pending, session)));
+                        }
+                        Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                    },
+                    _ = tokio::time::sleep(EVENTS_KEEPALIVE) => {
+                        return Some((Ok(web::Bytes::from_static(b": keepalive\n\n")), (slot, events, pending, session)));
+                    }
+                }
+            }
+        },
+    );
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // 压缩与代理缓冲都会让事件攒在一起才发出
+        .insert_header((header::CONTENT_ENCODING, "identity"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream)
+}
+
+// 去重存储的内容目录，文件以 SHA-256 命名
+const BLOB_DIR: &str = "data/blobs";
+
+// 文件是否与其他路径共享同一份内容（硬链接数大于 1）
+#[cfg(unix)]
+fn is_shared_file(md: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    md.nlink() > 1
+}
+
+// 无法读取链接数的平台上按共享处理，宁可多解除一次链接
+#[cfg(not(unix))]
+fn is_shared_file(_md: &fs::Metadata) -> bool {
+    true
+}
+
+// 文件与其他路径共享内容时，复制一份再原子地替换原路径，内容保持不变；
+// 未开启去重时也可能遇到之前开启时留下的共享文件，因此不看当前配置
+fn unshare_file(path: &Path) -> std::io::Result<()> {
+    if !fs::symlink_metadata(path).is_ok_and(|md| md.is_file() && is_shared_file(&md)) {
+        return Ok(());
+    }
+    let tmp = path.with_file_name(format!(".webdisk-unshare-{:016x}", thread_rng().gen::<u64>()));
+    let result = fs::copy(path, &tmp).and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+// 将文件内容存入 BLOB_DIR，并让原路径成为指向该内容的硬链接；
+// 文件系统不支持硬链接（或与 data 不在同一文件系统）时返回错误，原文件保持不变
+fn dedup_file(path: &Path, blob_dir: &Path) -> std::io::Result<()> {
+    let hash = hash_file::<Sha256>(path)?;
+
+    fs::create_dir_all(blob_dir)?;
+    let blob = blob_dir.join(&hash);
+    if blob.exists() {
+        // 已有相同内容：先在同目录建立临时链接，再原子地替换刚上传的文件
+        let tmp = path.with_file_name(format!(".{}.dedup", hash));
+        fs::hard_link(&blob, &tmp)?;
+        if let Err(e) = fs::rename(&tmp, path) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+    } else {
+        fs::hard_link(path, &blob)?;
+    }
+    Ok(())
+}
+
+// 健康检查，同时报告文件存储目录所在磁盘的剩余空间
+#[get("/healthz")]
+async fn healthz(config: web::Data<Config>) -> HttpResponse {
+    let free_bytes = fs2::available_space(&config.cwd).ok();
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "ok",
+        "free_bytes": free_bytes,
+    }))
+}
+
+// 版本信息，构建细节仅在配置开启时返回
+#[get("/version")]
+async fn version_info(config: web::Data<Config>) -> HttpResponse {
+    let mut info = serde_json::json!({
+        "version": VERSION,
+        "authors": AUTHORS,
+        "description": DESCRIPTION,
+    });
+    if config.expose_build_info {
+        let build_time = env!("WEBDISK_BUILD_TIMESTAMP")
+            .parse::<i64>()
+            .ok()
+            .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+            .map(|t| t.to_rfc3339());
+        info["git_commit"] = serde_json::json!(option_env!("WEBDISK_GIT_SHA"));
+        info["build_timestamp"] = serde_json::json!(build_time);
+        info["rustc_version"] = serde_json::json!(option_env!("WEBDISK_RUSTC_VERSION"));
+    }
+    HttpResponse::Ok().json(info)
+}
+
+// WebDAV 根目录对应的受保护路径，根目录可能与网页的 cwd 不同
+struct DavProtectedPaths(ProtectedPaths);
+
+// WebDAV 锁管理器，所有请求与工作线程共享同一份锁状态
+#[derive(Clone)]
+struct DavLocks(Box<MemLs>);
+
+// 由 WebDAV 处理的方法，OPTIONS 由 options_handler 统一应答
+const DAV_METHODS: &[&str] = &["GET", "HEAD", "PUT", "DELETE", "COPY", "MOVE", "MKCOL",
+                               "PROPFIND", "PROPPATCH", "LOCK", "UNLOCK", "POST"];
+
+// 修改 WebDAV 处理函数，挂载路径可配置，因此在 main 中手动注册路由
+// WebDAV 文件系统：整体写入（PUT）先写到同目录下的临时文件，写完后再原子地重命名为目标文件，
+// 上传中断或出错时目标文件保持原样；其余操作原样交给 LocalFs
+#[derive(Clone)]
+struct AtomicPutFs {
+    inner: Box<LocalFs>,
+    root: PathBuf,  // inner 对应的本地目录
+    pending: Arc<std::sync::Mutex<Vec<DavPath>>>,  // 尚未提交的临时文件
+}
+
+impl AtomicPutFs {
+    fn new(inner: Box<LocalFs>, root: &Path) -> Self {
+        AtomicPutFs { inner, root: root.to_path_buf(), pending: Arc::default() }
+    }
+
+    // 删除所有未提交的临时文件
+    async fn discard_pending(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        for temp in pending {
+            let _ = self.inner.remove_file(&temp).await;
+        }
+    }
+}
+
+impl DavFileSystem for AtomicPutFs {
+    fn open<'a>(&'a self, path: &'a DavPath, options: OpenOptions) -> FsFuture<'a, Box<dyn DavFile>> {
+        Box::pin(async move {
+            // 分段更新（Content-Range）需要在原文件上修改，不经过临时文件；
+            // 文件由去重存储共享内容时先复制出独立的一份，避免改动所有共享该内容的文件。
+            // 此时 dav-server 已完成锁与 If 头的检查
+            if !(options.write && options.truncate) {
+                if options.write {
+                    let local = self.root.join(path.as_rel_ospath());
+                    web::block(move || unshare_file(&local)).await
+                        .map_err(|_| FsError::GeneralFailure)?
+                        .map_err(|_| FsError::GeneralFailure)?;
+                }
+                return self.inner.open(path, options).await;
+            }
+            match self.inner.metadata(path).await {
+                Ok(_) if options.create_new => return Err(FsError::Exists),
+                Ok(meta) if meta.is_dir() => return Err(FsError::Forbidden),
+                Err(FsError::NotFound) if !options.create => return Err(FsError::NotFound),
+                _ => {}
+            }
+
+            let mut dir = path.parent().as_url_string();
+            if !dir.ends_with('/') {
+                dir.push('/');
+            }
+            let temp = DavPath::new(&format!("{}.webdisk-upload-{:016x}", dir, thread_rng().gen::<u64>()))
+                .map_err(|_| FsError::GeneralFailure)?;
+            let file = self.inner.open(&temp, OpenOptions { create_new: true, ..options.clone() }).await?;
+            self.pending.lock().unwrap().push(temp.clone());
+            Ok(Box::new(AtomicPutFile {
+                file,
+                fs: self.clone(),
+                temp,
+                target: path.clone(),
+                expected: options.size,
+                written: 0,
+            }) as Box<dyn DavFile>)
+        })
+    }
+
+    fn read_dir<'a>(&'a self, path: &'a DavPath, meta: ReadDirMeta) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
+        self.inner.read_dir(path, meta)
+    }
+
+    fn metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn create_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        self.inner.create_dir(path)
+    }
+
+    fn remove_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        self.inner.remove_dir(path)
+    }
+
+    fn remove_file<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        self.inner.remove_file(path)
+    }
+
+    fn rename<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        self.inner.rename(from, to)
+    }
+
+    fn copy<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        self.inner.copy(from, to)
+    }
+}
+
+// 写入临时文件的 PUT 目标，flush 时确认内容完整后才替换目标文件
+struct AtomicPutFile {
+    file: Box<dyn DavFile>,
+    fs: AtomicPutFs,
+    temp: DavPath,
+    target: DavPath,
+    expected: Option<u64>,  // Content-Length 给出的大小
+    written: u64,
+}
+
+impl std::fmt::Debug for AtomicPutFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AtomicPutFile")
+            .field("temp", &self.temp)
+            .field("target", &self.target)
+            .field("written", &self.written)
+            .finish()
+    }
+}
+
+impl DavFile for AtomicPutFile {
+    fn metadata(&mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        self.file.metadata()
+    }
+
+    fn write_buf(&mut self, buf: Box<dyn web::Buf + Send>) -> FsFuture<'_, ()> {
+        self.written += buf.remaining() as u64;
+        self.file.write_buf(buf)
+    }
+
+    fn write_bytes(&mut self, buf: web::Bytes) -> FsFuture<'_, ()> {
+        self.written += buf.len() as u64;
+        self.file.write_bytes(buf)
+    }
+
+    fn read_bytes(&mut self, count: usize) -> FsFuture<'_, web::Bytes> {
+        self.file.read_bytes(count)
+    }
+
+    fn seek(&mut self, pos: std::io::SeekFrom) -> FsFuture<'_, u64> {
+        self.file.seek(pos)
+    }
+
+    fn flush(&mut self) -> FsFuture<'_, ()> {
+        Box::pin(async move {
+            self.file.flush().await?;
+            // 实际写入的字节数与 Content-Length 不符时不提交，dav_server 随后按残缺上传应答 400
+            if self.expected.is_some_and(|len| len != self.written) {
+                return Ok(());
+            }
+            self.fs.inner.rename(&self.temp, &self.target).await?;
+            self.fs.pending.lock().unwrap().retain(|p| p != &self.temp);
+            Ok(())
+        })
+    }
+}
+
+// 处理过程被取消（如客户端断开后请求被丢弃）时，在后台删除残留的临时文件
+struct DiscardOnDrop(AtomicPutFs);
+
+impl Drop for DiscardOnDrop {
+    fn drop(&mut self) {
+        if self.0.pending.lock().is_ok_and(|p| !p.is_empty()) {
+            let fs = self.0.clone();
+            actix_web::rt::spawn(async move { fs.discard_pending().await });
+        }
+    }
+}
+
+// 各方法需要的权限（读取，修改）；COPY 与 MOVE 要读取源文件，两者都需要。
+// 只查询目录本身的 PROPFIND（Depth: 0）不列出内容，不要求读取权限，客户端上传前通常会先确认目录存在
+fn dav_method_access(req: &HttpRequest) -> (bool, bool) {
+    match req.method().as_str() {
+        "PUT" | "DELETE" | "MKCOL" | "PROPPATCH" | "LOCK" | "UNLOCK" => (false, true),
+        "COPY" | "MOVE" => (true, true),
+        "PROPFIND" if req.headers().get("Depth").is_some_and(|d| d == "0") => (false, false),
+        _ => (true, false),
+    }
+}
+
+// 投递箱模式（只能上传）允许的请求：上传文件、创建目录、加锁解锁，以及查询目录本身的属性（Depth: 0）
+fn drop_box_allows(req: &HttpRequest) -> bool {
+    match req.method().as_str() {
+        "PUT" | "MKCOL" | "LOCK" | "UNLOCK" => true,
+        "PROPFIND" => req.headers().get("Depth").is_some_and(|d| d == "0"),
+        _ => false,
+    }
+}
+
+// WebDAV 请求目标所在的目录：目标是目录时为其本身，否则为上级目录
+fn dav_target_dir(target: &Path) -> PathBuf {
+    if target.is_dir() {
+        target.to_path_buf()
+    } else {
+        target.parent().map(Path::to_path_buf).unwrap_or_else(|| target.to_path_buf())
+    }
+}
+
+// 浏览器发出的 GET/HEAD 请求（Accept 中包含 text/html）
+fn accepts_html(req: &HttpRequest) -> bool {
+    matches!(req.method().as_str(), "GET" | "HEAD")
+        && req.headers().get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/html"))
+}
+
+// 创建用户独立的 WebDAV 根目录，Unix 下仅服务进程的用户可访问
+fn create_user_root(path: &Path) -> std::io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(path)
+}
+
+// 用户书签保存在单独的文件中，避免频繁改写配置文件；键为用户名，值为相对于用户根目录的目录路径
+const BOOKMARKS_FILE: &str = "data/bookmarks.json";
+const MAX_BOOKMARKS: usize = 50;
+
+fn bookmarks_lock() -> &'static std::sync::Mutex<()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    &LOCK
+}
+
+fn load_bookmarks(file: &Path) -> BTreeMap<String, Vec<String>> {
+    fs::read_to_string(file).ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+// 添加或删除一个书签，先写临时文件再重命名，避免写到一半留下损坏的文件
+fn update_bookmarks(file: &Path, user: &str, dir: &str, add: bool) -> std::io::Result<()> {
+    let _guard = bookmarks_lock().lock().unwrap_or_else(|e| e.into_inner());
+    let mut all = load_bookmarks(file);
+    let list = all.entry(user.to_string()).or_default();
+    list.retain(|p| p != dir);
+    if add {
+        if list.len() >= MAX_BOOKMARKS {
+            return Err(std::io::Error::other(format!("书签数量已达上限 {}", MAX_BOOKMARKS)));
+        }
+        list.push(dir.to_string());
+    }
+    if list.is_empty() {
+        all.remove(user);
+    }
+    let tmp = file.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(&all).map_err(std::io::Error::other)?)?;
+    fs::rename(&tmp, file)
+}
+
+// 跨站提交的表单不能修改书签：带有 Origin 时须与当前站点一致
+fn same_origin(req: &HttpRequest) -> bool {
+    let Some(origin) = req.headers().get(header::ORIGIN).and_then(|v| v.to_str().ok()) else {
+        return true;
+    };
+    let conn = req.connection_info();
+    origin == format!("{}://{}", conn.scheme(), conn.host())
+}
+
+#[derive(Debug, Deserialize)]
+struct BookmarkQuery {
+    bookmark: String,  // add 或 remove
+}
+
+// 记录被拒绝的 WebDAV 请求，便于发现密码猜测与权限配置问题；未携带认证信息的质询不记录
+fn log_dav_denied(req: &HttpRequest, user: Option<&str>, reason: &str) {
+    tracing::warn!(
+        event = "webdav_denied",
+        addr = req.connection_info().realip_remote_addr().unwrap_or("-"),
+        user,
+        method = %req.method(),
+        path = req.path(),
+        reason,
+        "WebDAV 请求被拒绝",
+    );
+}
+
+async fn webdav_handler(
+    req: HttpRequest,
+    mut payload: web::Payload,
+    config: web::Data<Config>,
+    protected: web::Data<DavProtectedPaths>,
+    locks: Option<web::Data<DavLocks>>,
+) -> Result<HttpResponse, Error> {
+    if !config.webdav.enabled {
+        return Ok(HttpResponse::NotFound().body("WebDAV service is disabled"));
+    }
+
+    // 只读模式下直接拒绝所有会修改数据的方法
+    if config.webdav.read_only && matches!(req.method().as_str(),
+        "PUT" | "DELETE" | "MKCOL" | "COPY" | "MOVE" | "PROPPATCH" | "LOCK"
+    ) {
+        return Ok(HttpResponse::Forbidden().body("WebDAV is in read-only mode"));
+    }
+
+    let tail = percent_decode_str(req.match_info().query("tail")).decode_utf8_lossy()
+        .trim_start_matches('/')
+        .to_string();
+    // 投递箱与书签按 tail 判断目录，含 .. 的路径会在之后被 dav-server 解析到别处，直接拒绝
+    if join_in_cwd("", &tail).is_none() {
+        return Ok(HttpResponse::BadRequest().body("Invalid path"));
+    }
+
+    // 添加基本认证检查
+    let mut principal = None;
+    let mut landing_path = None;
+    let mut user_root = false;
+    let mut drop_box = false;
+    let dav_root = config.webdav.root_dir(&config.cwd);
+    let mut base = PathBuf::from(dav_root);
+    if let Some(auth) = req.headers().get(header::AUTHORIZATION) {
+        let auth_str = auth.to_str().map_err(|_| {
+            actix_web::error::ErrorUnauthorized("Invalid authorization header")
+        })?;
+
+        if let Some(encoded) = auth_str.strip_prefix("Basic ") {
+            let credentials = BASE64.decode(encoded).map_err(|_| {
+                actix_web::error::ErrorUnauthorized("Invalid base64 in authorization")
+            })?;
+
+            let credentials_str = String::from_utf8(credentials).map_err(|_| {
+                actix_web::error::ErrorUnauthorized("Invalid UTF-8 in authorization")
+            })?;
+
+            let parts: Vec<&str> = credentials_str.splitn(2, ':').collect();
+            if parts.len() == 2 {
+                let username = parts[0];
+                let password = parts[1];
+
+                // 用户名不存在与密码错误返回相同的应答
+                if let Some(user_config) = authenticate(&config, username, password).await {
+                    principal = Some(username.to_string());
+                    landing_path = user_config.landing_path.clone();
+                    base = user_config.root_dir(dav_root);
+                    user_root = user_config.root.is_some();
+
+                    // 检查权限：读取与修改分别需要 r 与 w
+                    let (need_read, need_write) = dav_method_access(&req);
+                    let can_read = user_config.permissions.contains('r');
+                    let can_write = user_config.permissions.contains('w');
+                    if need_write && !can_write {
+                        log_dav_denied(&req, Some(username), "write_permission");
+                        return Ok(HttpResponse::Forbidden().body("Write permission required"));
+                    }
+                    if need_read && !can_read {
+                        log_dav_denied(&req, Some(username), "read_permission");
+                        return Ok(HttpResponse::Forbidden().body("Read permission required"));
+                    }
+
+                    // 只有 w 权限的用户只能上传（投递箱）：可以写入新文件、创建目录，
+                    // 不能删除、移动或改动看不到的已有文件
+                    if !can_read {
+                        if !drop_box_allows(&req) {
+                            return Ok(HttpResponse::Forbidden().body("Read permission required"));
+                        }
+                        drop_box = true;
+                    }
+                } else {
+                    let reason = if config.webdav.users.contains_key(username) { "invalid_password" } else { "unknown_user" };
+                    log_dav_denied(&req, Some(username), reason);
+                    return Ok(HttpResponse::Unauthorized()
+                        .append_header((header::WWW_AUTHENTICATE, "Basic realm=\"WebDAV Server\""))
+                        .body("Invalid credentials"));
+                }
+            } else {
+                log_dav_denied(&req, None, "malformed_credentials");
+                return Ok(HttpResponse::Unauthorized()
+                    .append_header((header::WWW_AUTHENTICATE, "Basic realm=\"WebDAV Server\""))
+                    .body("Invalid credentials"));
+            }
+        } else {
+            // 不支持的认证方式不能被当作匿名访问
+            log_dav_denied(&req, None, "unsupported_scheme");
+            return Ok(HttpResponse::Unauthorized()
+                .append_header((header::WWW_AUTHENTICATE, "Basic realm=\"WebDAV Server\""))
+                .body("Unsupported authorization scheme"));
+        }
+    } else if config.webdav.allow_anonymous
+        && drop_box_allows(&req)
+        && is_write_only(dav_root, &dav_target_dir(&base.join(&tail)))
+    {
+        // 目录设置了 write_only 时，允许匿名访问的情况下未登录也可以向其中上传
+        drop_box = true;
+    } else if !(config.webdav.allow_anonymous
+        && matches!(req.method().as_str(), "GET" | "HEAD" | "PROPFIND"))
+    {
+        // 允许匿名访问时，未携带认证信息的只读请求直接放行，写操作仍需登录
+        return Ok(HttpResponse::Unauthorized()
+            .append_header((header::WWW_AUTHENTICATE, "Basic realm=\"WebDAV Server\""))
+            .finish());
+    } else if is_write_only(dav_root, &dav_target_dir(&base.join(&tail))) {
+        return Ok(HttpResponse::Forbidden().body("Upload only"));
+    }
+
+    // 新用户的独立目录在首次访问时创建；共享根目录不存在属于配置问题，不自动创建
+    if user_root && !base.exists() {
+        create_user_root(&base)?;
+    }
+
+    let target = base.join(&tail);
+    if protected.0.contains(&target) {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    // COPY/MOVE 的目标同样不能落在受保护的数据文件上，否则可以覆盖配置或写入去重存储
+    if matches!(req.method().as_str(), "COPY" | "MOVE") {
+        let Some(dest) = dav_destination(&req, config.webdav.mount_prefix()).map(|rel| base.join(rel)) else {
+            return Ok(HttpResponse::BadRequest().body("Invalid destination"));
+        };
+        if protected.0.contains(&dest) || dest.parent().is_some_and(|parent| protected.0.contains(parent)) {
+            return Ok(HttpResponse::Forbidden().body("Protected destination"));
+        }
+    }
+    // 投递箱中的文件按不存在处理，不暴露已有文件
+    if drop_box && req.method().as_str() == "PROPFIND" && !target.is_dir() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    // 列表页面中的书签按钮以 POST ?bookmark=add|remove 提交到当前目录，完成后返回该目录
+    if req.method() == actix_web::http::Method::POST {
+        let action = web::Query::<BookmarkQuery>::from_query(req.query_string()).ok().map(|q| q.into_inner().bookmark);
+        let (Some(user), Some(action @ ("add" | "remove"))) = (principal.as_deref(), action.as_deref()) else {
+            return Ok(HttpResponse::MethodNotAllowed().finish());
+        };
+        if !config.webdav.pretty_index || !target.is_dir() {
+            return Ok(HttpResponse::MethodNotAllowed().finish());
+        }
+        if !same_origin(&req) {
+            return Ok(HttpResponse::Forbidden().body("Cross-origin request"));
+        }
+        // 按规范化后的相对路径保存，a//b/ 与 ./a/b 视为同一个目录
+        let Some(dir) = join_in_cwd("", &tail) else {
+            return Ok(HttpResponse::BadRequest().body("Invalid path"));
+        };
+        let dir = dir.iter().map(|part| part.to_string_lossy()).collect::<Vec<_>>().join("/");
+        let user = user.to_string();
+        let add = action == "add";
+        web::block(move || update_bookmarks(Path::new(BOOKMARKS_FILE), &user, &dir, add)).await?
+            .map_err(actix_web::error::ErrorBadRequest)?;
+        return Ok(HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, req.uri().path().to_string()))
+            .finish());
+    }
+
+    // 浏览器访问目录时使用与网页相同的列表页面，同步客户端（PROPFIND 或不接受 HTML 的 GET）不受影响
+    if config.webdav.pretty_index && accepts_html(&req) && target.is_dir() {
+        if !req.path().ends_with('/') {
+            return Ok(redirect_canonical(&req, &format!("{}/", percent_decode_str(req.path()).decode_utf8_lossy())));
+        }
+        // 直接打开根地址（没有 Referer，如登录后或从浏览器书签进入）时跳转到用户的默认目录；
+        // 从页面内返回根目录时不跳转
+        let landing = landing_path.as_deref()
+            .map(|p| p.trim_matches(['/', '\\']))
+            .filter(|p| tail.is_empty() && !p.is_empty() && !req.headers().contains_key(header::REFERER));
+        if let Some(landing) = landing {
+            let dir = base.join(landing);
+            let inside = matches!((fs::canonicalize(&dir), fs::canonicalize(&base)), (Ok(dir), Ok(root)) if dir.starts_with(&root));
+            if inside && dir.is_dir() && !protected.0.contains(&dir) {
+                return Ok(HttpResponse::Found()
+                    .insert_header((header::LOCATION, format!("{}/{}/", config.webdav.mount_prefix(), encode_path(landing))))
+                    .finish());
+            }
+        }
+        let query = web::Query::<IndexQuery>::from_query(req.query_string())
+            .map(|q| q.into_inner())
+            .unwrap_or_default();
+        let bookmarks = principal.as_ref()
+            .map(|user| load_bookmarks(Path::new(BOOKMARKS_FILE)).remove(user).unwrap_or_default());
+        let current_path = req.match_info().query("tail").trim_start_matches('/');
+        let page = ListingPage {
+            dir: &target,
+            root: &base.to_string_lossy(),
+            current_path,
+            webdav: true,
+            bookmarks: bookmarks.as_deref(),
+        };
+        return render_listing(&req, &config, Arc::new(LocalStorage), &protected.0, &query, ui_strings(&config, &req), page).await;
+    }
+
+    let filesystem = AtomicPutFs::new(LocalFs::new(&base, true, true, false), &base);
+    let _discard = DiscardOnDrop(filesystem.clone());
+    let mut builder = DavHandler::builder()
+        .filesystem(Box::new(filesystem.clone()))
+        .strip_prefix(config.webdav.mount_prefix())
+        .autoindex(true);
+
+    // 启用锁管理时，锁令牌按用户归属，冲突的写入会被拒绝（423 Locked）
+    if let Some(locks) = locks {
+        builder = builder.locksystem(locks.0.clone());
+        if let Some(principal) = principal {
+            builder = builder.principal(principal);
+        }
+    }
+
+    let handler = builder.build_handler();
+
+    // 只转发 path 与 query，保持客户端原始的百分号编码；
+    // 绝对形式的请求目标（含协议与主机）会被规范为 origin 形式
+    let mut put_target = None;
+    let mut tracker = None;
+    let mut uri = req.uri()
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or("/")
+        .to_string();
+
+    if req.method() == hyper::Method::PUT {
+        // 已知上传大小时预先检查磁盘空间，避免写到一半失败留下残缺文件；
+        // 大小未知时由底层在磁盘写满时返回 507
+        let incoming = req.headers().get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        if let Some(len) = incoming {
+            if !has_space_for(&base, len, config.upload.min_free_bytes) {
+                return Ok(HttpResponse::InsufficientStorage().body("Insufficient storage"));
+            }
+        }
+        if config.upload.progress_events {
+            tracker = UploadTracker::from_request(&req, incoming);
+        }
+
+        // 清理文件名并按冲突策略处理已存在的目标
+        let rel = req.path().strip_prefix(config.webdav.mount_prefix()).unwrap_or("");
+        if let Some((parent, last)) = rel.rsplit_once('/') {
+            let original = percent_decode_str(last).decode_utf8_lossy().to_string();
+            let dir = base.join(
+                percent_decode_str(parent).decode_utf8_lossy().trim_start_matches('/')
+            );
+            let clean = sanitize_upload_name(&original);
+            // 投递箱不能覆盖看不到的已有文件，同名时改为自动重命名
+            let policy = match config.upload.on_conflict {
+                ConflictPolicy::Overwrite if drop_box => ConflictPolicy::Rename,
+                policy => policy,
+            };
+            match resolve_upload_name(&dir, &clean, policy) {
+                None => return Ok(HttpResponse::Conflict().body("File already exists")),
+                Some(name) => {
+                    if name != original {
+                        uri = format!("{}{}/{}", config.webdav.mount_prefix(), parent, utf8_percent_encode(&name, PATH_SEGMENT));
+                        if let Some(query) = req.uri().query() {
+                            uri = format!("{}?{}", uri, query);
+                        }
+                    }
+                    put_target = Some(dir.join(name));
+                }
+            }
+        }
+    }
+
+    let mut dav_req = hyper::Request::builder()
+        .method(req.method().clone())
+        .uri(uri)
+        .version(req.version());
+
+    for (name, value) in req.headers() {
+        dav_req = dav_req.header(name, value);
+    }
+
+    // 任何带请求体的方法都原样转发（PUT 的文件内容、PROPFIND/PROPPATCH/LOCK 的 XML 等）
+    let has_body = req.headers().contains_key(header::TRANSFER_ENCODING)
+        || req.headers().get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+            .is_some_and(|len| len > 0);
+
+    let body_aborted = Arc::new(AtomicBool::new(false));
+    let body = if has_body {
+        let (mut tx, body) = hyper::Body::channel();
+        let idle_secs = config.server.body_idle_timeout_secs;
+        let idle = if idle_secs == 0 { Duration::MAX } else { Duration::from_secs(idle_secs) };
+        let aborted = body_aborted.clone();
+        let progress = tracker.clone();
+        
+        actix_web::rt::spawn(async move {
+            loop {
+                match tokio::time::timeout(idle, payload.next()).await {
+                    Ok(Some(Ok(chunk))) => {
+                        if let Some(progress) = &progress {
+                            progress.advance(chunk.len() as u64);
+                        }
+                        if tx.send_data(chunk).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    // 读取出错或客户端长时间不发送数据时中止请求体，
+                    // 避免处理程序把残缺的内容当作完整上传保存
+                    Ok(Some(Err(_))) | Err(_) => {
+                        aborted.store(true, Ordering::SeqCst);
+                        tx.abort();
+                        break;
+                    }
+                }
+            }
+        });
+
+        body
+    } else {
+        hyper::Body::empty()
+    };
+
+    let dav_req = dav_req.body(body)
+        .unwrap_or_else(|_| hyper::Request::new(hyper::Body::empty()));
+
+    let dav_resp = handler.handle(dav_req).await;
+    let (parts, body) = dav_resp.into_parts();
+
+    // 上传未完成时内容只写进了临时文件，删除即可，目标文件不受影响
+    filesystem.discard_pending().await;
+    if body_aborted.load(Ordering::SeqCst) {
+        return Ok(HttpResponse::RequestTimeout().body("Request body incomplete"));
+    }
+    if let Some(tracker) = &tracker {
+        tracker.finish(parts.status);
+    }
+
+    if config.upload.dedup && parts.status.is_success() {
+        if let Some(target) = put_target {
+            let result = web::block(move || dedup_file(&target, Path::new(BLOB_DIR))).await;
+            if let Ok(Err(e)) | Err(e) = result.map_err(std::io::Error::other) {
+                tracing::warn!(event = "dedup_failed", error = %e, "去重存储失败，已保留为普通文件");
+            }
+        }
+    }
+    let mut builder = HttpResponse::build(parts.status);
+
+    // 长度与传输编码由 actix 重新生成，原样复制会导致重复或冲突的响应头
+    let etag_present = parts.headers.contains_key(header::ETAG);
+    let accept_ranges_present = parts.headers.contains_key(header::ACCEPT_RANGES);
+    let content_length = parts.headers.get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+
+    for (name, value) in parts.headers {
+        if let Some(name) = name {
+            if name == header::CONTENT_LENGTH || name == header::TRANSFER_ENCODING {
+                continue;
+            }
+            builder.append_header((name, value));
+        }
+    }
+
+    if let Some(len) = content_length {
+        builder.no_chunking(len);
+    }
+
+    // 文件下载响应始终声明支持断点续传
+    let is_download = matches!(req.method().as_str(), "GET" | "HEAD")
+        && matches!(parts.status.as_u16(), 200 | 206)
+        && etag_present;
+    if is_download && !accept_ranges_present {
+        builder.append_header((header::ACCEPT_RANGES, "bytes"));
+    }
+    // WebDAV 下载的 SVG 同样不允许在浏览器中直接执行脚本
+    if is_download && !config.trust_svg && is_svg(Path::new(req.path())) {
+        builder.insert_header((header::CONTENT_SECURITY_POLICY, SVG_CSP));
+        builder.insert_header((header::CONTENT_DISPOSITION, "attachment"));
+    }
+
+    Ok(builder.streaming(body))
+}
+
+const TEMPLATE: &str = r#"
+<!DOCTYPE html>
+<html lang="{{t.lang}}">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>{{branding.site_title}}</title>
+    <link rel="icon" href="/favicon.ico"/>
+    {% if pwa.enabled %}
+    <link rel="manifest" href="/manifest.json"/>
+    <link rel="apple-touch-icon" href="{{pwa_icon}}"/>
+    <meta name="theme-color" content="{{pwa.theme_color}}">
+    <meta name="mobile-web-app-capable" content="yes">
+    <meta name="apple-mobile-web-app-capable" content="yes">
+    <script>
+        if ('serviceWorker' in navigator) {
+            navigator.serviceWorker.register('/sw.js');
+        }
+    </script>
+    {% endif %}
+    <style>
+        body {
+            font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, "Helvetica Neue", Arial, sans-serif;
+            margin: 20px;
+            background-color: #f8f9fa;
+        }
+        .entry {
+            display: flex;
+            align-items: center;
+            padding: 15px;
+            margin: 5px 0;
+            background-color: white;
+            border-radius: 8px;
+            box-shadow: 0 1px 3px rgba(0,0,0,0.1);
+        }
+        .entry:hover {
+            background-color: #f8f9fa;
+        }
+        .info-group {
+            display: flex;
+            align-items: center;
+            gap: 20px;
+            margin-left: auto;
+        }
+        a {
+            text-decoration: none;
+            color: inherit;
+        }
+        a:hover {
+            text-decoration: underline;
+        }
+        .logo {
+            height: 1.5em;
+            vertical-align: middle;
+            margin-right: 10px;
+        }
+        h1 {
+            color: #333;
+            border-bottom: 2px solid #ddd;
+            padding-bottom: 10px;
+            font-size: 1.5em;
+            word-break: break-all;
+        }
+        .breadcrumbs a {
+            color: #2196F3;
+            text-decoration: none;
+        }
+        .breadcrumbs a:hover {
+            text-decoration: underline;
+        }
+        .crumb-sep {
+            margin: 0 6px;
+            color: #aaa;
+        }
+        .name-column {
+            flex: 2;
+            min-width: 0;
+            overflow: visible;
+            text-overflow: ellipsis;
+            white-space: normal;
+            word-break: break-all;
+        }
+        .size-column {
+            flex: 0.8;
+            text-align: right;
+            min-width: 80px;
+        }
+        .perm-column, .downloads-column, .type-column {
+            text-align: right;
+            white-space: nowrap;
+            color: #666;
+            font-size: 0.9em;
+            min-width: 60px;
+        }
+        .perm-column {
+            font-family: monospace;
+        }
+        .date-column {
+            flex: 1.2;
+            text-align: right;
+            white-space: nowrap;
+            min-width: 150px;
+        }
+        .preview-container {
+            display: none;
+            margin: 8px 0 8px 32px;
+            vertical-align: middle;
+        }
+        .preview-container img {
+            max-width: 160px;
+            max-height: 90px;
+            object-fit: contain;
+            border-radius: 4px;
+            display: block;
+        }
+        .preview-container video {
+            max-width: 160px;
+            max-height: 90px;
+            object-fit: contain;
+            border-radius: 4px;
+            display: block;
+        }
+        .preview-placeholder {
+            display: inline-block;
+            padding: 24px 16px;
+            border: 1px dashed #ccc;
+            border-radius: 4px;
+            color: #6c757d;
+            font-size: 0.9em;
+            cursor: pointer;
+        }
+        .preview-container audio {
+            width: 320px;
+            height: 32px;
+            display: block;
+        }
+        .file-icon {
+            margin-right: 8px;
+            font-size: 1.2em;
+            display: inline-block;
+            width: 32px;
+            text-align: center;
+        }
+        .download-btn {
+            background-color: #4CAF50;
+            color: white;
+            padding: 4px 8px;
+            border-radius: 4px;
+            font-size: 0.8em;
+            text-decoration: none;
+            display: inline-block;
+            margin-right: 10px;
+            min-width: 50px;
+            text-align: center;
+            white-space: nowrap;
+        }
+        .view-btn {
+            background-color: #FF9800;
+            color: white;
+            padding: 4px 8px;
+            border-radius: 4px;
+            font-size: 0.8em;
+            text-decoration: none;
+            display: inline-block;
+            margin-right: 10px;
+            min-width: 50px;
+            text-align: center;
+            white-space: nowrap;
+        }
+        .copy-btn {
+            color: #4CAF50;
+            font-size: 0.8em;
+            cursor: pointer;
+            margin-right: 10px;
+            white-space: nowrap;
+        }
+        .toast {
+            position: fixed;
+            left: 50%;
+            bottom: 40px;
+            transform: translateX(-50%);
+            background-color: rgba(0, 0, 0, 0.75);
+            color: white;
+            padding: 8px 16px;
+            border-radius: 4px;
+            font-size: 14px;
+            display: none;
+            z-index: 1000;
+        }
+        .checksum-btn {
+            color: #607D8B;
+            font-size: 0.75em;
+            text-decoration: none;
+            margin-right: 10px;
+            white-space: nowrap;
+        }
+        .entry-form {
+            display: inline;
+            margin: 0 10px 0 0;
+        }
+        .entry-form button {
+            border: none;
+            background: none;
+            padding: 0;
+            font-size: 0.75em;
+            cursor: pointer;
+        }
+        .rename-btn {
+            color: #607D8B;
+        }
+        .delete-btn {
+            color: #e53935;
+        }
+        .preview-btn {
+            background-color: #2196F3;
+            color: white;
+            padding: 4px 8px;
+            border-radius: 4px;
+            font-size: 0.8em;
+            cursor: pointer;
+            margin-right: 10px;
+            min-width: 50px;
+            text-align: center;
+            white-space: nowrap;
+        }
+        .empty-placeholder {
+            padding: 40px 20px;
+            text-align: center;
+            color: #6c757d;
+            background-color: white;
+            border-radius: 8px;
+        }
+        .empty-placeholder code {
+            user-select: all;
+        }
+        .truncated-notice {
+            padding: 10px 16px;
+            margin-bottom: 16px;
+            border: 1px solid #ffe08a;
+            border-radius: 4px;
+            background-color: #fff8e1;
+            color: #8a6d00;
+        }
+        .readme {
+            padding: 12px 16px;
+            margin-bottom: 16px;
+            border: 1px solid #eee;
+            border-radius: 4px;
+            background-color: #fafafa;
+            overflow-x: auto;
+        }
+        .readme pre {
+            margin: 0;
+            white-space: pre-wrap;
+        }
+        .readme img {
+            max-width: 100%;
+        }
+        .filter-bar {
+            display: flex;
+            flex-wrap: wrap;
+            gap: 8px;
+            margin-bottom: 10px;
+        }
+        .search-bar {
+            display: flex;
+            flex-wrap: wrap;
+            align-items: center;
+            gap: 8px;
+            margin-bottom: 10px;
+        }
+        .search-bar input[type="search"] {
+            flex: 1;
+            max-width: 320px;
+            padding: 4px 10px;
+            border: 1px solid #ccc;
+            border-radius: 16px;
+            font: inherit;
+            font-size: 0.85em;
+        }
+        .bookmark-bar form {
+            margin: 0;
+        }
+        .search-bar button,
+        .bookmark-bar button {
+            border: none;
+            font: inherit;
+            cursor: pointer;
+        }
+        .chip {
+            padding: 4px 12px;
+            border-radius: 16px;
+            background-color: #e9ecef;
+            color: #555;
+            font-size: 0.85em;
+        }
+        .chip.active {
+            background-color: #2196F3;
+            color: white;
+        }
+        .chip.playlist {
+            background-color: #e8f5e9;
+            color: #2e7d32;
+        }
+        .sort-label {
+            padding: 4px 0;
+            color: #888;
+            font-size: 0.85em;
+        }
+        .chip.clear {
+            background-color: #fdecea;
+            color: #e53935;
+        }
+        .selection-bar {
+            display: flex;
+            align-items: center;
+            gap: 15px;
+            margin-bottom: 10px;
+            color: #555;
+            font-size: 0.9em;
+        }
+        .select-box {
+            margin-right: 10px;
+        }
+        .zip-btn {
+            margin-left: auto;
+            background-color: #4CAF50;
+            color: white;
+            border: none;
+            padding: 6px 12px;
+            border-radius: 4px;
+            cursor: pointer;
+        }
+        .zip-btn:disabled {
+            background-color: #bbb;
+            cursor: default;
+        }
+        .unavailable {
+            color: #999;
+            cursor: not-allowed;
+        }
+        .link-target {
+            color: #888;
+            font-size: 0.9em;
+            margin-left: 6px;
+        }
+        .link-target.broken {
+            color: #e53935;
+            text-decoration: line-through;
+        }
+        .note-column {
+            color: #999;
+            font-size: 0.9em;
+        }
+        .view-grid {
+            display: grid;
+            grid-template-columns: repeat(auto-fill, minmax(220px, 1fr));
+            gap: 10px;
+        }
+        .view-grid .entry {
+            flex-direction: column;
+            align-items: flex-start;
+            gap: 8px;
+            margin: 0;
+        }
+        .view-grid .info-group {
+            margin-left: 0;
+            flex-wrap: wrap;
+            gap: 8px;
+        }
+        .error-box {
+            padding: 40px 20px;
+            text-align: center;
+            background-color: white;
+            border-radius: 8px;
+            box-shadow: 0 1px 3px rgba(0,0,0,0.1);
+        }
+        .error-box h2 {
+            color: #e53935;
+            margin-top: 0;
+        }
+        .error-box p {
+            color: #666;
+        }
+        .error-box a {
+            display: inline-block;
+            margin: 10px 5px 0;
+            padding: 6px 14px;
+            border-radius: 4px;
+            background-color: #2196F3;
+            color: white;
+        }
+        .footer {
+            position: fixed;
+            bottom: 0;
+            left: 0;
+            right: 0;
+            width: 100%;
+            text-align: center;
+            padding: 20px 0;
+            background-color: #f8f9fa;
+            border-top: 1px solid #eee;
+        }
+        .footer a {
+            color: #666;
+            text-decoration: none;
+            font-size: 14px;
+            display: block;
+            margin: 0 auto;
+        }
+        .footer p {
+            margin: 5px 0;
+            color: #999;
+            font-size: 12px;
+        }
+        body {
+            margin-bottom: 100px;
+        }
+        @media (max-width: 768px) {
+            body {
+                margin: 10px;
+            }
+            .entry {
+                flex-direction: column;
+                align-items: flex-start;
+                gap: 8px;
+                padding: 12px;
+            }
+            .name-column {
+                flex: 1;
+                width: 100%;
+                margin-bottom: 4px;
+            }
+            .info-group {
+                width: 100%;
+                justify-content: flex-start;
+                flex-wrap: wrap;
+                gap: 10px;
+            }
+            .size-column {
+                min-width: auto;
+                order: 2;
+            }
+            .date-column {
+                min-width: auto;
+                width: 100%;
+                text-align: left;
+                order: 3;
+            }
+            .download-btn {
+                order: 1;
+                margin-right: 0;
+            }
+            h1 {
+                font-size: 1.2em;
+            }
+        }
+    </style>
+</head>
+<body>
+    {% if branding.logo_url %}
+    <a href="/"><img class="logo" src="{{branding.logo_url}}" alt="{{branding.site_title}}"></a>
+    {% endif %}
+    {% if error_message %}
+    <h1>{{error_title}}</h1>
+    <div class="error-box">
+        <h2>{{error_title}}</h2>
+        <p>{{error_message}}</p>
+        <p>/{{current_path | escape}}</p>
+        <a href="{{parent_url}}">{{t.parent_dir}}</a>
+        <a href="/">{{t.root_dir}}</a>
+    </div>
+    {% else %}
+    <h1>{{t.directory}}: <span class="breadcrumbs">{% for crumb in breadcrumbs %}{% if not loop.first %}<span class="crumb-sep">/</span>{% endif %}{% if loop.last %}<span class="crumb-current">{{crumb.name | escape}}</span>{% else %}<a href="{{crumb.url}}">{{crumb.name | escape}}</a>{% endif %}{% endfor %}</span>{% if search %} · {{t.search_results}} “{{search | escape}}”{% if search_recursive %} ({{t.search_recursive}}){% endif %}{% endif %}</h1>
+    {% if show_bookmarks %}
+    <div class="filter-bar bookmark-bar">
+        <span class="sort-label">{{t.bookmarks}}</span>
+        {% for bookmark in bookmarks %}
+        <a href="{{bookmark.url}}" class="chip">★ {{bookmark.name | escape}}</a>
+        {% endfor %}
+        <form method="post" action="./?bookmark={% if bookmarked %}remove{% else %}add{% endif %}">
+            <button type="submit" class="chip">{% if bookmarked %}☆ {{t.remove_bookmark}}{% else %}＋ {{t.add_bookmark}}{% endif %}</button>
+        </form>
+    </div>
+    {% endif %}
+    <form class="search-bar" method="get" action="./">
+        <input type="search" name="q" value="{{search | escape}}" placeholder="{{t.search_placeholder}}">
+        <label class="sort-label"><input type="checkbox" name="recursive" value="true"{% if search_recursive %} checked{% endif %}> {{t.search_recursive}}</label>
+        {% if filter_active %}<input type="hidden" name="type" value="{{filter_key}}">{% endif %}
+        <button type="submit" class="chip">🔍 {{t.search}}</button>
+        {% if search %}<a href="./{% if filter_active %}?type={{filter_key}}{% endif %}" class="chip clear">✕ {{t.search_clear}}</a>{% endif %}
+    </form>
+    <div class="filter-bar">
+        <a href="./{% if search %}?{{search_query | trim_start_matches(pat="&")}}{% endif %}" class="chip{% if not filter_active %} active{% endif %}">{{t.filter_all}}</a>
+        {% for chip in filters %}
+        <a href="./?type={{chip.key}}{{search_query}}" class="chip{% if chip.active %} active{% endif %}">{{chip.label}}</a>
+        {% endfor %}
+        {% if filter_active %}
+        <a href="./{% if search %}?{{search_query | trim_start_matches(pat="&")}}{% endif %}" class="chip clear">✕ {{t.filter_clear}}</a>
+        {% endif %}
+        {% if show_playlist %}
+        <a href="/playlist?path={{current_path | urlencode}}" class="chip playlist">🎵 {{t.playlist}}</a>
+        {% endif %}
+    </div>
+    <div class="filter-bar sort-bar">
+        <span class="sort-label">{{t.sort_by}}</span>
+        {% for option in sort_options %}
+        <a href="./?sort={{option.key}}&order={{option.next_order}}{{filter_query}}" class="chip{% if option.active %} active{% endif %}">{{option.label}}{% if option.active %}{% if settings.order == "asc" %} ↑{% else %} ↓{% endif %}{% endif %}</a>
+        {% endfor %}
+        <a href="./?view=list{{filter_query}}" class="chip{% if settings.view == "list" %} active{% endif %}">☰ {{t.view_list}}</a>
+        <a href="./?view=grid{{filter_query}}" class="chip{% if settings.view == "grid" %} active{% endif %}">▦ {{t.view_grid}}</a>
+    </div>
+    {% if mkdir_action %}
+    <form class="search-bar" method="post" action="{{mkdir_action}}">
+        <input type="text" name="name" placeholder="{{t.new_folder_placeholder}}" required>
+        <button type="submit" class="chip">📁 {{t.new_folder}}</button>
+    </form>
+    {% endif %}
+    {% if upload_action %}
+    <form class="search-bar upload-form" method="post" action="{{upload_action}}" enctype="multipart/form-data"{% if upload_progress %} onsubmit="return uploadWithProgress(this)"{% endif %}>
+        {% if not write_only %}<label class="sort-label"><input type="checkbox" name="overwrite" value="true"> {{t.upload_overwrite}}</label>{% endif %}
+        <input type="file" name="file" multiple required>
+        <button type="submit" class="chip">⬆ {{t.upload_submit}}</button>
+        {% if upload_progress %}<progress hidden></progress>{% endif %}
+    </form>
+    {% endif %}
+    {% if not webdav %}
+    <div class="selection-bar">
+        <label><input type="checkbox" id="select-all" onchange="toggleSelectAll(this.checked)"> {{t.select_all}}</label>
+        <span id="selection-info"></span>
+        <button id="zip-btn" class="zip-btn" onclick="downloadSelected()" disabled>{{t.zip_download}}</button>
+    </div>
+    <form id="zip-form" method="post" action="/zip" style="display:none">
+        <input type="hidden" name="path" value="{{current_path | escape}}">
+        <input type="hidden" name="names" id="zip-names">
+    </form>
+    {% endif %}
+    {% endif %}
+    {% if directory_readme %}
+    <div class="readme">{{directory_readme}}</div>
+    {% endif %}
+    {% if truncated_notice %}
+    <div class="truncated-notice">{{truncated_notice}}</div>
+    {% endif %}
+    <div class="entries{% if settings and settings.view == "grid" %} view-grid{% endif %}">
+    {% if streaming %}<!--ENTRIES-->{% else %}{% for entry in entries %}{% include "entry.html" %}{% endfor %}{% endif %}
+    </div>
+    {% if is_empty %}
+    <div id="empty-placeholder" class="empty-placeholder">
+        <p>{% if write_only %}{{t.write_only_notice}}{% elif search %}{{t.empty_search}}{% elif filter_active %}{{t.empty_filter}}{% else %}{{t.empty_directory}}{% endif %}</p>
+        {% if upload_url and not filter_active and not search %}
+        <p class="upload-hint">{{t.empty_upload_hint}} <code>{{upload_url}}</code></p>
+        {% endif %}
+    </div>
+    {% endif %}
+
+    <div id="toast" class="toast"></div>
+    <div id="preview-modal" class="preview-modal" onclick="this.style.display='none'">
+        <div class="preview-content" id="preview-content" onclick="event.stopPropagation()"></div>
+    </div>
+        <footer class="footer">
+        <a href="{{branding.footer_url}}" target="_blank">
+            <p>{{branding.footer_text}}</p>
+        </a>
+    </footer>
+    <script>
+    const SELECTED_TEXT = "{{t.selected}}";
+    const COPIED_TEXT = "{{t.copied}}";
+    const LOAD_PREVIEW_TEXT = "{{t.load_preview}}";
+    const RENAME_PROMPT_TEXT = "{{t.rename_prompt}}";
+
+    function formatSize(size) {
+        const units = ['B', 'KB', 'MB', 'GB', 'TB', 'PB'];
+        let i = 0;
+        while (size >= 1024 && i < units.length - 1) {
+            size /= 1024;
+            i++;
+        }
+        return i === 0 ? `${size} ${units[i]}` : `${size.toFixed(2)} ${units[i]}`;
+    }
+
+    function selectedBoxes() {
+        return Array.from(document.querySelectorAll('.select-box:checked'));
+    }
+
+    function updateSelection() {
+        const boxes = selectedBoxes();
+        const total = boxes.reduce((sum, b) => sum + Number(b.dataset.size), 0);
+        const info = document.getElementById('selection-info');
+        if (!info) {
+            return;
+        }
+        info.textContent = boxes.length > 0
+            ? SELECTED_TEXT.replace('{count}', boxes.length).replace('{size}', formatSize(total))
+            : '';
+        document.getElementById('zip-btn').disabled = boxes.length === 0;
+    }
+
+    function toggleSelectAll(checked) {
+        document.querySelectorAll('.select-box').forEach(b => b.checked = checked);
+        updateSelection();
+    }
+
+    function downloadSelected() {
+        const names = selectedBoxes().map(b => b.value);
+        if (names.length === 0) {
+            return;
+        }
+        document.getElementById('zip-names').value = JSON.stringify(names);
+        document.getElementById('zip-form').submit();
+    }
+
+    function showToast(text) {
+        const toast = document.getElementById('toast');
+        toast.textContent = text;
+        toast.style.display = 'block';
+        clearTimeout(toast.timer);
+        toast.timer = setTimeout(() => toast.style.display = 'none', 1500);
+    }
+
+    // 复制文件的完整链接，地址的协议与主机取自当前页面
+    function copyLink(path) {
+        const url = window.location.origin + path;
+        if (navigator.clipboard && window.isSecureContext) {
+            navigator.clipboard.writeText(url).then(() => showToast(COPIED_TEXT));
+            return;
+        }
+        // 非 HTTPS 页面无法使用 Clipboard API，退回到选中文本后复制
+        const input = document.createElement('textarea');
+        input.value = url;
+        document.body.appendChild(input);
+        input.select();
+        document.execCommand('copy');
+        input.remove();
+        showToast(COPIED_TEXT);
+    }
+
+    // 以条目的直链得到相对于根目录的路径，输入的新名称可以包含 / 以移动到其他目录
+    function renameEntry(form) {
+        const from = decodeURIComponent(form.dataset.url).replace(/^\/+/, '');
+        const slash = from.lastIndexOf('/');
+        const dir = slash >= 0 ? from.slice(0, slash + 1) : '';
+        const name = prompt(RENAME_PROMPT_TEXT, from.slice(slash + 1));
+        if (!name || name === from.slice(slash + 1)) {
+            return false;
+        }
+        form.elements.from.value = from;
+        form.elements.to.value = name.startsWith('/') ? name : dir + name;
+        return true;
+    }
+
+    function togglePreview(url, name, deferred) {
+        const previewContainer = document.getElementById(`preview-${name}`);
+        const icon = document.getElementById(`icon-${name}`);
+        
+        if (previewContainer.style.display === 'block') {
+            previewContainer.style.display = 'none';
+            icon.style.display = 'inline-block';
+            previewContainer.innerHTML = '';
+            return;
+        }
+
+        icon.style.display = 'none';
+        previewContainer.style.display = 'block';
+
+        // 较大的文件先显示占位，点击后才开始加载
+        if (deferred) {
+            const placeholder = document.createElement('span');
+            placeholder.className = 'preview-placeholder';
+            placeholder.textContent = LOAD_PREVIEW_TEXT;
+            placeholder.onclick = (event) => {
+                event.preventDefault();
+                showPreview(previewContainer, url, name);
+            };
+            previewContainer.replaceChildren(placeholder);
+            return;
+        }
+        showPreview(previewContainer, url, name);
+    }
+
+    function showPreview(previewContainer, url, name) {
+        const ext = name.split('.').pop().toLowerCase();
+        if (['jpg', 'jpeg', 'png', 'gif', 'webp'].includes(ext)) {
+            previewContainer.innerHTML = `<img src="${url}" alt="${name}" loading="lazy">`;
+        } else if (['mp4', 'webm'].includes(ext)) {
+            previewContainer.innerHTML = `<video src="${url}" controls preload="metadata"></video>`;
+        } else if (['mp3', 'wav', 'ogg'].includes(ext)) {
+            previewContainer.innerHTML = `<audio src="${url}" controls preload="metadata"></audio>`;
+        }
+    }
+    {% if upload_progress %}
+
+    // 以 fetch 提交上传表单，通过 /events 订阅服务器已收到的字节数并显示在进度条上
+    function uploadWithProgress(form) {
+        const session = `${Date.now().toString(36)}-${Math.random().toString(36).slice(2)}`;
+        const bar = form.querySelector('progress');
+        const events = new EventSource(`/events?session=${session}`);
+        events.addEventListener('progress', (e) => {
+            const { received, total } = JSON.parse(e.data);
+            if (total) {
+                bar.max = total;
+                bar.value = received;
+            }
+        });
+        bar.removeAttribute('value');
+        bar.hidden = false;
+        fetch(form.action, {
+            method: 'POST',
+            body: new FormData(form),
+            headers: { 'X-Upload-Session': session, 'X-Upload-Id': 'form' },
+        }).then(async (res) => {
+            events.close();
+            if (res.ok) {
+                location.href = res.url;
+            } else {
+                bar.hidden = true;
+                alert(await res.text());
+            }
+        }, () => {
+            events.close();
+            bar.hidden = true;
+        });
+        return false;
+    }
+    {% endif %}
+    {% if live_updates %}
+
+    function findEntry(name) {
+        return Array.from(document.querySelectorAll('.entry')).find(e => e.dataset.name === name);
+    }
+
+    // 订阅当前目录的变更，原地更新列表；目录路径取自地址栏，目录名不会被拼进脚本
+    function watchDirectory() {
+        const scheme = location.protocol === 'https:' ? 'wss' : 'ws';
+        const socket = new WebSocket(`${scheme}://${location.host}/ws/watch?path=${encodeURIComponent(location.pathname.slice(1))}`);
+        socket.onmessage = (message) => {
+            const change = JSON.parse(message.data);
+            const existing = findEntry(change.name);
+            if (change.event === 'deleted') {
+                if (existing) {
+                    existing.remove();
+                }
+            } else if (change.html) {
+                const template = document.createElement('template');
+                template.innerHTML = change.html.trim();
+                const row = template.content.firstElementChild;
+                if (existing) {
+                    existing.replaceWith(row);
+                } else {
+                    document.querySelector('.entries').appendChild(row);
+                }
+            }
+            // 空目录中出现新文件后隐藏空目录提示
+            const placeholder = document.getElementById('empty-placeholder');
+            if (placeholder) {
+                placeholder.hidden = document.querySelector('.entry:not([data-name=".."])') !== null;
+            }
+            updateSelection();
+        };
+        // 连接断开后稍后重连；监听数量已满时服务器会拒绝握手
+        socket.onclose = () => setTimeout(watchDirectory, 5000);
+    }
+    watchDirectory();
+    {% endif %}
+    </script>
+</body>
+</html>
+"#;
+
+// 目录列表中单个条目的模板片段，整页渲染与流式输出共用
+const ENTRY_TEMPLATE: &str = r#"
+    <div class="entry" data-name="{{entry.name}}">
+        {% if entry.name != ".." and entry.accessible and not webdav %}
+        <input type="checkbox" class="select-box" value="{{entry.name}}" data-size="{{entry.size}}" onchange="updateSelection()">
+        {% endif %}
+        <div class="name-column">
+            {% if not entry.accessible %}
+            <span class="unavailable" title="{{entry.note}}">
+                <span class="file-icon">{{entry.icon}}</span>
+                {{entry.display_name}}
+            </span>
+            {% elif entry.is_dir %}
+            <a href="./{{entry.name}}/" class="directory">📁 {{entry.name}}/</a>
+            {% else %}
+            <a href="./{{entry.name}}">
+                <span class="file-icon" id="icon-{{entry.name}}">{{entry.icon}}</span>
+                <span class="preview-container" id="preview-{{entry.name}}"></span>
+                {{entry.display_name}}
+            </a>
+            {% endif %}
+            {% if entry.symlink_target %}
+            <span class="link-target{% if not entry.accessible %} broken{% endif %}">→ {{entry.symlink_target}}</span>
+            {% endif %}
+        </div>
+        <div class="info-group">
+            {% if not entry.accessible %}
+                <div class="note-column">{{entry.note}}</div>
+            {% elif not entry.is_dir %}
+                {% if entry.preview_url != "" %}
+                <span class="preview-btn" onclick="togglePreview('{{entry.preview_url}}', '{{entry.display_name}}', {{entry.preview_deferred}})">{{t.preview}}</span>
+                {% endif %}
+                {% if entry.viewable %}
+                <a href="./{{entry.name}}" class="view-btn" target="_blank">{{t.view}}</a>
+                {% endif %}
+                <a href="./{{entry.name}}?dl=1" class="download-btn" download="{{entry.display_name}}">{{t.download}}</a>
+                <span class="copy-btn" data-url="{{entry.url}}" onclick="copyLink(this.dataset.url)">{{t.copy_link}}</span>
+                {% if not webdav %}
+                <a href="/checksum?path={{current_path | urlencode}}{% if current_path is not ending_with("/") %}/{% endif %}{{entry.name | urlencode}}&format=text" class="checksum-btn" target="_blank" title="{{t.checksum}}">SHA256</a>
+                {% endif %}
+            {% endif %}
+            {% if settings.web_rename and not webdav and entry.url != "" %}
+            <form class="entry-form" method="post" action="/rename" onsubmit="return renameEntry(this)" data-url="{{entry.url}}">
+                <input type="hidden" name="from">
+                <input type="hidden" name="to">
+                <button type="submit" class="rename-btn">{{t.rename}}</button>
+            </form>
+            {% endif %}
+            {% if settings.web_delete and not webdav and entry.url != "" %}
+            <form class="entry-form" method="post" action="/delete{{entry.url}}{% if entry.is_dir %}?recursive=true{% endif %}" onsubmit="return confirm({% if entry.is_dir %}'{{t.delete_dir_confirm}}'{% else %}'{{t.delete_confirm}}'{% endif %}.replace('{name}', this.dataset.name))" data-name="{{entry.display_name}}">
+                <button type="submit" class="delete-btn">{{t.delete}}</button>
+            </form>
+            {% endif %}
+            {% for column in settings.columns %}
+                {% if column == "size" %}
+                {% if entry.accessible and not entry.is_dir %}<div class="size-column">{{entry.size_string}}</div>{% endif %}
+                {% elif column == "modified" %}
+                <div class="date-column">{{entry.modified_time}}</div>
+                {% elif column == "permissions" %}
+                <div class="perm-column">{{entry.permissions}}</div>
+                {% elif column == "downloads" %}
+                {% if entry.accessible and not entry.is_dir %}<div class="downloads-column" title="{{t.downloads}}">⬇ {{entry.downloads}}</div>{% endif %}
+                {% elif column == "type" %}
+                <div class="type-column">{{entry.file_type}}</div>
+                {% endif %}
+            {% endfor %}
+        </div>
+    </div>
+"#;
+
+// 流式输出时条目列表在整页模板中的占位标记
+const ENTRIES_MARKER: &str = "<!--ENTRIES-->";
+
+// 全局模板实例，页面模板通过 include 引用条目片段
+fn templates() -> &'static tera::Tera {
+    static TEMPLATES: std::sync::OnceLock<tera::Tera> = std::sync::OnceLock::new();
+    TEMPLATES.get_or_init(|| {
+        let mut tera = tera::Tera::default();
+        tera.add_raw_templates(vec![
+            ("index.html", TEMPLATE),
+            ("entry.html", ENTRY_TEMPLATE),
+        ])
+        .expect("内置模板解析失败");
+        tera.autoescape_on(vec![]);
+        tera
+    })
+}
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const AUTHORS: &str = env!("CARGO_PKG_AUTHORS", "yxyos");
+pub const DESCRIPTION: &str = env!("CARGO_PKG_DESCRIPTION");
+
+// 修改错误类型
+#[derive(Debug)]
+pub struct ConfigError(pub String);
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// 随机密码可选的符号字符
+const PASSWORD_SYMBOLS: &[u8] = b"!@#$%^&*-_=+";
+// --readable 时排除的易混淆字符
+const AMBIGUOUS_CHARS: &[u8] = b"0O1lI";
+
+fn default_password_length() -> usize {
+    16
+}
+
+// 随机密码生成选项，每个字符从字符集中均匀选取
+pub struct PasswordOptions {
+    pub len: usize,
+    pub symbols: bool,   // 是否包含符号
+    pub readable: bool,  // 是否排除易混淆字符
+}
+
+impl PasswordOptions {
+    pub fn charset(&self) -> Vec<u8> {
+        let symbols: &[u8] = if self.symbols { PASSWORD_SYMBOLS } else { &[] };
+        (b'0'..=b'9')
+            .chain(b'A'..=b'Z')
+            .chain(b'a'..=b'z')
+            .chain(symbols.iter().copied())
+            .filter(|c| !(self.readable && AMBIGUOUS_CHARS.contains(c)))
+            .collect()
+    }
+
+    pub fn generate(&self) -> String {
+        let charset = self.charset();
+        let dist = Slice::new(&charset).expect("密码字符集不能为空");
+        thread_rng()
+            .sample_iter(dist)
+            .take(self.len)
+            .map(|&c| c as char)
+            .collect()
+    }
+
+    // 熵估计（位）
+    pub fn entropy_bits(&self) -> f64 {
+        self.len as f64 * (self.charset().len() as f64).log2()
+    }
+}
+
+// 所有工作线程共享的应用状态；锁状态需在工作线程之间共享，因此在工厂函数外创建
+#[derive(Clone)]
+pub struct AppState {
+    protected: web::Data<ProtectedPaths>,
+    storage: web::Data<dyn Storage>,
+    dav_protected: web::Data<DavProtectedPaths>,
+    dav_locks: Option<web::Data<DavLocks>>,
+}
+
+impl AppState {
+    pub fn new(config: &Config) -> Self {
+        AppState {
+            protected: web::Data::new(ProtectedPaths::new(&config.cwd)),
+            storage: web::Data::from(Arc::new(LocalStorage) as Arc<dyn Storage>),
+            dav_protected: web::Data::new(DavProtectedPaths(
+                ProtectedPaths::new(config.webdav.root_dir(&config.cwd))
+            )),
+            dav_locks: config.webdav.locking.then(|| web::Data::new(DavLocks(MemLs::new()))),
+        }
+    }
+}
+
+// 构建应用及全部路由，服务器的每个工作线程与测试共用
+pub fn build_app(
+    config: &Config,
+    state: &AppState,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    let cors = config.cors.as_ref().map(CorsConfig::build);
+    let mut app = App::new()
+        .wrap(from_fn(compress_response))
+        .wrap(Condition::new(cors.is_some(), cors.unwrap_or_default()))
+        .wrap(from_fn(connection_limit_guard))
+        .wrap(Condition::new(config.web_auth.enabled, from_fn(web_auth_guard)))
+        .wrap(Condition::new(config.log.access, from_fn(access_log)))
+        .app_data(web::Data::new(config.clone()))
+        .app_data(state.protected.clone())
+        .app_data(state.storage.clone())
+        .service(options_handler)
+        .service(favicon)
+        .service(healthz)
+        .service(version_info)
+        .service(playlist)
+        .service(checksum)
+        .service(api_list)
+        .service(web_upload)
+        .service(web_delete)
+        .service(web_mkdir)
+        .service(web_rename)
+        .service(watch_socket)
+        .service(upload_events)
+        .service(zip_download);
+    
+    // 未开启时不注册，同名文件仍可正常访问
+    if config.sitemap.enabled {
+        app = app.service(sitemap_xml).service(index_json);
+    }
+    if config.pwa.enabled {
+        app = app.service(pwa_manifest).service(pwa_icon).service(service_worker);
+    }
+
+    // WebDAV 需在通配路由之前注册，否则 GET/HEAD 会被 index 截获
+    if config.webdav.enabled {
+        if let Some(locks) = &state.dav_locks {
+            app = app.app_data(locks.clone());
+        }
+        app = app.app_data(state.dav_protected.clone());
+        app = app.service(
+            web::resource(format!("{}/{{tail:.*}}", config.webdav.mount_prefix()))
+                .guard(guard::fn_guard(|ctx| DAV_METHODS.contains(&ctx.head().method.as_str())))
+                .to(webdav_handler)
+        );
+    }
+    
+    app.service(index)
+}
+
+// cwd 必须是绝对路径或以 ./、../ 开头的相对路径。windows 为 true 时按 Windows 的规则判断：
+// 接受 C:\ 盘符与 \\server 共享路径，以及 .\、..\ 写法
+pub fn is_valid_cwd(value: &str, windows: bool) -> bool {
+    if !windows {
+        return ["/", "./", "../"].iter().any(|p| value.starts_with(p));
+    }
+    let drive = matches!(value.as_bytes(), [letter, b':', b'\\' | b'/', ..] if letter.is_ascii_alphabetic());
+    drive || value.starts_with("\\\\") || ["./", "../", ".\\", "..\\"].iter().any(|p| value.starts_with(p))
+}
+
+// Windows 下统一以 / 作为分隔符保存，与请求路径拼接后格式一致；
+// 其他平台上反斜杠是合法的文件名字符，保持原样
+pub fn normalize_separators(value: &str, windows: bool) -> String {
+    if windows {
+        value.replace('\\', "/")
+    } else {
+        value.to_string()
+    }
+}
+
+// 去掉用户可能写上的方括号，得到裸 IPv6 地址
+pub fn strip_ipv6_brackets(host: &str) -> &str {
+    host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host)
+}
+
+// 由 IPv6 地址和端口构造套接字地址，统一加方括号后解析，避免 ::1:8080 这类歧义写法
+pub fn ipv6_socket_addr(host: &str, port: u16) -> Result<SocketAddr, std::net::AddrParseError> {
+    format!("[{}]:{}", strip_ipv6_brackets(host), port).parse()
+}
+
+// 解析监听地址并为每个地址创建套接字；IPv6 套接字显式设置 IPV6_V6ONLY，
+// 否则 Linux 上监听 :: 会同时占用 IPv4 端口，与单独的 IPv4 套接字冲突。
+// 端口为 0 时由系统分配，同一地址解析出的其余地址沿用第一个分配到的端口
+pub fn bind_listeners(addr: &str, ipv6: bool) -> std::io::Result<Vec<std::net::TcpListener>> {
+    let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?
+        .filter(|a| a.is_ipv6() == ipv6)
+        .collect();
+    if addrs.is_empty() {
+        return Err(std::io::Error::new(std::io::ErrorKind::AddrNotAvailable, format!("{} 没有可用的地址", addr)));
+    }
+    let mut listeners: Vec<std::net::TcpListener> = Vec::with_capacity(addrs.len());
+    for mut addr in addrs {
+        if addr.port() == 0 {
+            if let Some(first) = listeners.first() {
+                addr.set_port(first.local_addr()?.port());
+            }
+        }
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        if ipv6 {
+            socket.set_only_v6(true)?;
+        }
+        #[cfg(not(windows))]
+        socket.set_reuse_address(true)?;
+        socket.bind(&addr.into())?;
+        socket.listen(1024)?;
+        listeners.push(socket.into());
+    }
+    Ok(listeners)
+}
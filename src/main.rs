@@ -1,731 +1,326 @@
-use actix_files::NamedFile;
-use actix_web::{get, App, HttpResponse, HttpServer, Result, web, Error, HttpRequest};
-use actix_web::middleware::Compress;
-use actix_web::http::header;
-use serde::{Serialize, Deserialize};
+use actix_web::HttpServer;
 use std::{env, fs};
-use std::path::{Path, PathBuf};
+use std::io::IsTerminal;
+use std::path::Path;
 use std::time::Duration;
-use percent_encoding::percent_decode_str;
-use chrono::{DateTime, Local};
 use std::process::Command;
 use std::fs::OpenOptions;
 use std::collections::BTreeMap;
-use dav_server::DavHandler;
-use dav_server::localfs::LocalFs;
-use futures_util::StreamExt;
-use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
-use rand::{thread_rng, Rng};
-use rand::distributions::Alphanumeric;
+use webdisk::{
+    bind_listeners, build_app, hash_password, ipv6_socket_addr, is_valid_cwd, normalize_separators, strip_ipv6_brackets, AppState, BindMode, Config, ConfigError,
+    ConnectionSlot, LogFormat, UserConfig, AUTHORS, DESCRIPTION, VERSION,
+};
 
-// 添加自定义序列化模块
-mod ordered_map {
-    use serde::{self, Deserialize, Deserializer, Serializer};
-    use std::collections::BTreeMap;
-
-    pub fn serialize<S, K, V>(value: &BTreeMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-        K: serde::Serialize + Ord,
-        V: serde::Serialize,
-    {
-        use serde::ser::SerializeMap;
-        let mut map = serializer.serialize_map(Some(value.len()))?;
-        for (k, v) in value {
-            map.serialize_entry(k, v)?;
-        }
-        map.end()
-    }
-
-    pub fn deserialize<'de, D, K, V>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error>
-    where
-        D: Deserializer<'de>,
-        K: serde::Deserialize<'de> + Ord,
-        V: serde::Deserialize<'de>,
-    {
-        BTreeMap::deserialize(deserializer)
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct Config {
-    ip: String,
-    ipv6: String,
-    port: u16,
-    cwd: String,
-    webdav: WebDAVConfig,  // 添加 WebDAV 配置
+fn print_version() {
+    println!("云溪起源网盘 v{}", VERSION);
+    println!("作者: {}", AUTHORS);
+    println!("描述: {}", DESCRIPTION);
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct WebDAVConfig {
-    enabled: bool,
-    #[serde(with = "ordered_map")]  // 使用自定义序列化
-    users: BTreeMap<String, UserConfig>,
-}
+// 导出时用于替换密码的占位符；导入时遇到该值表示保留原密码
+const MASKED_PASSWORD: &str = "******";
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct UserConfig {
-    password: String,
-    permissions: String,  // "r" = read, "w" = write, "x" = execute
+fn is_csv_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
 }
 
-#[derive(Debug, Serialize)]
-struct FileEntry {
-    name: String,
-    display_name: String,
-    size_string: String,
-    modified_time: String,
-    is_dir: bool,
-    icon: String,        // 添加图标字段
-    preview_url: String, // 添加预览URL字段
+// 字段含逗号、引号或换行时加引号，引号本身转义为两个引号
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
 }
 
-impl Config {
-    fn load() -> std::io::Result<Self> {
-        let data_dir = Path::new("data");
-        let config_path = data_dir.join("config.yaml");
-
-        if !data_dir.exists() {
-            fs::create_dir_all(data_dir)?;
-        }
-
-        if !config_path.exists() {
-            Self::create_default_config()?;
-        }
-        
-        let config_str = fs::read_to_string(&config_path)?;
-        let config: Self = serde_yaml::from_str(&config_str)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        
-        let cwd_path = Path::new(&config.cwd);
-        if !cwd_path.exists() {
-            fs::create_dir_all(cwd_path)?;
+// 解析一行 CSV，支持带引号的字段
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
         }
-        
-        Ok(config)
     }
+    fields.push(field);
+    fields
+}
 
-    // 添加创建默认配置的函数
-    fn create_default_config() -> std::io::Result<()> {
-        let mut users = BTreeMap::new();
-        users.insert("admin".to_string(), UserConfig {
-            password: "admin".to_string(),
-            permissions: "rwx".to_string(),
-        });
-
-        let config = Config {
-            ip: "0.0.0.0".to_string(),
-            ipv6: "::".to_string(),
-            port: 8080,
-            cwd: "data/www".to_string(),
-            webdav: WebDAVConfig {
-                enabled: false,
-                users,
-            },
-        };
-
-        let yaml_str = serde_yaml::to_string(&config)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        fs::write("data/config.yaml", yaml_str)?;
-        println!("已创建默认配置文件");
-        Ok(())
+// 校验导入的用户名与权限字符串
+fn validate_user(username: &str, user: &UserConfig) -> Result<(), String> {
+    if username.is_empty() || username.contains(':') {
+        return Err(format!("无效的用户名 \"{}\"", username));
     }
-
-    // 添加从指定路径加载配置的方法
-    fn load_from(config_path: &Path) -> std::io::Result<Self> {
-        if !config_path.exists() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                ConfigError("指定的配置文件不存在".to_string())
-            ));
-        }
-        
-        let config_str = fs::read_to_string(config_path)?;
-        let config: Self = serde_yaml::from_str(&config_str)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        
-        let cwd_path = Path::new(&config.cwd);
-        if !cwd_path.exists() {
-            fs::create_dir_all(cwd_path)?;
-        }
-        
-        Ok(config)
+    if !user.permissions.chars().all(|c| "rwx".contains(c)) {
+        return Err(format!("用户 {} 的权限字符串 \"{}\" 无效，只能包含 r、w、x", username, user.permissions));
     }
+    Ok(())
 }
 
+// 按文件扩展名导出为 CSV 或 YAML，mask 为 true 时不导出明文密码
+fn export_users(users: &BTreeMap<String, UserConfig>, path: &Path, mask: bool) -> std::io::Result<()> {
+    let users: BTreeMap<String, UserConfig> = users.iter()
+        .map(|(name, user)| {
+            let mut user = user.clone();
+            if mask {
+                user.password = MASKED_PASSWORD.to_string();
+            }
+            (name.clone(), user)
+        })
+        .collect();
 
-// 文件大小格式化
-fn format_size(size: u64) -> String {
-    const UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
-    let mut size = size as f64;
-    let mut unit_index = 0;
-    
-    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit_index += 1;
-    }
-    
-    if unit_index == 0 {
-        format!("{} {}", size as u64, UNITS[unit_index])
+    let content = if is_csv_file(path) {
+        let mut out = String::from("username,password,permissions,root\n");
+        for (name, user) in &users {
+            out.push_str(&format!(
+                "{},{},{},{}\n",
+                csv_field(name),
+                csv_field(&user.password),
+                csv_field(&user.permissions),
+                csv_field(user.root.as_deref().unwrap_or("")),
+            ));
+        }
+        out
     } else {
-        format!("{:.2} {}", size, UNITS[unit_index])
-    }
+        serde_yaml::to_string(&users).map_err(std::io::Error::other)?
+    };
+    fs::write(path, content)
 }
 
-// 获取文件图标
-fn get_file_icon(name: &str) -> &'static str {
-    let extension = name.rsplit('.').next().unwrap_or("").to_lowercase();
-    match extension.as_str() {
-        // 镜像文件
-        "iso" | "img" | "esd" | "wim" | "vhd" | "vmdk" => "💿",
-        // 图片
-        "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" | "svg" => "🖼️",
-        // 视频
-        "mp4" | "mkv" | "avi" | "mov" | "wmv" | "flv" | "webm" => "🎥",
-        // 音频
-        "mp3" | "wav" | "ogg" | "m4a" | "flac" | "aac" => "🎵",
-        // 文档
-        "pdf" => "📕",
-        "doc" | "docx" => "📘",
-        "xls" | "xlsx" => "📗",
-        "ppt" | "pptx" => "📙",
-        "txt" | "md" | "log" => "📄",
-        // 压缩文件
-        "zip" | "rar" | "7z" | "tar" | "gz" | "bz2" | "xz" => "📦",
-        // 代码文件
-        "c" | "cpp" | "h" | "hpp" | "rs" | "go" | "py" | "js" | "html" | "css" | "java" => "📝",
-        // 可执行文件
-        "exe" | "msi" | "bat" | "sh" | "cmd" => "⚙️",
-        // 配置文件
-        "json" | "yaml" | "yml" | "toml" | "ini" | "conf" => "⚙️",
-        // 字体文件
-        "ttf" | "otf" | "woff" | "woff2" => "🔤",
-        // 默认文件图标
-        _ => "📄",
+// 读取导入文件，每一项为解析成功的用户或带行号/用户名的错误说明
+fn read_user_file(path: &Path) -> std::io::Result<Vec<Result<(String, UserConfig), String>>> {
+    let content = fs::read_to_string(path)?;
+
+    if !is_csv_file(path) {
+        let users: BTreeMap<String, UserConfig> = serde_yaml::from_str(&content)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        return Ok(users.into_iter()
+            .map(|(name, user)| validate_user(&name, &user).map(|_| (name, user)))
+            .collect());
     }
-}
 
-// 判断文件是否可预览
-fn is_previewable(name: &str) -> bool {
-    let extension = name.rsplit('.').next().unwrap_or("").to_lowercase();
-    matches!(extension.as_str(), 
-        "jpg" | "jpeg" | "png" | "gif" | "webp" |
-        "mp4" | "webm" |
-        "mp3" | "wav" | "ogg"
-    )
+    let mut rows = Vec::new();
+    for (line_no, line) in content.lines().enumerate() {
+        // 跳过表头与空行
+        if line_no == 0 || line.trim().is_empty() {
+            continue;
+        }
+        let fields = parse_csv_line(line);
+        if fields.len() < 3 {
+            rows.push(Err(format!("第 {} 行: 至少需要 username、password、permissions 三列", line_no + 1)));
+            continue;
+        }
+        let name = fields[0].trim().to_string();
+        let user = UserConfig {
+            password: fields[1].clone(),
+            permissions: fields[2].trim().to_string(),
+            root: fields.get(3).map(|r| r.trim().to_string()).filter(|r| !r.is_empty()),
+            landing_path: None,
+        };
+        rows.push(validate_user(&name, &user)
+            .map(|_| (name, user))
+            .map_err(|e| format!("第 {} 行: {}", line_no + 1, e)));
+    }
+    Ok(rows)
 }
 
-async fn get_directory_entries(path: &Path) -> Vec<FileEntry> {
-    let mut entries = Vec::new();
-    let mut dirs = Vec::new();
-    let mut files = Vec::new();
-    
-    if let Ok(read_dir) = fs::read_dir(path) {
-        for entry in read_dir.flatten() {
-            if let Ok(metadata) = entry.metadata() {
-                let name = entry.file_name().to_string_lossy().to_string();
-                
-                // 跳过 favicon.ico 文件的显示
-                if name == "favicon.ico" {
-                    continue;
-                }
-                
-                let size = metadata.len();
-                
-                // 检查是否为软链接
-                let is_symlink = metadata.file_type().is_symlink();
-                let is_dir = if is_symlink {
-                    // 如果是软链接，获取目标文件的元数据
-                    if let Ok(target_metadata) = fs::metadata(entry.path()) {
-                        target_metadata.is_dir()
-                    } else {
-                        false  // 如果无法获取目标元数据，当作普通文件处理
-                    }
-                } else {
-                    metadata.is_dir()
-                };
-
-                let size_string = if is_dir {
-                    "目录".to_string()
-                } else {
-                    format_size(size)
-                };
-                
-                let modified = metadata.modified().unwrap_or(std::time::SystemTime::now());
-                let datetime: DateTime<Local> = modified.into();
-                
-                let file_entry = FileEntry {
-                    name: name.clone(),
-                    display_name: if is_symlink {
-                        format!("{} ", name)
-                    } else {
-                        name.clone()
-                    },
-                    size_string,
-                    modified_time: datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
-                    is_dir,
-                    icon: if is_dir {
-                        "📁".to_string()  // 文件夹图标
-                    } else if is_symlink {
-                        "🔗".to_string()  // 软链接图标
-                    } else {
-                        get_file_icon(&name).to_string()
-                    },
-                    preview_url: if is_previewable(&name) && !is_dir {
-                        format!("./{}", name)
-                    } else {
-                        String::new()
-                    },
-                };
+// 批量导入用户；replace 为 true 时删除文件中未列出的用户
+fn import_users(config: &mut Config, path: &Path, replace: bool) -> std::io::Result<()> {
+    let rows = read_user_file(path)?;
+    let (mut added, mut updated, mut skipped) = (Vec::new(), Vec::new(), 0);
+    let mut seen = std::collections::HashSet::new();
+
+    for row in rows {
+        let (name, mut user) = match row {
+            Ok(row) => row,
+            Err(e) => {
+                println!("跳过 {}", e);
+                skipped += 1;
+                continue;
+            }
+        };
+        seen.insert(name.clone());
+
+        // 已是哈希的密码（如从本程序导出的文件）无法也无需检查强度
+        if user.password != MASKED_PASSWORD && !user.password_is_hashed() {
+            if let Err(e) = config.webdav.check_password(&user.password) {
+                println!("跳过用户 {}: {}", name, e);
+                skipped += 1;
+                continue;
+            }
+        }
 
-                if is_dir {
-                    dirs.push(file_entry);
-                } else {
-                    files.push(file_entry);
+        let existing = config.webdav.users.get(&name);
+        if user.password == MASKED_PASSWORD {
+            // 导出时隐藏了密码：已有用户保留原密码，新用户生成随机密码
+            match existing {
+                Some(old) => user.password = old.password.clone(),
+                None => {
+                    user.password = config.webdav.password_options().generate();
+                    println!("用户 {} 的密码已隐藏，已生成随机密码: {}（约 {:.0} 位熵）",
+                        name, user.password, config.webdav.password_options().entropy_bits());
                 }
             }
         }
+        if !user.password_is_hashed() {
+            user.password = hash_password(&user.password);
+        }
+        // CSV 中没有默认目录一列，更新已有用户时保留原来的设置
+        if user.landing_path.is_none() {
+            user.landing_path = existing.and_then(|old| old.landing_path.clone());
+        }
+        if existing.is_some() {
+            updated.push(name.clone());
+        } else {
+            added.push(name.clone());
+        }
+        config.webdav.users.insert(name, user);
     }
-    
-    dirs.sort_by(|a, b| a.display_name.to_lowercase().cmp(&b.display_name.to_lowercase()));
-    files.sort_by(|a, b| a.display_name.to_lowercase().cmp(&b.display_name.to_lowercase()));
-    
-    entries.extend(dirs);
-    entries.extend(files);
-    
-    if path.parent().is_some() && path != Path::new(&"data/www") {
-        entries.insert(0, FileEntry {
-            name: "..".to_string(),
-            display_name: "返回上级目录".to_string(),
-            size_string: "".to_string(),
-            modified_time: "".to_string(),
-            is_dir: true,
-            icon: "📁".to_string(),
-            preview_url: String::new(),
+
+    let mut removed = Vec::new();
+    if replace {
+        config.webdav.users.retain(|name, _| {
+            let keep = seen.contains(name);
+            if !keep {
+                removed.push(name.clone());
+            }
+            keep
         });
     }
-    entries
-}
 
-#[get("/{path:.*}")]
-async fn index(
-    req: actix_web::HttpRequest,
-    config: web::Data<Config>,
-) -> Result<HttpResponse> {
-    let path = req.match_info().query("path").to_string();
-    let full_path = PathBuf::from(&config.cwd).join(
-        percent_decode_str(&path)
-            .decode_utf8()
-            .unwrap_or_default()
-            .as_ref()
-    );
-    
-    match (full_path.exists(), full_path.is_file()) {
-        (false, _) => Ok(HttpResponse::NotFound().body("404 Not Found")),
-        (true, true) => Ok(NamedFile::open(&full_path)?.into_response(&req)),
-        (true, false) => {
-            let entries = get_directory_entries(&full_path).await;
-            
-            let mut context = tera::Context::new();
-            context.insert("current_path", &path);
-            context.insert("entries", &entries);
-            
-            let rendered = tera::Tera::one_off(TEMPLATE, &context, false)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-            
-            Ok(HttpResponse::Ok()
-                .content_type("text/html; charset=utf-8")
-                .body(rendered))
-        }
+    println!("导入完成:");
+    println!("- 新增 {} 个用户{}", added.len(), list_suffix(&added));
+    println!("- 更新 {} 个用户{}", updated.len(), list_suffix(&updated));
+    if replace {
+        println!("- 删除 {} 个用户{}", removed.len(), list_suffix(&removed));
     }
+    println!("- 跳过 {} 行无效数据", skipped);
+    Ok(())
 }
 
-// 修改 WebDAV 处理函数
-#[actix_web::route("/webdav/{tail:.*}", method="GET", method="HEAD", method="PUT", 
-                   method="DELETE", method="COPY", method="MOVE", method="MKCOL", 
-                   method="PROPFIND", method="PROPPATCH", method="LOCK", method="UNLOCK")]
-async fn webdav_handler(
-    req: HttpRequest,
-    mut payload: web::Payload,
-    config: web::Data<Config>,
-) -> Result<HttpResponse, Error> {
-    if !config.webdav.enabled {
-        return Ok(HttpResponse::NotFound().body("WebDAV service is disabled"));
-    }
-
-    // 添加基本认证检查
-    if let Some(auth) = req.headers().get(header::AUTHORIZATION) {
-        let auth_str = auth.to_str().map_err(|_| {
-            actix_web::error::ErrorUnauthorized("Invalid authorization header")
-        })?;
-
-        if auth_str.starts_with("Basic ") {
-            let credentials = BASE64.decode(&auth_str[6..]).map_err(|_| {
-                actix_web::error::ErrorUnauthorized("Invalid base64 in authorization")
-            })?;
-
-            let credentials_str = String::from_utf8(credentials).map_err(|_| {
-                actix_web::error::ErrorUnauthorized("Invalid UTF-8 in authorization")
-            })?;
-
-            let parts: Vec<&str> = credentials_str.splitn(2, ':').collect();
-            if parts.len() == 2 {
-                let username = parts[0];
-                let password = parts[1];
-
-                if let Some(user_config) = config.webdav.users.get(username) {
-                    if user_config.password != password {
-                        return Ok(HttpResponse::Unauthorized()
-                            .append_header((header::WWW_AUTHENTICATE, "Basic realm=\"WebDAV Server\""))
-                            .body("Invalid password"));
-                    }
-
-                    // 检查权限
-                    let method = req.method();
-                    let need_write = matches!(method.as_str(), 
-                        "PUT" | "DELETE" | "MKCOL" | "COPY" | "MOVE"
-                    );
-
-                    if need_write && !user_config.permissions.contains('w') {
-                        return Ok(HttpResponse::Forbidden().body("Write permission required"));
-                    }
-
-                    if !user_config.permissions.contains('r') {
-                        return Ok(HttpResponse::Forbidden().body("Read permission required"));
-                    }
-                } else {
-                    return Ok(HttpResponse::Unauthorized()
-                        .append_header((header::WWW_AUTHENTICATE, "Basic realm=\"WebDAV Server\""))
-                        .body("Invalid username"));
-                }
-            }
-        }
+fn list_suffix(names: &[String]) -> String {
+    if names.is_empty() {
+        String::new()
     } else {
-        return Ok(HttpResponse::Unauthorized()
-            .append_header((header::WWW_AUTHENTICATE, "Basic realm=\"WebDAV Server\""))
-            .finish());
+        format!(": {}", names.join(", "))
     }
+}
 
-    // 确保基础目录存在
-    let base = PathBuf::from(&config.cwd);
-    if !base.exists() {
-        fs::create_dir_all(&base)?;
+// 检查用户目录能否被 --purge 删除：必须是独立目录，且位于共享根目录之内
+fn check_purge_target(user: &UserConfig, shared_root: &str) -> Result<(), String> {
+    if user.root.is_none() {
+        return Err("该用户使用共享根目录".to_string());
     }
-
-    let handler = DavHandler::builder()
-        .filesystem(LocalFs::new(&base, true, true, false))
-        .strip_prefix("/webdav")
-        .autoindex(true)
-        .build_handler();
-
-    let uri = req.uri().to_string();
-    let mut dav_req = hyper::Request::builder()
-        .method(req.method().clone())
-        .uri(uri)
-        .version(req.version());
-
-    for (name, value) in req.headers() {
-        dav_req = dav_req.header(name, value);
+    let shared = fs::canonicalize(shared_root).map_err(|e| format!("无法解析共享根目录: {}", e))?;
+    let root = match fs::canonicalize(user.root_dir(&shared.to_string_lossy())) {
+        Ok(root) => root,
+        // 目录不存在时无需删除任何文件
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("无法解析用户目录: {}", e)),
+    };
+    if root == shared {
+        return Err("用户目录即为共享根目录".to_string());
     }
+    if !root.starts_with(&shared) {
+        return Err("用户目录位于共享根目录之外".to_string());
+    }
+    Ok(())
+}
 
-    let body = if req.method() == hyper::Method::PUT {
-        let (tx, body) = hyper::Body::channel();
-        let mut tx = Some(tx);
-        
-        actix_web::rt::spawn(async move {
-            while let Some(chunk) = payload.next().await {
-                if let Ok(chunk) = chunk {
-                    if let Some(tx) = tx.as_mut() {
-                        if tx.send_data(chunk.into()).await.is_err() {
-                            break;
-                        }
-                    }
-                }
-            }
-        });
-
-        body
-    } else {
-        hyper::Body::empty()
-    };
+// 交互式启动信息
+// 按监听方式生成 IPv4 与 IPv6 的访问地址，未监听的一方为 None
+fn listen_urls(config: &Config) -> (Option<String>, Option<String>) {
+    let ipv4 = (config.bind_mode != BindMode::Ipv6)
+        .then(|| format!("http://{}:{}", config.ip, config.port));
+    let ipv6 = (config.bind_mode != BindMode::Ipv4 && !config.ipv6.is_empty())
+        .then(|| format!("http://[{}]:{}", strip_ipv6_brackets(&config.ipv6), config.port));
+    (ipv4, ipv6)
+}
 
-    let dav_req = dav_req.body(body)
-        .unwrap_or_else(|_| hyper::Request::new(hyper::Body::empty()));
+fn print_banner(config: &Config) {
+    let (ipv4_url, ipv6_url) = listen_urls(config);
 
-    let dav_resp = handler.handle(dav_req).await;
-    let (parts, body) = dav_resp.into_parts();
-    let mut builder = HttpResponse::build(parts.status);
+    println!("\n云溪起源网盘 v{}", VERSION);
+    println!("作者: {}", AUTHORS);
+    println!("描述: {}\n", DESCRIPTION);
     
-    for (name, value) in parts.headers {
-        if let Some(name) = name {
-            builder.append_header((name, value));
-        }
+    println!("系统信息:");
+    println!("- PID: {}", std::process::id());
+    println!("- 监听方式: {}", config.bind_mode.label());
+    if let Some(url) = &ipv4_url {
+        println!("- IPv4: {}", url);
     }
-    
-    Ok(builder.streaming(body))
-}
+    if let Some(url) = &ipv6_url {
+        println!("- IPv6: {}", url);
+    }
+    println!("- 目录: {}", config.cwd);
 
-const TEMPLATE: &str = r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>文件索引</title>
-    <link rel="icon" href="/favicon.ico"/>
-    <style>
-        body {
-            font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, "Helvetica Neue", Arial, sans-serif;
-            margin: 20px;
-            background-color: #f8f9fa;
-        }
-        .entry {
-            display: flex;
-            align-items: center;
-            padding: 15px;
-            margin: 5px 0;
-            background-color: white;
-            border-radius: 8px;
-            box-shadow: 0 1px 3px rgba(0,0,0,0.1);
-        }
-        .entry:hover {
-            background-color: #f8f9fa;
-        }
-        .info-group {
-            display: flex;
-            align-items: center;
-            gap: 20px;
-            margin-left: auto;
-        }
-        a {
-            text-decoration: none;
-            color: inherit;
-        }
-        a:hover {
-            text-decoration: underline;
-        }
-        h1 {
-            color: #333;
-            border-bottom: 2px solid #ddd;
-            padding-bottom: 10px;
-            font-size: 1.5em;
-            word-break: break-all;
-        }
-        .name-column {
-            flex: 2;
-            min-width: 0;
-            overflow: visible;
-            text-overflow: ellipsis;
-            white-space: normal;
-            word-break: break-all;
-        }
-        .size-column {
-            flex: 0.8;
-            text-align: right;
-            min-width: 80px;
-        }
-        .date-column {
-            flex: 1.2;
-            text-align: right;
-            white-space: nowrap;
-            min-width: 150px;
-        }
-        .preview-container {
-            display: none;
-            margin: 8px 0 8px 32px;
-            vertical-align: middle;
-        }
-        .preview-container img {
-            max-width: 160px;
-            max-height: 90px;
-            object-fit: contain;
-            border-radius: 4px;
-            display: block;
-        }
-        .preview-container video {
-            max-width: 160px;
-            max-height: 90px;
-            object-fit: contain;
-            border-radius: 4px;
-            display: block;
-        }
-        .preview-container audio {
-            width: 320px;
-            height: 32px;
-            display: block;
-        }
-        .file-icon {
-            margin-right: 8px;
-            font-size: 1.2em;
-            display: inline-block;
-            width: 32px;
-            text-align: center;
-        }
-        .download-btn {
-            background-color: #4CAF50;
-            color: white;
-            padding: 4px 8px;
-            border-radius: 4px;
-            font-size: 0.8em;
-            text-decoration: none;
-            display: inline-block;
-            margin-right: 10px;
-            min-width: 50px;
-            text-align: center;
-            white-space: nowrap;
-        }
-        .preview-btn {
-            background-color: #2196F3;
-            color: white;
-            padding: 4px 8px;
-            border-radius: 4px;
-            font-size: 0.8em;
-            cursor: pointer;
-            margin-right: 10px;
-            min-width: 50px;
-            text-align: center;
-            white-space: nowrap;
-        }
-        .footer {
-            position: fixed;
-            bottom: 0;
-            left: 0;
-            right: 0;
-            width: 100%;
-            text-align: center;
-            padding: 20px 0;
-            background-color: #f8f9fa;
-            border-top: 1px solid #eee;
+    // 添加 WebDAV 信息输出
+    println!("\nWebDAV 信息:");
+    println!("- 状态: {}", if config.webdav.enabled { "已启用" } else { "已禁用" });
+    if config.webdav.enabled {
+        println!("- 锁管理: {}", if config.webdav.locking { "已启用" } else { "已禁用" });
+        if config.webdav.read_only {
+            println!("- 模式: 只读（拒绝所有写操作）");
         }
-        .footer a {
-            color: #666;
-            text-decoration: none;
-            font-size: 14px;
-            display: block;
-            margin: 0 auto;
+        if config.webdav.allow_anonymous {
+            println!("- 匿名访问: 已允许（只读）");
         }
-        .footer p {
-            margin: 5px 0;
-            color: #999;
-            font-size: 12px;
+        if let Some(root) = &config.webdav.root {
+            println!("- 根目录: {}", root);
         }
-        body {
-            margin-bottom: 100px;
+        if let Some(base) = ipv4_url.as_ref().or(ipv6_url.as_ref()) {
+            println!("- 地址: {}{}/", base, config.webdav.mount_prefix());
         }
-        @media (max-width: 768px) {
-            body {
-                margin: 10px;
-            }
-            .entry {
-                flex-direction: column;
-                align-items: flex-start;
-                gap: 8px;
-                padding: 12px;
-            }
-            .name-column {
-                flex: 1;
-                width: 100%;
-                margin-bottom: 4px;
-            }
-            .info-group {
-                width: 100%;
-                justify-content: flex-start;
-                flex-wrap: wrap;
-                gap: 10px;
-            }
-            .size-column {
-                min-width: auto;
-                order: 2;
-            }
-            .date-column {
-                min-width: auto;
-                width: 100%;
-                text-align: left;
-                order: 3;
-            }
-            .download-btn {
-                order: 1;
-                margin-right: 0;
-            }
-            h1 {
-                font-size: 1.2em;
+        if config.webdav.users.is_empty() {
+            println!("- 用户: 未配置任何用户");
+        } else {
+            println!("- 已配置用户列表:");
+            for (username, user_config) in &config.webdav.users {
+                println!("  用户名: {}", username);
+                println!("  密码: {}", MASKED_PASSWORD);
+                println!("  权限: {}", user_config.permissions);
+                println!();
             }
         }
-    </style>
-</head>
-<body>
-    <h1>目录: /{{current_path}}</h1>
-    {% for entry in entries %}
-    <div class="entry">
-        <div class="name-column">
-            {% if entry.is_dir %}
-            <a href="./{{entry.name}}/" class="directory">📁 {{entry.name}}/</a>
-            {% else %}
-            <a href="./{{entry.name}}">
-                <span class="file-icon" id="icon-{{entry.name}}">{{entry.icon}}</span>
-                <span class="preview-container" id="preview-{{entry.name}}"></span>
-                {{entry.display_name}}
-            </a>
-            {% endif %}
-        </div>
-        <div class="info-group">
-            {% if not entry.is_dir %}
-                {% if entry.preview_url != "" %}
-                <span class="preview-btn" onclick="togglePreview('{{entry.preview_url}}', '{{entry.display_name}}')">预览</span>
-                {% endif %}
-                <a href="./{{entry.name}}" class="download-btn" download="{{entry.display_name}}">下载</a>
-                <div class="size-column">{{entry.size_string}}</div>
-            {% endif %}
-            <div class="date-column">{{entry.modified_time}}</div>
-        </div>
-    </div>
-    {% endfor %}
-
-    <div id="preview-modal" class="preview-modal" onclick="this.style.display='none'">
-        <div class="preview-content" id="preview-content" onclick="event.stopPropagation()"></div>
-    </div>
-        <footer class="footer">
-        <a href="https://yxyos.cn" target="_blank">
-            <p>©2025 云溪起源</p>
-        </a>
-    </footer>
-    <script>
-    function togglePreview(url, name) {
-        const previewContainer = document.getElementById(`preview-${name}`);
-        const icon = document.getElementById(`icon-${name}`);
-        const ext = name.split('.').pop().toLowerCase();
-        
-        if (previewContainer.style.display === 'block') {
-            previewContainer.style.display = 'none';
-            icon.style.display = 'inline-block';
-            previewContainer.innerHTML = '';
-            return;
-        }
-
-        icon.style.display = 'none';
-        previewContainer.style.display = 'block';
-        
-        if (['jpg', 'jpeg', 'png', 'gif', 'webp'].includes(ext)) {
-            previewContainer.innerHTML = `<img src="${url}" alt="${name}">`;
-        } else if (['mp4', 'webm'].includes(ext)) {
-            previewContainer.innerHTML = `<video src="${url}" controls></video>`;
-        } else if (['mp3', 'wav', 'ogg'].includes(ext)) {
-            previewContainer.innerHTML = `<audio src="${url}" controls></audio>`;
-        }
     }
-    </script>
-</body>
-</html>
-"#;
+}
 
-const VERSION: &str = env!("CARGO_PKG_VERSION");
-const AUTHORS: &str = env!("CARGO_PKG_AUTHORS", "yxyos");
-const DESCRIPTION: &str = env!("CARGO_PKG_DESCRIPTION");
+// 启动信息的 JSON 形式，字段与交互式输出一致；不包含用户密码
+fn banner_json(config: &Config) -> serde_json::Value {
+    let (ipv4_url, ipv6_url) = listen_urls(config);
+    let webdav_url = ipv4_url.as_ref().or(ipv6_url.as_ref())
+        .map(|base| format!("{}{}/", base, config.webdav.mount_prefix()));
+    let users: BTreeMap<&String, &String> = config.webdav.users.iter()
+        .map(|(name, user)| (name, &user.permissions))
+        .collect();
 
-fn print_version() {
-    println!("云溪起源网盘 v{}", VERSION);
-    println!("作者: {}", AUTHORS);
-    println!("描述: {}", DESCRIPTION);
+    serde_json::json!({
+        "name": "云溪起源网盘",
+        "version": VERSION,
+        "pid": std::process::id(),
+        "bind_mode": config.bind_mode,
+        "ipv4": ipv4_url,
+        "ipv6": ipv6_url,
+        "cwd": config.cwd,
+        "webdav": {
+            "enabled": config.webdav.enabled,
+            "locking": config.webdav.locking,
+            "read_only": config.webdav.read_only,
+            "allow_anonymous": config.webdav.allow_anonymous,
+            "root": config.webdav.root_dir(&config.cwd),
+            "url": webdav_url,
+            "users": users,
+        },
+    })
 }
 
 fn print_help() {
@@ -734,24 +329,23 @@ fn print_help() {
     println!("\n选项:");
     println!("  -h, --help     显示帮助信息");
     println!("  -v, --version  显示版本信息");
+    println!("  --json-banner  以单行 JSON 输出启动信息（非终端输出时默认启用）");
+    println!("  --force        文件存储目录包含数据目录时仍然启动");
+    println!("  --strict-config  配置文件中存在未知字段时拒绝启动（默认警告后忽略）");
     println!("  --webdav       WebDAV 配置");
+    println!("  open           在系统文件管理器中打开文件存储目录");
+    println!("  serve [目录] [--port 端口] [--bind 地址]  不读取配置文件，直接共享指定目录（默认当前目录）");
     println!("\nWebDAV 配置:");
     println!("  --webdav true false          启用或禁用 WebDAV");
     println!("  --webdav add|del 用户名      添加或删除用户");
+    println!("  --webdav del 用户名 --purge  删除用户并删除其独立目录");
+    println!("  --webdav genpass [长度] [--readable] [--no-symbols]  生成随机密码");
+    println!("  --webdav hash-passwords      将明文保存的密码转换为哈希");
+    println!("  --webdav export 文件 [--mask]             导出用户（.csv 或 .yaml）");
+    println!("  --webdav import 文件 [--merge|--replace]  批量导入用户");
     println!("  --webdav 用户名:rwx 密码     设置权限和密码");
 }
 
-// 修改错误类型
-#[derive(Debug)]
-struct ConfigError(String);
-
-impl std::fmt::Display for ConfigError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
-
-impl std::error::Error for ConfigError {}
 
 fn is_valid_ip(value: &str) -> bool {
     if !value.chars().all(|c| c.is_ascii_digit() || c == '.') {
@@ -841,7 +435,7 @@ fn is_valid_ipv6(value: &str) -> bool {
 
 fn update_config(key: &str, value: &str) -> std::io::Result<()> {
     let config_path = Path::new("data/config.yaml");
-    let config_str = fs::read_to_string(&config_path)?;
+    let config_str = fs::read_to_string(config_path)?;
     let mut config: serde_yaml::Value = serde_yaml::from_str(&config_str)
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
 
@@ -858,35 +452,35 @@ fn update_config(key: &str, value: &str) -> std::io::Result<()> {
         "ipv6" => {
             if value == "no" {
                 config["ipv6"] = serde_yaml::Value::String("".to_string());
-            } else if !is_valid_ipv6(value) {
+            } else if !is_valid_ipv6(strip_ipv6_brackets(value)) {
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::InvalidInput,
                     ConfigError("必须是有效的IPv6地址（如 ::1 或 2001:db8::1）或 'no' 以禁用 IPv6".to_string())
                 ));
             } else {
-                config["ipv6"] = serde_yaml::Value::String(value.to_string());
+                config["ipv6"] = serde_yaml::Value::String(strip_ipv6_brackets(value).to_string());
             }
         }
         "port" => {
             match value.parse::<u16>() {
-                Ok(port) if port > 0 => {
+                // 0 表示由系统分配空闲端口，启动信息中显示实际端口
+                Ok(port) => {
                     config["port"] = serde_yaml::Value::Number(serde_yaml::Number::from(port));
                 }
                 _ => return Err(std::io::Error::new(
                     std::io::ErrorKind::InvalidInput,
-                    ConfigError("端口必须是1-65535之间的数字".to_string())
+                    ConfigError("端口必须是0-65535之间的数字".to_string())
                 ))
             }
         }
         "cwd" => {
-            let path = Path::new(value);
-            if !path.is_absolute() && !value.starts_with("./") && !value.starts_with("../") {
+            if !is_valid_cwd(value, cfg!(windows)) {
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::InvalidInput,
                     ConfigError("路径必须是绝对路径或以 ./ 或 ../ 开头的相对路径".to_string())
                 ));
             }
-            config["cwd"] = serde_yaml::Value::String(value.to_string());
+            config["cwd"] = serde_yaml::Value::String(normalize_separators(value, cfg!(windows)));
         }
         _ => return Err(std::io::Error::new(
             std::io::ErrorKind::InvalidInput,
@@ -896,20 +490,24 @@ fn update_config(key: &str, value: &str) -> std::io::Result<()> {
 
     let new_config = serde_yaml::to_string(&config)
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-    fs::write(&config_path, new_config)?;
+    fs::write(config_path, new_config)?;
     println!("已更新配置: {} = {}", key, value);
     Ok(())
 }
 
-fn write_pid() -> std::io::Result<()> {
-    let pid = std::process::id().to_string();
-    fs::write("data/yunxi-webdisk.pid", pid)?;
+// PID 文件第一行为进程号，监听成功后第二行写入实际监听的端口
+fn write_pid(port: Option<u16>) -> std::io::Result<()> {
+    let mut content = std::process::id().to_string();
+    if let Some(port) = port {
+        content.push_str(&format!("\n{}\n", port));
+    }
+    fs::write("data/yunxi-webdisk.pid", content)?;
     Ok(())
 }
 
 fn read_pid() -> std::io::Result<u32> {
     let pid_str = fs::read_to_string("data/yunxi-webdisk.pid")?;
-    pid_str.trim().parse::<u32>()
+    pid_str.lines().next().unwrap_or("").trim().parse::<u32>()
         .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid PID"))
 }
 
@@ -945,6 +543,29 @@ fn stop_process(pid: u32) -> std::io::Result<()> {
     Ok(())
 }
 
+// 用系统文件管理器打开目录，只负责启动，不等待其退出
+#[cfg(target_os = "macos")]
+fn open_in_file_manager(path: &Path) -> std::io::Result<()> {
+    Command::new("open").arg(path).spawn()?;
+    Ok(())
+}
+
+#[cfg(all(target_family = "unix", not(target_os = "macos")))]
+fn open_in_file_manager(path: &Path) -> std::io::Result<()> {
+    Command::new("xdg-open")
+        .arg(path)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+#[cfg(target_family = "windows")]
+fn open_in_file_manager(path: &Path) -> std::io::Result<()> {
+    Command::new("explorer").arg(path).spawn()?;
+    Ok(())
+}
+
 // 修改错误处理函数，使用引用而不是获取所有权
 fn format_error(e: &std::io::Error) -> String {
     match e.kind() {
@@ -963,25 +584,83 @@ fn format_error(e: &std::io::Error) -> String {
     }
 }
 
-// 添加随机密码生成函数
-fn generate_random_password() -> String {
-    let mut rng = thread_rng();
-    let password: String = (0..8)
-        .map(|_| {
-            let c = rng.sample(Alphanumeric) as char;
-            if rng.gen_bool(0.5) {
-                c.to_ascii_uppercase()
-            } else {
-                c.to_ascii_lowercase()
+// serve 命令使用的临时配置：不读写 config.yaml，不启用 WebDAV
+fn serve_config(args: &[String]) -> Result<Config, String> {
+    let mut config = Config::default();
+    config.webdav.enabled = false;
+    config.webdav.users.clear();
+    let mut dir = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--port" => {
+                let value = rest.next().ok_or("--port 需要指定端口")?;
+                config.port = value.parse().map_err(|_| format!("无效的端口 {}", value))?;
             }
-        })
-        .collect();
-    password
+            "--bind" => {
+                let value = rest.next().ok_or("--bind 需要指定地址")?;
+                if is_valid_ip(value) || is_valid_domain(value) {
+                    config.ip = value.to_string();
+                    config.bind_mode = BindMode::Ipv4;
+                } else if is_valid_ipv6(strip_ipv6_brackets(value)) {
+                    config.ipv6 = value.to_string();
+                    config.bind_mode = BindMode::Ipv6;
+                } else {
+                    return Err(format!("无效的监听地址 {}", value));
+                }
+            }
+            "--json-banner" | "--force" | "--strict-config" => {}
+            value if value.starts_with('-') => return Err(format!("未知选项 {}", value)),
+            value if dir.is_none() => dir = Some(value),
+            value => return Err(format!("多余的参数 {}", value)),
+        }
+    }
+    let dir = dir.unwrap_or(".");
+    if !Path::new(dir).is_dir() {
+        return Err(format!("目录不存在: {}", dir));
+    }
+    config.cwd = dir.to_string();
+    Ok(config)
+}
+
+
+// 重启时旧进程可能尚未释放端口，端口被占用时按配置间隔重试，其他错误直接返回
+async fn bind_with_retry(addr: &str, ipv6: bool, family: &str, config: &Config) -> std::io::Result<Vec<std::net::TcpListener>> {
+    let attempts = config.server.bind_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        match bind_listeners(addr, ipv6) {
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && attempt < attempts => {
+                tracing::warn!(
+                    event = "bind_retry",
+                    family,
+                    addr,
+                    attempt,
+                    attempts,
+                    error = %format_error(&e),
+                    "绑定失败，{} 毫秒后重试（第 {}/{} 次）", config.server.bind_retry_interval_ms, attempt, attempts,
+                );
+                actix_web::rt::time::sleep(Duration::from_millis(config.server.bind_retry_interval_ms)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
+    webdisk::set_strict_config(args.iter().any(|a| a == "--strict-config"));
+    // 日志需在加载配置前初始化，才能记录加载过程中的警告；serve 不读取配置文件
+    let log_format = if args.get(1).is_some_and(|a| a == "serve") {
+        LogFormat::Text
+    } else {
+        webdisk::configured_log_format()
+    };
+    webdisk::init_logging(log_format);
+    // serve 命令直接使用内存中的配置
+    let mut transient = None;
     
     if args.len() > 1 {
         match args[1].as_str() {
@@ -996,7 +675,7 @@ async fn main() -> std::io::Result<()> {
             "--host" => {
                 if args.len() == 4 {
                     if let Err(e) = update_config(&args[2], &args[3]) {
-                        eprintln!("{}", e.get_ref().unwrap().to_string());
+                        eprintln!("{}", e.get_ref().unwrap());
                         std::process::exit(1);
                     }
                     return Ok(());
@@ -1038,7 +717,7 @@ async fn main() -> std::io::Result<()> {
             }
             "start" => {
                 // 检查是否已经在运行
-                if let Ok(_) = read_pid() {
+                if read_pid().is_ok() {
                     println!("服务已经在运行中");
                     return Ok(());
                 }
@@ -1047,6 +726,7 @@ async fn main() -> std::io::Result<()> {
                 let exe = env::current_exe()?;
                 Command::new(exe)
                     .arg("run")
+                    .args(args.iter().filter(|a| matches!(a.as_str(), "--force" | "--strict-config")))
                     .stdin(std::process::Stdio::null())
                     .stdout(OpenOptions::new()
                         .create(true)
@@ -1090,9 +770,37 @@ async fn main() -> std::io::Result<()> {
                 }
                 return Ok(());
             }
+            "open" => {
+                // 先打印绝对路径，没有图形界面时也能知道文件实际存放的位置
+                let config = Config::load()?;
+                let cwd = match std::path::absolute(&config.cwd) {
+                    Ok(cwd) if cwd.is_dir() => cwd,
+                    _ => {
+                        eprintln!("文件存储目录不存在: {}", config.cwd);
+                        std::process::exit(1);
+                    }
+                };
+                println!("{}", cwd.display());
+                if let Err(e) = open_in_file_manager(&cwd) {
+                    eprintln!("无法打开文件管理器: {}", e);
+                }
+                return Ok(());
+            }
+            "serve" => {
+                match serve_config(&args[2..]) {
+                    Ok(config) => transient = Some(config),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
             "run" => {
                 // 内部命令，用于实际运行服务
-                write_pid()?;
+                write_pid(None)?;
+            }
+            "--json-banner" | "--force" | "--strict-config" => {
+                // 前台运行，仅改变启动信息的输出格式或启动检查
             }
             "--webdav" => {
                 let mut config = Config::load()?;
@@ -1122,25 +830,34 @@ async fn main() -> std::io::Result<()> {
                                 // 检查用户是否已存在
                                 if !config.webdav.users.contains_key(username) {
                                     if let Some(password) = args.get(4) {
+                                        if let Err(e) = config.webdav.check_password(password) {
+                                            println!("密码强度不足: {}", e);
+                                            return Ok(());
+                                        }
                                         // 添加带权限和密码的用户
                                         config.webdav.users.insert(username.to_string(), UserConfig {
-                                            password: password.to_string(),
+                                            password: hash_password(password),
                                             permissions: permissions.to_string(),
+                                            root: None,
+                                            landing_path: None,
                                         });
                                         println!("已添加用户:");
                                         println!("- 用户名: {}", username);
-                                        println!("- 密码: {}", password);
+                                        println!("- 密码: {}", MASKED_PASSWORD);
                                         println!("- 权限: {}", permissions);
                                     } else {
                                         // 添加带权限的用户，使用随机密码
-                                        let random_password = generate_random_password();
+                                        let random_password = config.webdav.password_options().generate();
                                         config.webdav.users.insert(username.to_string(), UserConfig {
-                                            password: random_password.clone(),
+                                            password: hash_password(&random_password),
                                             permissions: permissions.to_string(),
+                                            root: None,
+                                            landing_path: None,
                                         });
                                         println!("已添加用户:");
                                         println!("- 用户名: {}", username);
                                         println!("- 密码: {}", random_password);
+                                        println!("- 强度: 约 {:.0} 位熵", config.webdav.password_options().entropy_bits());
                                         println!("- 权限: {}", permissions);
                                     }
                                 } else {
@@ -1150,23 +867,32 @@ async fn main() -> std::io::Result<()> {
                                 // 原有的普通添加用户逻辑，使用随机密码
                                 if !config.webdav.users.contains_key(username) {
                                     if let Some(password) = args.get(4) {
+                                        if let Err(e) = config.webdav.check_password(password) {
+                                            println!("密码强度不足: {}", e);
+                                            return Ok(());
+                                        }
                                         config.webdav.users.insert(username.to_string(), UserConfig {
-                                            password: password.to_string(),
+                                            password: hash_password(password),
                                             permissions: "r".to_string(),
+                                            root: None,
+                                            landing_path: None,
                                         });
                                         println!("已添加用户:");
                                         println!("- 用户名: {}", username);
-                                        println!("- 密码: {}", password);
+                                        println!("- 密码: {}", MASKED_PASSWORD);
                                         println!("- 权限: r");
                                     } else {
-                                        let random_password = generate_random_password();
+                                        let random_password = config.webdav.password_options().generate();
                                         config.webdav.users.insert(username.to_string(), UserConfig {
-                                            password: random_password.clone(),
+                                            password: hash_password(&random_password),
                                             permissions: "r".to_string(),
+                                            root: None,
+                                            landing_path: None,
                                         });
                                         println!("已添加用户:");
                                         println!("- 用户名: {}", username);
                                         println!("- 密码: {}", random_password);
+                                        println!("- 强度: 约 {:.0} 位熵", config.webdav.password_options().entropy_bits());
                                         println!("- 权限: r");
                                     }
                                 } else {
@@ -1179,7 +905,32 @@ async fn main() -> std::io::Result<()> {
                     }
                     Some("del") => {
                         if let Some(username) = args.get(3) {
-                            if config.webdav.users.remove(username).is_some() {
+                            let purge = args.get(4).map(|s| s.as_str()) == Some("--purge");
+                            if let Some(user) = config.webdav.users.get(username) {
+                                let root = user.root_dir(config.webdav.root_dir(&config.cwd));
+                                if purge {
+                                    if let Err(reason) = check_purge_target(user, config.webdav.root_dir(&config.cwd)) {
+                                        println!("拒绝删除用户目录: {}", reason);
+                                        return Ok(());
+                                    }
+                                    println!("将删除用户 {} 及其目录 {} 下的所有文件", username, root.display());
+                                    println!("输入 yes 确认，其他任意输入取消");
+                                    let mut input = String::new();
+                                    std::io::stdin().read_line(&mut input)?;
+                                    if input.trim() != "yes" {
+                                        println!("已取消");
+                                        return Ok(());
+                                    }
+                                    if root.exists() {
+                                        fs::remove_dir_all(&root)?;
+                                    }
+                                    println!("已删除用户目录 {}", root.display());
+                                } else if user.root.is_some() {
+                                    println!("提示: 用户文件仍保留在 {}，如需一并删除请使用 --purge", root.display());
+                                } else {
+                                    println!("提示: 该用户使用共享根目录 {}，其中的文件未做改动", root.display());
+                                }
+                                config.webdav.users.remove(username);
                                 println!("已删除用户 {}", username);
                             } else {
                                 println!("用户 {} 不存在", username);
@@ -1188,6 +939,67 @@ async fn main() -> std::io::Result<()> {
                             println!("请指定要删除的用户名");
                         }
                     }
+                    Some("genpass") => {
+                        // 只生成并打印密码，不修改配置
+                        let mut options = config.webdav.password_options();
+                        for arg in &args[3..] {
+                            match arg.as_str() {
+                                "--readable" => options.readable = true,
+                                "--no-symbols" => options.symbols = false,
+                                len => match len.parse::<usize>() {
+                                    Ok(len) if (8..=256).contains(&len) => options.len = len,
+                                    _ => {
+                                        println!("无效的密码长度 {}，必须是 8-256 之间的数字", len);
+                                        return Ok(());
+                                    }
+                                },
+                            }
+                        }
+                        println!("{}", options.generate());
+                        eprintln!("强度: 约 {:.0} 位熵", options.entropy_bits());
+                        return Ok(());
+                    }
+                    Some("hash-passwords") => {
+                        // 将旧版本遗留的明文密码转换为哈希，已是哈希的密码不变
+                        let mut migrated = Vec::new();
+                        for (username, user) in config.webdav.users.iter_mut().filter(|(_, u)| !u.password_is_hashed()) {
+                            user.password = hash_password(&user.password);
+                            migrated.push(username.clone());
+                        }
+                        if migrated.is_empty() {
+                            println!("所有用户的密码均已以哈希形式保存");
+                        } else {
+                            println!("已将 {} 个用户的明文密码转换为哈希: {}", migrated.len(), migrated.join(", "));
+                        }
+                    }
+                    Some("export") => {
+                        let Some(file) = args.get(3) else {
+                            println!("请指定导出文件路径");
+                            return Ok(());
+                        };
+                        let mask = args.get(4).map(|s| s.as_str()) == Some("--mask");
+                        export_users(&config.webdav.users, Path::new(file), mask)?;
+                        println!("已导出 {} 个用户到 {}", config.webdav.users.len(), file);
+                        return Ok(());
+                    }
+                    Some("import") => {
+                        let Some(file) = args.get(3) else {
+                            println!("请指定导入文件路径");
+                            return Ok(());
+                        };
+                        let replace = match args.get(4).map(|s| s.as_str()) {
+                            None | Some("--merge") => false,
+                            Some("--replace") => true,
+                            Some(other) => {
+                                println!("未知的导入策略 {}，只能是 --merge 或 --replace", other);
+                                return Ok(());
+                            }
+                        };
+                        if let Err(e) = import_users(&mut config, Path::new(file), replace) {
+                            eprintln!("导入失败: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
                     Some(arg) => {
                         if let Some(username) = args.get(2) {
                             if arg.contains(':') {
@@ -1204,15 +1016,21 @@ async fn main() -> std::io::Result<()> {
 
                                 // 检查是否同时设置密码
                                 if let Some(password) = args.get(3) {
+                                    if let Err(e) = config.webdav.check_password(password) {
+                                        println!("密码强度不足: {}", e);
+                                        return Ok(());
+                                    }
                                     if let Some(user) = config.webdav.users.get_mut(username) {
                                         user.permissions = permissions.to_string();
-                                        user.password = password.to_string();
+                                        user.password = hash_password(password);
                                         println!("已更新用户 {} 的权限为 {} 和密码", username, permissions);
                                     } else {
                                         // 如果用户不存在，创建新用户
                                         config.webdav.users.insert(username.to_string(), UserConfig {
-                                            password: password.to_string(),
+                                            password: hash_password(password),
                                             permissions: permissions.to_string(),
+                                            root: None,
+                                            landing_path: None,
                                         });
                                         println!("已创建用户 {}，设置权限为 {} 和密码", username, permissions);
                                     }
@@ -1226,9 +1044,13 @@ async fn main() -> std::io::Result<()> {
                                     }
                                 }
                             } else if let Some(password) = args.get(3) {
+                                if let Err(e) = config.webdav.check_password(password) {
+                                    println!("密码强度不足: {}", e);
+                                    return Ok(());
+                                }
                                 // 只设置密码
                                 if let Some(user) = config.webdav.users.get_mut(username) {
-                                    user.password = password.to_string();
+                                    user.password = hash_password(password);
                                     println!("已更新用户 {} 的密码", username);
                                 } else {
                                     println!("用户 {} 不存在", username);
@@ -1246,8 +1068,15 @@ async fn main() -> std::io::Result<()> {
                             println!("\n用户列表:");
                             for (username, user) in &config.webdav.users {
                                 println!("- {}", username);
-                                println!("  密码: {}", user.password);
+                                if user.password_is_hashed() {
+                                    println!("  密码: {}", MASKED_PASSWORD);
+                                } else {
+                                    println!("  密码: {}（明文保存，建议执行 --webdav hash-passwords）", MASKED_PASSWORD);
+                                }
                                 println!("  权限: {}", user.permissions);
+                                if let Some(root) = &user.root {
+                                    println!("  目录: {}", root);
+                                }
                             }
                         } else {
                             println!("未配置任何用户");
@@ -1256,7 +1085,7 @@ async fn main() -> std::io::Result<()> {
                 }
                 // 保存配置
                 let yaml_str = serde_yaml::to_string(&config)
-                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+                    .map_err(std::io::Error::other)?;
                 fs::write("data/config.yaml", yaml_str)?;
                 return Ok(());
             }
@@ -1267,124 +1096,176 @@ async fn main() -> std::io::Result<()> {
         }
     }
 
-    let config = if let Ok(config_path) = env::var("YUNXI_CONFIG") {
+    let serving = transient.is_some();
+    let mut config = if let Some(config) = transient {
+        config
+    } else if let Ok(config_path) = env::var("YUNXI_CONFIG") {
         Config::load_from(Path::new(&config_path))?
     } else {
         Config::load()?
     };
 
-    let bind_addr_v4 = format!("{}:{}", config.ip, config.port);
-    let ipv6_bind = if config.ipv6.starts_with('[') {
-        format!("{}:{}", config.ipv6, config.port)
-    } else {
-        format!("{}:{}", config.ipv6, config.port)
-    };
-    let has_ipv6 = !config.ipv6.is_empty();
-    
-    println!("\n云溪起源网盘 v{}", VERSION);
-    println!("作者: {}", AUTHORS);
-    println!("描述: {}\n", DESCRIPTION);
-    
-    println!("系统信息:");
-    println!("- PID: {}", std::process::id());
-    println!("- IPv4: http://{}", bind_addr_v4);
-    if has_ipv6 {
-        let display_ipv6 = if config.ipv6.starts_with('[') {
-            config.ipv6.to_string()
-        } else {
-            format!("[{}]", config.ipv6)
-        };
-        println!("- IPv6: http://{}:{}", display_ipv6, config.port);
+    // 对外提供的目录包含程序自身的数据目录时拒绝启动，确认无误可用 --force 跳过
+    // serve 不创建数据目录，只在数据目录已存在时检查
+    let overlap = if serving && !Path::new("data").is_dir() { None } else { config.data_dir_overlap() };
+    if let Some(data_dir) = overlap {
+        tracing::warn!(
+            event = "data_dir_overlap",
+            cwd = %config.cwd,
+            data_dir = %data_dir.display(),
+            "文件存储目录包含程序数据目录，配置文件（含 WebDAV 密码）、PID 与日志位于该目录中，虽然不会出现在列表中，但建议将 cwd 改为单独的目录",
+        );
+        if !args.iter().any(|a| a == "--force") {
+            tracing::error!(event = "startup_refused", "如确认无误，请使用 --force 启动");
+            // run 已写入 PID 文件，未启动就退出时需清理，否则 start 会误认为服务在运行
+            if args.get(1).is_some_and(|a| a == "run") {
+                let _ = fs::remove_file("data/yunxi-webdisk.pid");
+            }
+            std::process::exit(1);
+        }
     }
-    println!("- 目录: {}", config.cwd);
 
-    // 添加 WebDAV 信息输出
-    println!("\nWebDAV 信息:");
-    println!("- 状态: {}", if config.webdav.enabled { "已启用" } else { "已禁用" });
-    if config.webdav.enabled {
-        if config.webdav.users.is_empty() {
-            println!("- 用户: 未配置任何用户");
+    webdisk::init_icon_overrides();
+
+    // 按监听方式分别为 IPv4 与 IPv6 创建套接字；双栈模式下任一成功即可启动
+    // 端口为 0 时由系统分配空闲端口，先绑定的协议分配到的端口同样用于另一种协议
+    let has_ipv6 = !config.ipv6.is_empty();
+    let (want_ipv4, want_ipv6) = (config.bind_mode != BindMode::Ipv6, config.bind_mode != BindMode::Ipv4 && has_ipv6);
+    let mut listeners = Vec::new();
+    let mut bound = Vec::new();
+    let mut first_error = None;
+    for (family, wanted, is_v6) in [("IPv4", want_ipv4, false), ("IPv6", want_ipv6, true)] {
+        if !wanted {
+            continue;
+        }
+        // validate 已保证非空的 ipv6 可以解析，这里得到的总是带方括号的 [addr]:port
+        let addr = if is_v6 {
+            ipv6_socket_addr(&config.ipv6, config.port).map(|addr| addr.to_string()).unwrap_or_default()
         } else {
-            println!("- 已配置用户列表:");
-            for (username, user_config) in &config.webdav.users {
-                println!("  用户名: {}", username);
-                println!("  密码: {}", user_config.password);
-                println!("  权限: {}", user_config.permissions);
-                println!();
+            format!("{}:{}", config.ip, config.port)
+        };
+        match bind_with_retry(&addr, is_v6, family, &config).await {
+            Ok(found) => {
+                if config.port == 0 {
+                    config.port = found[0].local_addr()?.port();
+                }
+                listeners.extend(found);
+                bound.push(family);
+            }
+            Err(e) => {
+                tracing::error!(event = "bind_failed", family, addr, error = %format_error(&e), "绑定失败");
+                first_error.get_or_insert(e);
             }
         }
     }
+    if let Some(e) = first_error {
+        // 只监听一种协议时，绑定失败直接退出
+        if bound.is_empty() || config.bind_mode != BindMode::Dual {
+            return Err(e);
+        }
+    }
+    // 后台运行时把实际监听的端口写入 PID 文件，便于脚本读取
+    if args.get(1).is_some_and(|a| a == "run") {
+        write_pid(Some(config.port))?;
+    }
 
-    println!("\n服务启动中...");
+    // 旧版本配置中的明文密码仍可登录，启动时提醒转换
+    let plaintext: Vec<&str> = config.webdav.users.iter()
+        .filter(|(_, user)| !user.password_is_hashed())
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if !plaintext.is_empty() {
+        tracing::warn!(
+            event = "plaintext_passwords",
+            users = %plaintext.join(","),
+            "以下 WebDAV 用户的密码以明文保存，可执行 webdisk --webdav hash-passwords 转换为哈希",
+        );
+    }
+
+    // JSON 日志中启动信息作为一条日志事件输出；
+    // 非交互终端（如 start 写入日志文件）或指定 --json-banner 时输出单行 JSON，便于日志采集
+    if log_format == LogFormat::Json {
+        let (ipv4, ipv6) = listen_urls(&config);
+        tracing::info!(
+            event = "startup",
+            version = VERSION,
+            pid = std::process::id(),
+            bind_mode = config.bind_mode.as_str(),
+            ipv4,
+            ipv6,
+            cwd = %config.cwd,
+            webdav = config.webdav.enabled,
+            "服务启动中",
+        );
+    } else if args.iter().any(|a| a == "--json-banner") || !std::io::stdout().is_terminal() {
+        println!("{}", banner_json(&config));
+    } else {
+        print_banner(&config);
+        println!("\n服务启动中...");
+    }
     
+    let state = AppState::new(&config);
     let app_factory = {
         let config = config.clone();
-        move || {
-            let mut app = App::new()
-                .wrap(Compress::default())
-                .app_data(web::Data::new(config.clone()))
-                .service(index);
-            
-            if config.webdav.enabled {
-                app = app.service(webdav_handler);
-            }
-            
-            app
-        }
+        move || build_app(&config, &state)
     };
     
     // 创建基本的服务器配置
+    let workers = num_cpus::get();
+    let per_ip_limit = config.limits.max_connections_per_ip;
     let make_server = || {
-        HttpServer::new(app_factory.clone())
-            .workers(num_cpus::get())
+        let server = HttpServer::new(app_factory.clone())
+            .workers(workers)
             .backlog(1024)
             .keep_alive(Duration::from_secs(30))
-    };
-
-    // 尝试绑定 IPv4
-    let server = match make_server().bind(&bind_addr_v4) {
-        Ok(ipv4_server) => {
-            if has_ipv6 {
-                match ipv4_server.bind(&ipv6_bind) {
-                    Ok(dual_server) => {
-                        println!("服务器启动成功");
-                        dual_server
-                    }
-                    Err(e) => {
-                        println!("服务器启动成功（仅 IPv4）");
-                        println!("IPv6 绑定失败: {}", format_error(&e));
-                        make_server().bind(&bind_addr_v4)?
-                    }
+            .client_request_timeout(Duration::from_secs(config.server.client_timeout_secs))
+            .client_disconnect_timeout(Duration::from_secs(config.server.client_shutdown_secs))
+            // 连接建立时按对端 IP 计数，占位存放在连接的扩展数据中，连接关闭时释放
+            .on_connect(move |conn, ext| {
+                if per_ip_limit == 0 {
+                    return;
                 }
-            } else {
-                println!("服务器启动成功");
-                ipv4_server
-            }
-        }
-        Err(e) => {
-            eprintln!("IPv4 绑定失败: {}", format_error(&e));
-            if has_ipv6 {
-                match make_server().bind(&ipv6_bind) {
-                    Ok(ipv6_server) => {
-                        println!("服务器启动成功（仅 IPv6）");
-                        ipv6_server
-                    }
-                    Err(e2) => {
-                        eprintln!("IPv6 绑定失败: {}", format_error(&e2));
-                        return Err(e);
-                    }
+                let peer = conn.downcast_ref::<actix_web::rt::net::TcpStream>()
+                    .and_then(|stream| stream.peer_addr().ok());
+                if let Some(peer) = peer {
+                    ext.insert(ConnectionSlot::acquire(peer.ip(), per_ip_limit));
                 }
-            } else {
-                return Err(e);
-            }
+            });
+        // actix 的连接上限按工作线程计算，总上限平均分配给各工作线程
+        match config.limits.max_connections {
+            0 => server,
+            max => server.max_connections(max.div_ceil(workers)),
         }
     };
 
+    let server = listeners.into_iter().try_fold(make_server(), |server, listener| server.listen(listener))?;
+    match bound.as_slice() {
+        [only] if want_ipv4 && want_ipv6 => {
+            tracing::info!(event = "listening", port = config.port, family = *only, "服务器启动成功（仅 {}）", only)
+        }
+        _ => tracing::info!(event = "listening", port = config.port, family = %bound.join(","), "服务器启动成功"),
+    }
+
+    // serve 额外给出可直接在浏览器中打开的地址，监听全部地址时使用本机回环地址
+    if serving {
+        let host = match config.bind_mode {
+            BindMode::Ipv6 => match strip_ipv6_brackets(&config.ipv6) {
+                "::" => "[::1]".to_string(),
+                addr => format!("[{}]", addr),
+            },
+            _ if config.ip == "0.0.0.0" => "127.0.0.1".to_string(),
+            _ => config.ip.clone(),
+        };
+        let dir = std::path::absolute(&config.cwd).unwrap_or_else(|_| config.cwd.clone().into());
+        let url = format!("http://{}:{}/", host, config.port);
+        tracing::info!(event = "serving", dir = %dir.display(), url, "正在共享 {}: {}", dir.display(), url);
+    }
+
     // 启动服务器
     if let Err(e) = server.run().await {
-        eprintln!("{}", format_error(&e));
+        tracing::error!(event = "server_error", error = %format_error(&e), "服务异常退出");
         std::process::exit(1);
     }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,2081 @@
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::dev::Payload;
+use actix_web::error::PayloadError;
+use actix_web::{test, web};
+use futures_util::stream::{self, Stream};
+use std::pin::Pin;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rand::{thread_rng, Rng};
+use std::path::PathBuf;
+use std::{env, fs};
+use webdisk::{bind_listeners, build_app, hash_password, ipv6_socket_addr, is_valid_cwd, normalize_separators, template_error_response, AccelMode, AppState, Config, ConflictPolicy, UserConfig};
+
+// 测试用的临时目录：www 作为 cwd，outside.txt 位于 cwd 之外，用于检查越界访问
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn new() -> Self {
+        let root = env::temp_dir().join(format!("webdisk-test-{}-{}", std::process::id(), thread_rng().gen::<u64>()));
+        fs::create_dir_all(root.join("www/docs")).unwrap();
+        fs::write(root.join("www/hello.txt"), "hello").unwrap();
+        fs::write(root.join("outside.txt"), "SECRET").unwrap();
+        TestDir(root)
+    }
+
+    fn config(&self) -> Config {
+        let mut config = Config {
+            cwd: self.0.join("www").to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        config.webdav.enabled = true;
+        config
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+async fn get(config: &Config, uri: &str) -> (StatusCode, String) {
+    let app = test::init_service(build_app(config, &AppState::new(config))).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (status, String::from_utf8_lossy(&body).to_string())
+}
+
+async fn propfind(config: &Config, auth: Option<&str>) -> StatusCode {
+    let app = test::init_service(build_app(config, &AppState::new(config))).await;
+    let mut req = test::TestRequest::default()
+        .method(Method::from_bytes(b"PROPFIND").unwrap())
+        .uri("/webdav/")
+        .insert_header(("Depth", "1"));
+    if let Some(credentials) = auth {
+        req = req.insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode(credentials))));
+    }
+    test::call_service(&app, req.to_request()).await.status()
+}
+
+#[actix_web::test]
+async fn lists_directory() {
+    let dir = TestDir::new();
+    let (status, body) = get(&dir.config(), "/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("hello.txt"));
+    assert!(body.contains("docs"));
+}
+
+#[actix_web::test]
+async fn downloads_file() {
+    let dir = TestDir::new();
+    let (status, body) = get(&dir.config(), "/hello.txt").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "hello");
+}
+
+#[actix_web::test]
+async fn head_returns_headers_without_body() {
+    let dir = TestDir::new();
+    let config = dir.config();
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+
+    let res = test::call_service(&app, test::TestRequest::default().method(Method::HEAD).uri("/hello.txt").to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(actix_web::body::MessageBody::size(res.response().body()), actix_web::body::BodySize::Sized(5));
+    assert!(res.headers().contains_key(header::ETAG));
+    assert!(res.headers().contains_key(header::LAST_MODIFIED));
+
+    let res = test::call_service(&app, test::TestRequest::default().method(Method::HEAD).uri("/docs/").to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get(header::CONTENT_TYPE).unwrap().to_str().unwrap().starts_with("text/html"));
+}
+
+#[actix_web::test]
+async fn supports_download_manager_range_requests() {
+    let dir = TestDir::new();
+    let config = dir.config();
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let auth = format!("Basic {}", BASE64.encode("admin:admin"));
+
+    for uri in ["/hello.txt", "/webdav/hello.txt"] {
+        // 先用 HEAD 获取长度与 ETag，再分段续传
+        let res = test::call_service(&app, test::TestRequest::default()
+            .method(Method::HEAD).uri(uri)
+            .insert_header((header::AUTHORIZATION, auth.as_str()))
+            .to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK, "{}", uri);
+        assert_eq!(res.headers().get(header::ACCEPT_RANGES).unwrap(), "bytes", "{}", uri);
+        let etag = res.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string();
+
+        let ranged = |range: &str, if_range: &str| test::TestRequest::get().uri(uri)
+            .insert_header((header::AUTHORIZATION, auth.as_str()))
+            .insert_header((header::RANGE, range))
+            .insert_header((header::IF_RANGE, if_range))
+            .to_request();
+        let res = test::call_service(&app, ranged("bytes=0-1", &etag)).await;
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT, "{}", uri);
+        assert_eq!(res.headers().get(header::CONTENT_RANGE).unwrap(), "bytes 0-1/5", "{}", uri);
+        assert_eq!(test::read_body(res).await, "he");
+        let res = test::call_service(&app, ranged("bytes=2-", &etag)).await;
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT, "{}", uri);
+        assert_eq!(test::read_body(res).await, "llo");
+
+        // 文件已变化（ETag 不匹配）时返回完整内容
+        let res = test::call_service(&app, ranged("bytes=2-", "\"stale\"")).await;
+        assert_eq!(res.status(), StatusCode::OK, "{}", uri);
+        assert_eq!(test::read_body(res).await, "hello");
+    }
+}
+
+#[cfg(unix)]
+#[actix_web::test]
+async fn unreadable_files_are_forbidden_not_missing() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = TestDir::new();
+    let config = dir.config();
+    fs::write(dir.0.join("www/secret.txt"), "secret").unwrap();
+    fs::create_dir(dir.0.join("www/locked")).unwrap();
+    for name in ["www/secret.txt", "www/locked"] {
+        fs::set_permissions(dir.0.join(name), fs::Permissions::from_mode(0o000)).unwrap();
+    }
+
+    let (status, body) = get(&config, "/missing.txt").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body.contains("404 Not Found"));
+
+    // 以 root 运行时权限位不生效，无法构造不可读的文件
+    if fs::read(dir.0.join("www/secret.txt")).is_err() {
+        for uri in ["/secret.txt", "/locked/"] {
+            let (status, body) = get(&config, uri).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+            assert!(body.contains("403 Forbidden"), "{}", uri);
+        }
+    }
+    fs::set_permissions(dir.0.join("www/locked"), fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[actix_web::test]
+async fn broken_template_renders_friendly_error_page() {
+    let mut tera = tera::Tera::default();
+    tera.add_raw_template("broken.html", "{{ missing_variable.field }}").unwrap();
+    let error = tera.render("broken.html", &tera::Context::new()).unwrap_err();
+    let req = test::TestRequest::get().uri("/").to_http_request();
+    let mut config = Config::default();
+
+    let res = template_error_response(&config, &req, &error);
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "text/html; charset=utf-8");
+    let body = String::from_utf8(actix_web::body::to_bytes(res.into_body()).await.unwrap().to_vec()).unwrap();
+    assert!(body.contains("class=\"error-box\"") && body.contains("<h2>500 "), "{}", body);
+    assert!(!body.contains("missing_variable"));
+
+    // 开启 debug 后页面中显示具体原因
+    config.debug = true;
+    let res = template_error_response(&config, &req, &error);
+    let body = String::from_utf8(actix_web::body::to_bytes(res.into_body()).await.unwrap().to_vec()).unwrap();
+    assert!(body.contains("<pre>") && body.contains("missing_variable"), "{}", body);
+}
+
+// 程序数据目录（data/）相对于进程工作目录解析，用到它的测试依次运行，
+// 结束后只删除测试自己创建的内容
+static DATA_DIR_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+struct DataDir {
+    path: PathBuf,
+    created: Vec<PathBuf>,
+    _lock: tokio::sync::MutexGuard<'static, ()>,
+}
+
+impl DataDir {
+    async fn new() -> Self {
+        let lock = DATA_DIR_LOCK.lock().await;
+        let mut dir = DataDir { path: env::current_dir().unwrap().join("data"), created: Vec::new(), _lock: lock };
+        if !dir.path.exists() {
+            fs::create_dir(&dir.path).unwrap();
+            dir.created.push(dir.path.clone());
+        }
+        dir
+    }
+
+    // 文件不存在时写入，测试结束后删除
+    fn create(&mut self, name: &str, content: &str) {
+        let path = self.path.join(name);
+        if !path.exists() {
+            fs::write(&path, content).unwrap();
+            self.created.push(path);
+        }
+    }
+
+    // 登记测试过程中可能生成的路径
+    fn track(&mut self, name: &str) {
+        let path = self.path.join(name);
+        if !path.exists() {
+            self.created.push(path);
+        }
+    }
+}
+
+impl Drop for DataDir {
+    fn drop(&mut self) {
+        for path in self.created.iter().rev() {
+            let _ = fs::remove_file(path).or_else(|_| fs::remove_dir(path));
+        }
+    }
+}
+
+#[actix_web::test]
+async fn serves_favicon_override_with_validators() {
+    let mut data = DataDir::new().await;
+    // 不替换开发环境中已有的图标
+    if data.path.join("favicon.ico").exists() {
+        return;
+    }
+    let config = TestDir::new().config();
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/favicon.ico").to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "image/x-icon");
+    let embedded = test::read_body(res).await;
+    assert!(!embedded.is_empty());
+
+    data.create("favicon.ico", "custom-icon");
+    let res = test::call_service(&app, test::TestRequest::get().uri("/favicon.ico").to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "image/x-icon");
+    assert_eq!(res.headers().get(header::CACHE_CONTROL).unwrap(), "public, max-age=86400");
+    assert!(res.headers().contains_key(header::LAST_MODIFIED));
+    let etag = res.headers().get(header::ETAG).unwrap().clone();
+    assert_eq!(test::read_body(res).await, "custom-icon");
+
+    let req = test::TestRequest::get().uri("/favicon.ico").insert_header((header::IF_NONE_MATCH, etag)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[actix_web::test]
+async fn never_serves_own_data_files() {
+    let mut data = DataDir::new().await;
+    data.create("config.yaml", "webdav:\n  users: {}\n");
+
+    // cwd 为项目根目录时整个数据目录都不可见
+    let mut config = Config { cwd: env::current_dir().unwrap().to_string_lossy().to_string(), ..Config::default() };
+    for uri in ["/data/config.yaml", "/data/"] {
+        let (status, _) = get(&config, uri).await;
+        assert!(matches!(status, StatusCode::NOT_FOUND | StatusCode::FORBIDDEN), "{} {}", uri, status);
+    }
+    let (_, body) = get(&config, "/").await;
+    assert!(!body.contains("data-name=\"data\""));
+
+    // cwd 就是数据目录时配置文件仍不可访问
+    config.cwd = data.path.to_string_lossy().to_string();
+    let (status, _) = get(&config, "/config.yaml").await;
+    assert!(matches!(status, StatusCode::NOT_FOUND | StatusCode::FORBIDDEN), "{}", status);
+    let (_, body) = get(&config, "/").await;
+    assert!(!body.contains("config.yaml"));
+}
+
+#[actix_web::test]
+async fn webdav_copy_and_move_cannot_target_data_files() {
+    let mut data = DataDir::new().await;
+    data.create("config.yaml", "webdav:\n  users: {}\n");
+    data.create("webdisk-test-source.yaml", "evil");
+    data.track("webdisk-test-moved.yaml");
+    for name in ["blobs", "cache"] {
+        data.track(name);
+        data.track(&format!("{}/x", name));
+        let _ = fs::create_dir(data.path.join(name));
+    }
+    let original = fs::read_to_string(data.path.join("config.yaml")).unwrap();
+
+    let mut config = Config { cwd: data.path.to_string_lossy().to_string(), ..Config::default() };
+    config.webdav.enabled = true;
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let request = |method: &str, destination: &str| test::TestRequest::default()
+        .method(Method::from_bytes(method.as_bytes()).unwrap())
+        .uri("/webdav/webdisk-test-source.yaml")
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .insert_header(("Destination", destination))
+        .insert_header(("Overwrite", "T"))
+        .to_request();
+
+    for (method, destination) in [
+        ("MOVE", "/webdav/config.yaml"),
+        ("MOVE", "http://localhost/webdav/./config.yaml"),
+        ("COPY", "/webdav/blobs/x"),
+        ("COPY", "/webdav/cache/x"),
+    ] {
+        let status = test::call_service(&app, request(method, destination)).await.status();
+        assert_eq!(status, StatusCode::FORBIDDEN, "{} {}", method, destination);
+    }
+    assert_eq!(fs::read_to_string(data.path.join("config.yaml")).unwrap(), original);
+    assert!(!data.path.join("blobs/x").exists() && !data.path.join("cache/x").exists());
+
+    // 越出挂载路径或缺少目标的请求直接拒绝
+    for destination in ["/webdav/../config.yaml", "/other/config.yaml", "/webdavconfig.yaml"] {
+        let status = test::call_service(&app, request("MOVE", destination)).await.status();
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", destination);
+    }
+
+    // 普通文件之间的移动不受影响
+    let status = test::call_service(&app, request("MOVE", "/webdav/webdisk-test-moved.yaml")).await.status();
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(fs::read_to_string(data.path.join("webdisk-test-moved.yaml")).unwrap(), "evil");
+}
+
+#[actix_web::test]
+async fn missing_file_is_not_found() {
+    let dir = TestDir::new();
+    let (status, _) = get(&dir.config(), "/missing.txt").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn rejects_path_traversal() {
+    let dir = TestDir::new();
+    let config = dir.config();
+    let outside = dir.0.join("outside.txt").to_string_lossy().to_string();
+    let absolute = format!("/{}", utf8_percent_encode(&outside, NON_ALPHANUMERIC));
+    let double_slash = format!("/{}", outside);
+    for uri in ["/../outside.txt", "/%2e%2e/outside.txt", "/..%2Foutside.txt", "/docs/../../outside.txt"] {
+        let (status, body) = get(&config, uri).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+        assert!(!body.contains("SECRET"), "{}", uri);
+    }
+    // 绝对路径被规范化为 cwd 下的同名路径，跟随重定向后仍然不存在
+    for uri in [&absolute, &double_slash] {
+        let location = redirect(&config, uri).await.unwrap();
+        assert!(location.starts_with('/') && !location.starts_with("//"), "{}", location);
+        let (status, body) = get(&config, &location).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+        assert!(!body.contains("SECRET"), "{}", uri);
+    }
+}
+
+// 返回 301 重定向的目标地址；不是 301 时返回 None
+async fn redirect(config: &Config, uri: &str) -> Option<String> {
+    let app = test::init_service(build_app(config, &AppState::new(config))).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    if res.status() != StatusCode::MOVED_PERMANENTLY {
+        return None;
+    }
+    res.headers().get(header::LOCATION).map(|v| v.to_str().unwrap().to_string())
+}
+
+#[actix_web::test]
+async fn collapses_duplicate_slashes() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/docs/b.txt"), "b").unwrap();
+    assert_eq!(redirect(&dir.config(), "/docs//b.txt").await.as_deref(), Some("/docs/b.txt"));
+    assert_eq!(redirect(&dir.config(), "//docs///").await.as_deref(), Some("/docs/"));
+}
+
+#[actix_web::test]
+async fn resolves_dot_segments() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/docs/b.txt"), "b").unwrap();
+    assert_eq!(redirect(&dir.config(), "/docs/./b.txt").await.as_deref(), Some("/docs/b.txt"));
+    assert_eq!(redirect(&dir.config(), "/docs/../hello.txt?dl=1").await.as_deref(), Some("/hello.txt?dl=1"));
+    assert_eq!(redirect(&dir.config(), "/docs/.").await.as_deref(), Some("/docs/"));
+}
+
+#[actix_web::test]
+async fn redirects_directory_without_trailing_slash() {
+    let dir = TestDir::new();
+    fs::create_dir(dir.0.join("www/docs/my dir")).unwrap();
+    let config = dir.config();
+    assert_eq!(redirect(&config, "/docs").await.as_deref(), Some("/docs/"));
+    assert_eq!(redirect(&config, "/docs/my%20dir?sort=size").await.as_deref(), Some("/docs/my%20dir/?sort=size"));
+    assert_eq!(redirect(&config, "/docs/").await, None);
+    assert_eq!(redirect(&config, "/hello.txt").await, None);
+
+    let config = Config { normalize_paths: false, ..dir.config() };
+    assert_eq!(get(&config, "/docs").await.0, StatusCode::OK);
+    assert_eq!(get(&config, "/docs//").await.0, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn webdav_requires_valid_credentials() {
+    let dir = TestDir::new();
+    let config = dir.config();
+    assert_eq!(propfind(&config, Some("admin:admin")).await, StatusCode::MULTI_STATUS);
+    assert_eq!(propfind(&config, Some("admin:wrong")).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(propfind(&config, Some("nobody:admin")).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(propfind(&config, None).await, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn webdav_accepts_hashed_passwords() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    let hashed = hash_password("secret");
+    assert!(hashed.starts_with("$argon2id$"));
+    config.webdav.users.get_mut("admin").unwrap().password = hashed.clone();
+    assert!(config.webdav.users["admin"].password_is_hashed());
+    assert_eq!(propfind(&config, Some("admin:secret")).await, StatusCode::MULTI_STATUS);
+    // 重复验证走缓存，结果不变
+    assert_eq!(propfind(&config, Some("admin:secret")).await, StatusCode::MULTI_STATUS);
+    assert_eq!(propfind(&config, Some("admin:wrong")).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(propfind(&config, Some("nobody:secret")).await, StatusCode::UNAUTHORIZED);
+    // 哈希字符串本身不能当作密码使用
+    assert_eq!(propfind(&config, Some(&format!("admin:{}", hashed))).await, StatusCode::UNAUTHORIZED);
+}
+
+async fn get_with_cookie(config: &Config, uri: &str, cookie: &str) -> (Vec<String>, String) {
+    let app = test::init_service(build_app(config, &AppState::new(config))).await;
+    let req = test::TestRequest::get().uri(uri).insert_header((header::COOKIE, cookie)).to_request();
+    let res = test::call_service(&app, req).await;
+    let set_cookies = res.headers().get_all(header::SET_COOKIE)
+        .map(|v| v.to_str().unwrap().to_string())
+        .collect();
+    let body = test::read_body(res).await;
+    (set_cookies, String::from_utf8_lossy(&body).to_string())
+}
+
+// 按名称升序时 hello.txt 在前，按大小降序时 zbig.txt 在前
+fn size_desc(body: &str) -> bool {
+    body.find("zbig.txt").unwrap() < body.find("hello.txt").unwrap()
+}
+
+#[actix_web::test]
+async fn default_config_file_stores_hashed_password() {
+    let mut data = DataDir::new().await;
+    // 不覆盖开发环境中已有的配置文件
+    if data.path.join("config.yaml").exists() {
+        return;
+    }
+    data.track("config.yaml");
+    Config::create_default_config().unwrap();
+
+    let written = fs::read_to_string(data.path.join("config.yaml")).unwrap();
+    assert!(written.contains("$argon2id$"), "{}", written);
+    assert!(!written.contains("password: admin"), "{}", written);
+
+    // 生成的默认账号仍是 admin / admin
+    let dir = TestDir::new();
+    let mut config = Config::from_yaml(&written, false).unwrap();
+    config.cwd = dir.config().cwd;
+    config.webdav.enabled = true;
+    assert_eq!(propfind(&config, Some("admin:admin")).await, StatusCode::MULTI_STATUS);
+    assert_eq!(propfind(&config, Some("admin:wrong")).await, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn sort_choice_persists_in_cookie() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/zbig.txt"), "x".repeat(4096)).unwrap();
+    let config = dir.config();
+
+    let (set_cookies, body) = get_with_cookie(&config, "/?sort=size&order=desc", "").await;
+    assert!(size_desc(&body));
+    assert!(set_cookies.iter().any(|c| c.starts_with("webdisk_sort=size;")));
+    assert!(set_cookies.iter().any(|c| c.starts_with("webdisk_order=desc;")));
+
+    // 没有查询参数时使用 Cookie，查询参数优先于 Cookie
+    let (set_cookies, body) = get_with_cookie(&config, "/", "webdisk_sort=size; webdisk_order=desc").await;
+    assert!(size_desc(&body));
+    assert!(set_cookies.is_empty());
+    let (_, body) = get_with_cookie(&config, "/?sort=name&order=asc", "webdisk_sort=size; webdisk_order=desc").await;
+    assert!(!size_desc(&body));
+
+    // 无效的取值不会写入 Cookie
+    let (set_cookies, body) = get_with_cookie(&config, "/?sort=evil%3B%20Domain%3Dx", "").await;
+    assert!(set_cookies.is_empty());
+    assert!(!size_desc(&body));
+}
+
+const SCRIPTED_SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" onload="alert(1)"><script>alert(document.cookie)</script></svg>"#;
+
+async fn get_headers(config: &Config, uri: &str) -> actix_web::http::header::HeaderMap {
+    let app = test::init_service(build_app(config, &AppState::new(config))).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    res.headers().clone()
+}
+
+#[actix_web::test]
+async fn untrusted_svg_is_not_rendered_inline() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/evil.svg"), SCRIPTED_SVG).unwrap();
+    let mut config = dir.config();
+
+    let headers = get_headers(&config, "/evil.svg").await;
+    let disposition = headers.get(header::CONTENT_DISPOSITION).unwrap().to_str().unwrap();
+    assert!(disposition.starts_with("attachment"), "{}", disposition);
+    let csp = headers.get(header::CONTENT_SECURITY_POLICY).unwrap().to_str().unwrap();
+    assert!(csp.contains("sandbox") && csp.contains("default-src 'none'"), "{}", csp);
+
+    config.trust_svg = true;
+    let headers = get_headers(&config, "/evil.svg").await;
+    assert!(headers.get(header::CONTENT_DISPOSITION).unwrap().to_str().unwrap().starts_with("inline"));
+    assert!(headers.get(header::CONTENT_SECURITY_POLICY).is_none());
+}
+
+#[actix_web::test]
+async fn caches_files_privately_when_authenticated() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/app.3f9a2c1b.js"), "js").unwrap();
+    let mut config = dir.config();
+    assert!(get_headers(&config, "/hello.txt").await.get(header::CACHE_CONTROL).is_none());
+
+    config.cache.max_age_secs = 60;
+    config.cache.immutable_hashed = true;
+    assert_eq!(get_headers(&config, "/hello.txt").await.get(header::CACHE_CONTROL).unwrap(), "public, max-age=60");
+    assert_eq!(get_headers(&config, "/app.3f9a2c1b.js").await.get(header::CACHE_CONTROL).unwrap(), "public, max-age=60, immutable");
+
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let req = test::TestRequest::get()
+        .uri("/hello.txt")
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.headers().get(header::CACHE_CONTROL).unwrap(), "private, max-age=60");
+}
+
+// 以指定用户发送 WebDAV 请求，返回状态码
+async fn dav(config: &Config, method: &str, uri: &str, credentials: &str, body: &str) -> StatusCode {
+    dav_request(config, method, uri, Some(credentials), "1", body).await
+}
+
+// 可指定 Depth 与是否匿名的 WebDAV 请求
+async fn dav_request(config: &Config, method: &str, uri: &str, credentials: Option<&str>, depth: &str, body: &str) -> StatusCode {
+    dav_response(config, method, uri, credentials, depth, body).await.0
+}
+
+// 同 dav_request，但一并返回响应体
+async fn dav_response(config: &Config, method: &str, uri: &str, credentials: Option<&str>, depth: &str, body: &str) -> (StatusCode, String) {
+    let app = test::init_service(build_app(config, &AppState::new(config))).await;
+    let mut req = test::TestRequest::default()
+        .method(Method::from_bytes(method.as_bytes()).unwrap())
+        .uri(uri)
+        .insert_header(("Depth", depth))
+        .set_payload(body.to_string());
+    if let Some(credentials) = credentials {
+        req = req.insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode(credentials))));
+    }
+    let res = test::call_service(&app, req.to_request()).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (status, String::from_utf8_lossy(&body).to_string())
+}
+
+#[actix_web::test]
+async fn propfind_honours_depth_and_requested_properties() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/docs/inner.txt"), "inner").unwrap();
+    let config = dir.config();
+    let allprop = r#"<?xml version="1.0"?><D:propfind xmlns:D="DAV:"><D:allprop/></D:propfind>"#;
+
+    // Depth: 0 只返回目录本身
+    let (status, body) = dav_response(&config, "PROPFIND", "/webdav/", Some("admin:admin"), "0", allprop).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert!(body.contains("<D:multistatus xmlns:D=\"DAV:\">"), "{}", body);
+    assert_eq!(body.matches("<D:response>").count(), 1, "{}", body);
+    assert!(body.contains("<D:href>/webdav/</D:href>"));
+    assert!(body.contains("<D:resourcetype><D:collection></D:collection></D:resourcetype>"));
+
+    // Depth: 1 包含直接子项，但不进入子目录
+    let (status, body) = dav_response(&config, "PROPFIND", "/webdav/", Some("admin:admin"), "1", allprop).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert_eq!(body.matches("<D:response>").count(), 3, "{}", body);
+    assert!(body.contains("<D:href>/webdav/hello.txt</D:href>"));
+    assert!(body.contains("<D:href>/webdav/docs/</D:href>"));
+    assert!(body.contains("<D:getcontentlength>5</D:getcontentlength>"));
+    assert!(!body.contains("inner.txt"));
+
+    // Depth: infinity 按 RFC 4918 拒绝
+    let (status, body) = dav_response(&config, "PROPFIND", "/webdav/", Some("admin:admin"), "infinity", allprop).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body.contains("propfind-finite-depth"), "{}", body);
+
+    // 指定属性时只返回请求的属性
+    let named = r#"<?xml version="1.0"?><D:propfind xmlns:D="DAV:"><D:prop><D:getcontentlength/></D:prop></D:propfind>"#;
+    let (status, body) = dav_response(&config, "PROPFIND", "/webdav/hello.txt", Some("admin:admin"), "0", named).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert!(body.contains("<D:getcontentlength>5</D:getcontentlength>"), "{}", body);
+    assert!(!body.contains("getlastmodified"));
+    assert!(!body.contains("getetag"));
+}
+
+#[actix_web::test]
+async fn webdav_preserves_encoded_paths_and_query_strings() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    config.webdav.mount_path = "/dav/files/".to_string();
+    let uri = "/dav/files/docs/a%20b%23c%3F%E4%B8%AD.txt";
+
+    let status = dav(&config, "PUT", &format!("{}?x=1", uri), "admin:admin", "exotic").await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(fs::read_to_string(dir.0.join("www/docs/a b#c?中.txt")).unwrap(), "exotic");
+
+    let (status, body) = dav_response(&config, "GET", &format!("{}?x=1&y=%20", uri), Some("admin:admin"), "0", "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "exotic");
+
+    let (status, body) = dav_response(&config, "PROPFIND", "/dav/files/docs/", Some("admin:admin"), "1", "").await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert!(body.contains("<D:href>/dav/files/docs/a%20b%23c%3F%E4%B8%AD.txt</D:href>"), "{}", body);
+
+    // 冲突重命名时重建的 URI 仍保留编码与查询串
+    config.upload.on_conflict = ConflictPolicy::Rename;
+    let status = dav(&config, "PUT", &format!("{}?x=1", uri), "admin:admin", "again").await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(fs::read_to_string(dir.0.join("www/docs/a b#c?中 (1).txt")).unwrap(), "again");
+    assert_eq!(fs::read_to_string(dir.0.join("www/docs/a b#c?中.txt")).unwrap(), "exotic");
+
+    // 旧的默认挂载路径不再交给 WebDAV 处理
+    assert_ne!(dav(&config, "PROPFIND", "/webdav/", "admin:admin", "").await, StatusCode::MULTI_STATUS);
+}
+
+#[actix_web::test]
+async fn webdav_forwards_proppatch_and_lock_bodies() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    config.webdav.locking = true;
+    let proppatch = r#"<?xml version="1.0"?><D:propertyupdate xmlns:D="DAV:" xmlns:Z="urn:webdisk-test"><D:set><D:prop><Z:author>alice</Z:author></D:prop></D:set></D:propertyupdate>"#;
+    // 请求体被解析后才会走到属性存储（本地文件系统不支持自定义属性，返回 501）；
+    // 请求体丢失时则是 XML 解析失败的 400
+    let (status, _) = dav_response(&config, "PROPPATCH", "/webdav/hello.txt", Some("admin:admin"), "0", proppatch).await;
+    assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    let (status, _) = dav_response(&config, "PROPPATCH", "/webdav/hello.txt", Some("admin:admin"), "0", "").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // LOCK 请求体中的 owner 会原样出现在锁信息中
+    let lock = r#"<?xml version="1.0"?><D:lockinfo xmlns:D="DAV:"><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype><D:owner><D:href>mailto:alice@example.com</D:href></D:owner></D:lockinfo>"#;
+    let (status, body) = dav_response(&config, "LOCK", "/webdav/docs/x.txt", Some("admin:admin"), "0", lock).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(body.contains("<D:exclusive />"), "{}", body);
+    assert!(body.contains("<D:owner><D:href>mailto:alice@example.com</D:href></D:owner>"), "{}", body);
+}
+
+#[actix_web::test]
+async fn webdav_put_sanitizes_names_and_applies_conflict_policy() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+
+    // 文件名中的路径分隔符、控制字符与开头的点都会被去掉
+    // 编码后的 ../ 在解码后就会被拒绝；反斜杠形式则只保留最后一段
+    assert_eq!(dav(&config, "PUT", "/webdav/docs/..%2F..%2Fescape.txt", "admin:admin", "a").await, StatusCode::BAD_REQUEST);
+    assert_eq!(dav(&config, "PUT", "/webdav/docs/..%5C..%5Cescape.txt", "admin:admin", "a").await, StatusCode::CREATED);
+    assert_eq!(fs::read_to_string(dir.0.join("www/docs/escape.txt")).unwrap(), "a");
+    assert!(!dir.0.join("escape.txt").exists());
+    assert_eq!(dav(&config, "PUT", "/webdav/docs/nul%00l.txt", "admin:admin", "b").await, StatusCode::CREATED);
+    assert_eq!(fs::read_to_string(dir.0.join("www/docs/null.txt")).unwrap(), "b");
+    assert_eq!(dav(&config, "PUT", "/webdav/docs/.hidden", "admin:admin", "c").await, StatusCode::CREATED);
+    assert_eq!(fs::read_to_string(dir.0.join("www/docs/hidden")).unwrap(), "c");
+
+    config.upload.on_conflict = ConflictPolicy::Error;
+    assert_eq!(dav(&config, "PUT", "/webdav/hello.txt", "admin:admin", "new").await, StatusCode::CONFLICT);
+    assert_eq!(fs::read_to_string(dir.0.join("www/hello.txt")).unwrap(), "hello");
+
+    config.upload.on_conflict = ConflictPolicy::Rename;
+    assert_eq!(dav(&config, "PUT", "/webdav/hello.txt", "admin:admin", "one").await, StatusCode::CREATED);
+    assert_eq!(dav(&config, "PUT", "/webdav/hello.txt", "admin:admin", "two").await, StatusCode::CREATED);
+    assert_eq!(fs::read_to_string(dir.0.join("www/hello.txt")).unwrap(), "hello");
+    assert_eq!(fs::read_to_string(dir.0.join("www/hello (1).txt")).unwrap(), "one");
+    assert_eq!(fs::read_to_string(dir.0.join("www/hello (2).txt")).unwrap(), "two");
+
+    config.upload.on_conflict = ConflictPolicy::Overwrite;
+    assert!(dav(&config, "PUT", "/webdav/hello.txt", "admin:admin", "replaced").await.is_success());
+    assert_eq!(fs::read_to_string(dir.0.join("www/hello.txt")).unwrap(), "replaced");
+}
+
+#[actix_web::test]
+async fn options_advertises_webdav_capabilities() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    let options = |uri: &str| test::TestRequest::default().method(Method::OPTIONS).uri(uri).to_request();
+
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    for uri in ["/webdav", "/webdav/", "/webdav/docs/"] {
+        let res = test::call_service(&app, options(uri)).await;
+        assert_eq!(res.status(), StatusCode::OK, "{}", uri);
+        assert_eq!(res.headers().get("DAV").unwrap(), "1", "{}", uri);
+        assert_eq!(res.headers().get("MS-Author-Via").unwrap(), "DAV");
+        let allow = res.headers().get(header::ALLOW).unwrap().to_str().unwrap();
+        assert!(allow.contains("PROPFIND") && allow.contains("PUT"), "{}", allow);
+    }
+    // 网页路径不声明 WebDAV 能力
+    let res = test::call_service(&app, options("/docs/")).await;
+    assert!(res.headers().get("DAV").is_none());
+    assert_eq!(res.headers().get(header::ALLOW).unwrap(), "OPTIONS, GET, HEAD");
+
+    config.webdav.locking = true;
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let res = test::call_service(&app, options("/webdav/")).await;
+    assert_eq!(res.headers().get("DAV").unwrap(), "1, 2");
+}
+
+#[actix_web::test]
+async fn creates_user_root_on_first_access() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    config.webdav.users.insert("alice".to_string(), UserConfig {
+        password: "secret".to_string(),
+        permissions: "rw".to_string(),
+        root: Some("homes/alice".to_string()),
+        landing_path: None,
+    });
+    let home = dir.0.join("www/homes/alice");
+    assert!(!home.exists());
+
+    assert_eq!(dav(&config, "PROPFIND", "/webdav/", "alice:secret", "").await, StatusCode::MULTI_STATUS);
+    assert!(home.is_dir());
+    assert_eq!(dav(&config, "PUT", "/webdav/note.txt", "alice:secret", "hi").await, StatusCode::CREATED);
+    assert_eq!(fs::read_to_string(home.join("note.txt")).unwrap(), "hi");
+}
+
+#[actix_web::test]
+async fn does_not_create_missing_shared_root() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    config.webdav.root = Some(dir.0.join("missing").to_string_lossy().to_string());
+    dav(&config, "PROPFIND", "/webdav/", "admin:admin", "").await;
+    assert!(!dir.0.join("missing").exists());
+}
+
+// 发送前半段内容后连接中断的 PUT 请求
+async fn interrupted_put(config: &Config, uri: &str) -> StatusCode {
+    let app = test::init_service(build_app(config, &AppState::new(config))).await;
+    let chunks: Vec<Result<web::Bytes, PayloadError>> = vec![
+        Ok(web::Bytes::from_static(b"partial")),
+        Err(PayloadError::Incomplete(None)),
+    ];
+    let (req, _) = test::TestRequest::put()
+        .uri(uri)
+        .insert_header((header::CONTENT_LENGTH, "100"))
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .to_request()
+        .replace_payload(Payload::from(Box::pin(stream::iter(chunks)) as Pin<Box<dyn Stream<Item = _>>>));
+    test::call_service(&app, req).await.status()
+}
+
+fn upload_leftovers(dir: &TestDir) -> Vec<String> {
+    fs::read_dir(dir.0.join("www")).unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|name| name.contains("upload"))
+        .collect()
+}
+
+#[actix_web::test]
+async fn interrupted_put_leaves_no_partial_file() {
+    let dir = TestDir::new();
+    let config = dir.config();
+
+    assert!(!interrupted_put(&config, "/webdav/new.txt").await.is_success());
+    assert!(!dir.0.join("www/new.txt").exists());
+
+    assert!(!interrupted_put(&config, "/webdav/hello.txt").await.is_success());
+    assert_eq!(fs::read_to_string(dir.0.join("www/hello.txt")).unwrap(), "hello");
+    assert!(upload_leftovers(&dir).is_empty(), "{:?}", upload_leftovers(&dir));
+
+    assert_eq!(dav(&config, "PUT", "/webdav/hello.txt", "admin:admin", "replaced").await, StatusCode::NO_CONTENT);
+    assert_eq!(fs::read_to_string(dir.0.join("www/hello.txt")).unwrap(), "replaced");
+    assert!(upload_leftovers(&dir).is_empty());
+}
+
+#[actix_web::test]
+async fn partial_put_keeps_shared_content_intact() {
+    let dir = TestDir::new();
+    let config = dir.config();
+    // 去重存储后两个路径共享同一份内容
+    fs::hard_link(dir.0.join("www/hello.txt"), dir.0.join("www/copy.txt")).unwrap();
+
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let req = test::TestRequest::put()
+        .uri("/webdav/hello.txt")
+        .insert_header((header::CONTENT_RANGE, "bytes 1-2/5"))
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .set_payload("EL")
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    assert_eq!(fs::read_to_string(dir.0.join("www/hello.txt")).unwrap(), "hELlo");
+    assert_eq!(fs::read_to_string(dir.0.join("www/copy.txt")).unwrap(), "hello");
+    assert!(upload_leftovers(&dir).is_empty());
+}
+
+#[actix_web::test]
+async fn pwa_manifest_is_opt_in() {
+    let dir = TestDir::new();
+    let config = dir.config();
+    assert_eq!(get(&config, "/manifest.json").await.0, StatusCode::NOT_FOUND);
+    assert!(!get(&config, "/").await.1.contains("rel=\"manifest\""));
+
+    let mut config = dir.config();
+    config.pwa.enabled = true;
+    config.pwa.short_name = Some("盘".to_string());
+    let (status, body) = get(&config, "/manifest.json").await;
+    assert_eq!(status, StatusCode::OK);
+    let manifest: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(manifest["name"], config.branding.site_title.as_str());
+    assert_eq!(manifest["short_name"], "盘");
+    assert_eq!(manifest["icons"][0]["src"], "/favicon.ico");
+    assert!(get(&config, "/").await.1.contains("rel=\"manifest\""));
+    assert!(get(&config, "/sw.js").await.1.contains("addEventListener('fetch'"));
+}
+
+#[actix_web::test]
+async fn offloads_file_to_proxy() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/docs/a b.txt"), "content").unwrap();
+
+    let mut config = dir.config();
+    config.proxy.accel_mode = AccelMode::Nginx;
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri("/docs/a%20b.txt").to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("X-Accel-Redirect").unwrap(), "/internal/docs/a%20b.txt");
+    assert!(res.headers().get(header::CONTENT_TYPE).unwrap().to_str().unwrap().starts_with("text/plain"));
+    assert!(test::read_body(res).await.is_empty());
+
+    config.proxy.accel_mode = AccelMode::Apache;
+    let headers = get_headers(&config, "/docs/a%20b.txt").await;
+    let expected = dir.0.join("www/docs/a b.txt");
+    assert_eq!(headers.get("X-Sendfile").unwrap().to_str().unwrap(), expected.to_string_lossy());
+
+    // 路径检查仍由程序完成
+    assert_eq!(get(&config, "/../outside.txt").await.0, StatusCode::NOT_FOUND);
+}
+
+const CHINESE_LOG: &str = "2024-05-01 12:00:00 服务启动成功，正在监听端口。\n2024-05-01 12:00:05 用户登录，开始同步文件。\n";
+const TRADITIONAL_LOG: &str = "2024-05-01 12:00:00 伺服器啟動成功，正在監聽連接埠。\n2024-05-01 12:00:05 使用者登入，開始同步檔案。\n";
+
+async fn text_preview(config: &Config, uri: &str) -> (StatusCode, Option<String>, String) {
+    let app = test::init_service(build_app(config, &AppState::new(config))).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    let status = res.status();
+    let charset = res.headers().get("X-Source-Charset").map(|v| v.to_str().unwrap().to_string());
+    let body = test::read_body(res).await;
+    (status, charset, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[actix_web::test]
+async fn transcodes_legacy_text_preview() {
+    let dir = TestDir::new();
+    let (gbk, _, _) = encoding_rs::GBK.encode(CHINESE_LOG);
+    assert_eq!(&gbk[20..24], &[0xB7, 0xFE, 0xCE, 0xF1]);  // 「服务」
+    fs::write(dir.0.join("www/gbk.log"), &gbk).unwrap();
+    let (big5, _, _) = encoding_rs::BIG5.encode(TRADITIONAL_LOG);
+    fs::write(dir.0.join("www/big5.log"), &big5).unwrap();
+    let config = dir.config();
+
+    let (status, charset, body) = text_preview(&config, "/gbk.log?raw=1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(charset.as_deref(), Some("GBK"));
+    assert_eq!(body, CHINESE_LOG);
+
+    let (_, charset, body) = text_preview(&config, "/big5.log?raw=1").await;
+    assert_eq!(charset.as_deref(), Some("Big5"));
+    assert_eq!(body, TRADITIONAL_LOG);
+
+    let (_, charset, body) = text_preview(&config, "/gbk.log?head=1&charset=gb18030").await;
+    assert_eq!(charset.as_deref(), Some("gb18030"));
+    assert_eq!(body, CHINESE_LOG);
+
+    let (_, charset, body) = text_preview(&config, "/hello.txt?raw=1").await;
+    assert_eq!(charset.as_deref(), Some("UTF-8"));
+    assert_eq!(body, "hello");
+
+    assert_eq!(text_preview(&config, "/gbk.log?raw=1&charset=nope").await.0, StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn write_only_user_can_upload_but_not_list() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    config.webdav.users.insert("drop".to_string(), UserConfig {
+        password: "drop".to_string(),
+        permissions: "w".to_string(),
+        root: None,
+        landing_path: None,
+    });
+
+    assert_eq!(dav(&config, "PUT", "/webdav/report.txt", "drop:drop", "report").await, StatusCode::CREATED);
+    assert_eq!(fs::read_to_string(dir.0.join("www/report.txt")).unwrap(), "report");
+    assert_eq!(dav_request(&config, "PROPFIND", "/webdav/", Some("drop:drop"), "0", "").await, StatusCode::MULTI_STATUS);
+
+    assert_eq!(dav(&config, "PROPFIND", "/webdav/", "drop:drop", "").await, StatusCode::FORBIDDEN);
+    assert_eq!(dav(&config, "GET", "/webdav/hello.txt", "drop:drop", "").await, StatusCode::FORBIDDEN);
+    assert_eq!(dav(&config, "DELETE", "/webdav/hello.txt", "drop:drop", "").await, StatusCode::FORBIDDEN);
+    assert_eq!(dav_request(&config, "PROPFIND", "/webdav/hello.txt", Some("drop:drop"), "0", "").await, StatusCode::NOT_FOUND);
+
+    // 同名上传不会覆盖看不到的已有文件
+    assert_eq!(dav(&config, "PUT", "/webdav/hello.txt", "drop:drop", "other").await, StatusCode::CREATED);
+    assert_eq!(fs::read_to_string(dir.0.join("www/hello.txt")).unwrap(), "hello");
+    assert_eq!(fs::read_to_string(dir.0.join("www/hello (1).txt")).unwrap(), "other");
+}
+
+#[actix_web::test]
+async fn write_only_directory_is_a_drop_box() {
+    let dir = TestDir::new();
+    fs::create_dir(dir.0.join("www/inbox")).unwrap();
+    fs::write(dir.0.join("www/inbox/.webdisk.yaml"), "write_only: true\n").unwrap();
+    fs::write(dir.0.join("www/inbox/secret.txt"), "SECRET").unwrap();
+    let mut config = dir.config();
+    config.webdav.allow_anonymous = true;
+
+    let (status, body) = get(&config, "/inbox/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.contains("secret.txt"));
+    assert!(body.contains("/webdav/inbox/"));
+    assert_eq!(get(&config, "/inbox/secret.txt").await.0, StatusCode::FORBIDDEN);
+    assert_eq!(get(&config, "/checksum?path=inbox/secret.txt").await.0, StatusCode::FORBIDDEN);
+
+    assert_eq!(dav_request(&config, "PUT", "/webdav/inbox/new.txt", None, "1", "new").await, StatusCode::CREATED);
+    assert_eq!(fs::read_to_string(dir.0.join("www/inbox/new.txt")).unwrap(), "new");
+    assert_eq!(dav_request(&config, "GET", "/webdav/inbox/secret.txt", None, "1", "").await, StatusCode::FORBIDDEN);
+    assert_eq!(dav_request(&config, "PROPFIND", "/webdav/inbox/", None, "1", "").await, StatusCode::FORBIDDEN);
+    // 其他目录仍不允许匿名上传，登录用户不受影响
+    assert_eq!(dav_request(&config, "PUT", "/webdav/new.txt", None, "1", "new").await, StatusCode::UNAUTHORIZED);
+    assert_eq!(dav(&config, "GET", "/webdav/inbox/secret.txt", "admin:admin", "").await, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn drop_box_rejects_parent_segments() {
+    let dir = TestDir::new();
+    fs::create_dir(dir.0.join("www/inbox")).unwrap();
+    fs::write(dir.0.join("www/inbox/.webdisk.yaml"), "write_only: true\n").unwrap();
+    let mut config = dir.config();
+    config.webdav.allow_anonymous = true;
+
+    for uri in ["/webdav/inbox/../planted.txt", "/webdav/inbox/%2e%2e/planted.txt", "/webdav/inbox/..%2Fplanted.txt"] {
+        assert_eq!(dav_request(&config, "PUT", uri, None, "1", "x").await, StatusCode::BAD_REQUEST, "{}", uri);
+    }
+    assert_eq!(dav_request(&config, "MKCOL", "/webdav/inbox/../newdir", None, "1", "").await, StatusCode::BAD_REQUEST);
+    assert!(!dir.0.join("www/planted.txt").exists());
+    assert!(!dir.0.join("www/newdir").exists());
+}
+
+#[actix_web::test]
+async fn permissions_are_checked_per_method() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    for (name, permissions) in [("reader", "r"), ("writer", "w")] {
+        config.webdav.users.insert(name.to_string(), UserConfig {
+            password: name.to_string(),
+            permissions: permissions.to_string(),
+            root: None,
+            landing_path: None,
+        });
+    }
+
+    assert_eq!(dav(&config, "GET", "/webdav/hello.txt", "reader:reader", "").await, StatusCode::OK);
+    assert_eq!(dav(&config, "PROPFIND", "/webdav/", "reader:reader", "").await, StatusCode::MULTI_STATUS);
+    assert_eq!(dav(&config, "PUT", "/webdav/new.txt", "reader:reader", "new").await, StatusCode::FORBIDDEN);
+    assert_eq!(dav(&config, "MKCOL", "/webdav/newdir", "reader:reader", "").await, StatusCode::FORBIDDEN);
+    assert_eq!(dav(&config, "PROPPATCH", "/webdav/hello.txt", "reader:reader", "").await, StatusCode::FORBIDDEN);
+    assert!(!dir.0.join("www/new.txt").exists());
+
+    assert_eq!(dav(&config, "PUT", "/webdav/new.txt", "writer:writer", "new").await, StatusCode::CREATED);
+    assert_eq!(dav(&config, "MKCOL", "/webdav/newdir", "writer:writer", "").await, StatusCode::CREATED);
+    assert_eq!(dav(&config, "GET", "/webdav/new.txt", "writer:writer", "").await, StatusCode::FORBIDDEN);
+    assert_eq!(dav(&config, "HEAD", "/webdav/hello.txt", "writer:writer", "").await, StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn hides_temp_files_from_listing() {
+    let dir = TestDir::new();
+    for name in ["build.TMP", "~$report.docx", "notes.txt~", "archive.tmp.zip"] {
+        fs::write(dir.0.join("www").join(name), "data").unwrap();
+    }
+    fs::write(dir.0.join("www/empty.txt"), "").unwrap();
+    let mut config = dir.config();
+
+    let (_, body) = get(&config, "/").await;
+    for hidden in ["build.TMP", "~$report.docx", "notes.txt~"] {
+        assert!(!body.contains(hidden), "{}", hidden);
+    }
+    assert!(body.contains("empty.txt"));
+    assert!(body.contains("archive.tmp.zip"));
+    assert_eq!(get(&config, "/build.TMP").await.0, StatusCode::OK);
+
+    config.hide.empty_files = true;
+    config.hide.patterns.clear();
+    let (_, body) = get(&config, "/").await;
+    assert!(!body.contains("empty.txt"));
+    assert!(body.contains("notes.txt~"));
+    assert!(body.contains("hello.txt"));
+}
+
+#[actix_web::test]
+async fn streams_upload_progress_events() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri("/events?session=s1").to_request()).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    config.upload.progress_events = true;
+    let session = format!("test-{}", thread_rng().gen::<u64>());
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri(&format!("/events?session={}", session)).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "text/event-stream");
+
+    let put = test::TestRequest::put()
+        .uri("/webdav/upload.txt")
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .insert_header(("X-Upload-Session", session.as_str()))
+        .insert_header(("X-Upload-Id", "u1"))
+        .set_payload("hello")
+        .to_request();
+    assert_eq!(test::call_service(&app, put).await.status(), StatusCode::CREATED);
+
+    let mut body = std::pin::pin!(res.into_body());
+    let mut received = String::new();
+    while !received.contains("event: end") {
+        let chunk = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            std::future::poll_fn(|cx| actix_web::body::MessageBody::poll_next(body.as_mut(), cx)),
+        ).await.expect("progress event").unwrap().ok().unwrap();
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    assert!(received.contains(r#"event: progress
+data: {"id":"u1","received":0,"total":5}"#));
+    assert!(received.contains(r#"event: end
+data: {"id":"u1","received":5,"total":5,"status":201}"#));
+}
+
+#[actix_web::test]
+async fn streams_web_form_upload_progress() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    config.upload.web_form = true;
+    config.upload.progress_events = true;
+    let session = format!("test-{}", thread_rng().gen::<u64>());
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri(&format!("/events?session={}", session)).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let (content_type, body) = multipart_body(&[("file", Some("a.txt"), "hello")]);
+    let upload = test::TestRequest::post()
+        .uri("/upload/docs")
+        .insert_header((header::CONTENT_TYPE, content_type))
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .insert_header(("X-Upload-Session", session.as_str()))
+        .insert_header(("X-Upload-Id", "form"))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(test::call_service(&app, upload).await.status(), StatusCode::SEE_OTHER);
+
+    let mut body = std::pin::pin!(res.into_body());
+    let mut received = String::new();
+    while !received.contains("event: end") {
+        let chunk = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            std::future::poll_fn(|cx| actix_web::body::MessageBody::poll_next(body.as_mut(), cx)),
+        ).await.expect("progress event").unwrap().ok().unwrap();
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    assert!(received.contains(r#"data: {"id":"form","received":5,"#), "{}", received);
+    assert!(received.contains(r#""status":303}"#), "{}", received);
+
+    let (_, page) = get(&config, "/docs/").await;
+    assert!(page.contains("uploadWithProgress(this)"));
+}
+
+// 以浏览器的方式访问 WebDAV 地址
+async fn browse_dav(config: &Config, uri: &str) -> (StatusCode, Option<String>, String) {
+    let app = test::init_service(build_app(config, &AppState::new(config))).await;
+    let req = test::TestRequest::get()
+        .uri(uri)
+        .insert_header((header::ACCEPT, "text/html,application/xhtml+xml,*/*;q=0.8"))
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    let status = res.status();
+    let location = res.headers().get(header::LOCATION).map(|v| v.to_str().unwrap().to_string());
+    let body = test::read_body(res).await;
+    (status, location, String::from_utf8_lossy(&body).to_string())
+}
+
+#[actix_web::test]
+async fn renders_webdav_directories_with_listing_template() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/docs/a b.txt"), "a").unwrap();
+    let mut config = dir.config();
+
+    let (status, _, body) = browse_dav(&config, "/webdav/docs/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.contains("sort-bar"));
+
+    config.webdav.pretty_index = true;
+    let (status, _, body) = browse_dav(&config, "/webdav/docs/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("sort-bar"));
+    assert!(body.contains(r#"data-url="/webdav/docs/a%20b.txt""#));
+    assert!(!body.contains("/zip"));
+
+    let (status, location, _) = browse_dav(&config, "/webdav/docs").await;
+    assert_eq!(status, StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(location.as_deref(), Some("/webdav/docs/"));
+
+    // 文件下载与同步客户端的请求不受影响
+    let (status, _, body) = browse_dav(&config, "/webdav/hello.txt").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "hello");
+    assert_eq!(propfind(&config, Some("admin:admin")).await, StatusCode::MULTI_STATUS);
+}
+
+#[actix_web::test]
+async fn detects_cwd_containing_data_dir() {
+    let dir = TestDir::new();
+    assert!(dir.config().data_dir_overlap().is_none());
+    for cwd in [".", "data"] {
+        let config = Config { cwd: cwd.to_string(), ..Config::default() };
+        assert!(config.data_dir_overlap().is_some(), "{}", cwd);
+    }
+    let config = Config { cwd: "data/www".to_string(), ..Config::default() };
+    assert!(config.data_dir_overlap().is_none());
+}
+
+#[actix_web::test]
+async fn live_updates_do_not_inline_directory_names_into_script() {
+    let dir = TestDir::new();
+    let name = "x\"+alert(1)+\"";
+    fs::create_dir(dir.0.join("www").join(name)).unwrap();
+    let mut config = dir.config();
+    config.live_updates = true;
+
+    let (status, body) = get(&config, &format!("/{}/", utf8_percent_encode(name, NON_ALPHANUMERIC))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("encodeURIComponent(location.pathname.slice(1))"));
+    assert!(!body.contains("(\"x\"+alert(1)"));
+}
+
+#[actix_web::test]
+async fn readme_links_allow_only_safe_schemes() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/docs/README.md"), concat!(
+        "[a](javascript:alert(1)) [b](java&#9;script:alert(2)) [c](vbscript:msgbox(3)) ",
+        "[d](data:text/html,<script>alert(4)</script>) [e](JavaScript:alert(5))\n\n",
+        "[ok1](https://example.com/) [ok2](mailto:a@example.com) [ok3](#top) [ok4](../hello.txt) [ok5](sub/a:b.txt)\n",
+    )).unwrap();
+    let mut config = dir.config();
+    config.readme.enabled = true;
+
+    let (_, body) = get(&config, "/docs/").await;
+    for bad in ["alert(1)", "alert(2)", "msgbox", "data:", "alert(5)"] {
+        assert!(!body.contains(bad), "{}", bad);
+    }
+    assert_eq!(body.matches("<a href=\"#\">").count(), 5);
+    for good in ["https://example.com/", "mailto:a@example.com", "href=\"#top\"", "href=\"../hello.txt\"", "href=\"sub/a:b.txt\""] {
+        assert!(body.contains(good), "{}", good);
+    }
+}
+
+#[cfg(unix)]
+#[actix_web::test]
+async fn hides_symlink_targets_by_default() {
+    let dir = TestDir::new();
+    let target = dir.0.join("www/hello.txt");
+    std::os::unix::fs::symlink(&target, dir.0.join("www/docs/link.txt")).unwrap();
+    let mut config = dir.config();
+
+    let (_, body) = get(&config, "/docs/").await;
+    assert!(body.contains("link.txt"));
+    assert!(!body.contains(&*target.to_string_lossy()));
+
+    config.show_symlink_targets = true;
+    let (_, body) = get(&config, "/docs/").await;
+    assert!(body.contains(&*target.to_string_lossy()));
+}
+
+#[cfg(unix)]
+#[actix_web::test]
+async fn playlist_lines_cannot_be_injected_through_file_names() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/docs/a.mp3"), "x").unwrap();
+    fs::write(dir.0.join("www/docs/b\n#EXTINF:1,evil\nhttp:evil.mp3"), "x").unwrap();
+
+    let (status, body) = get(&dir.config(), "/playlist?path=docs").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.lines().all(|line| line.starts_with("#EXTM3U") || line.starts_with("#EXTINF:") || line.starts_with("http://localhost")), "{}", body);
+    assert!(body.contains("/docs/b%0A%23EXTINF"));
+    assert!(!body.contains("\nhttp:evil.mp3"));
+}
+
+#[actix_web::test]
+async fn truncates_huge_listings() {
+    let dir = TestDir::new();
+    for i in 0..5 {
+        fs::write(dir.0.join(format!("www/docs/file{}.txt", i)), "x").unwrap();
+    }
+    let mut config = dir.config();
+    config.lang = "zh".to_string();
+    config.max_listing_entries = 3;
+
+    let (status, body) = get(&config, "/docs/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("file0.txt") && body.contains("file2.txt"));
+    assert!(!body.contains("file3.txt"));
+    assert!(body.contains("仅显示排序后的前 3 项，共 5 项"));
+
+    // 先按分类过滤再截断，排在后面的匹配项同样显示
+    fs::write(dir.0.join("www/docs/z0.jpg"), "x").unwrap();
+    fs::write(dir.0.join("www/docs/z1.jpg"), "x").unwrap();
+    let (_, body) = get(&config, "/docs/?type=image").await;
+    assert!(body.contains("z0.jpg") && body.contains("z1.jpg"));
+    assert!(!body.contains("class=\"truncated-notice\""));
+
+    config.max_listing_entries = 0;
+    let (_, body) = get(&config, "/docs/").await;
+    assert!(body.contains("file4.txt"));
+    assert!(!body.contains("truncated-notice\">"));
+}
+
+// 带 If-Modified-Since 请求目录，返回状态码与 Last-Modified
+async fn get_since(config: &Config, uri: &str, since: Option<&str>) -> (StatusCode, Option<String>) {
+    let app = test::init_service(build_app(config, &AppState::new(config))).await;
+    let mut req = test::TestRequest::get().uri(uri);
+    if let Some(since) = since {
+        req = req.insert_header((header::IF_MODIFIED_SINCE, since));
+    }
+    let res = test::call_service(&app, req.to_request()).await;
+    let last_modified = res.headers().get(header::LAST_MODIFIED).map(|v| v.to_str().unwrap().to_string());
+    (res.status(), last_modified)
+}
+
+#[actix_web::test]
+async fn directory_listing_honors_if_modified_since() {
+    let dir = TestDir::new();
+    let config = dir.config();
+
+    let (status, last_modified) = get_since(&config, "/docs/", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let last_modified = last_modified.expect("Last-Modified");
+    assert_eq!(get_since(&config, "/docs/", None).await.1.as_deref(), Some(last_modified.as_str()));
+    assert_eq!(get_since(&config, "/docs/", Some(&last_modified)).await.0, StatusCode::NOT_MODIFIED);
+    assert_eq!(get_since(&config, "/docs/", Some("Thu, 01 Jan 1970 00:00:00 GMT")).await.0, StatusCode::OK);
+
+    // 条目的修改时间晚于目录本身时以条目为准
+    let file = fs::File::create(dir.0.join("www/docs/new.txt")).unwrap();
+    file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(3600)).unwrap();
+    let (status, changed) = get_since(&config, "/docs/", Some(&last_modified)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(changed.as_deref(), Some(last_modified.as_str()));
+}
+
+#[actix_web::test]
+async fn defers_preview_of_large_media() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/docs/big.png"), vec![0u8; 2048]).unwrap();
+    fs::write(dir.0.join("www/docs/small.png"), vec![0u8; 16]).unwrap();
+    fs::write(dir.0.join("www/docs/clip.mp4"), vec![0u8; 2048]).unwrap();
+    let mut config = dir.config();
+    config.preview.max_inline_bytes.image = 1024;
+
+    let (_, body) = get(&config, "/docs/").await;
+    assert!(body.contains("togglePreview('./big.png?thumb=1', 'big.png', true)"));
+    assert!(body.contains("togglePreview('./small.png', 'small.png', false)"));
+    assert!(body.contains("togglePreview('./clip.mp4', 'clip.mp4', false)"));
+}
+
+#[actix_web::test]
+async fn unknown_config_fields_are_ignored_unless_strict() {
+    let mut value = serde_yaml::to_value(Config::default()).unwrap();
+    value["port"] = 9090.into();
+    value["prot"] = 8080.into();
+    value["webdav"]["lockin"] = true.into();
+    value["webdav"]["root"] = serde_yaml::Value::Null;
+    let text = serde_yaml::to_string(&value).unwrap();
+
+    let config = Config::from_yaml(&text, false).unwrap();
+    assert_eq!(config.port, 9090);
+
+    let err = Config::from_yaml(&text, true).unwrap_err().to_string();
+    assert!(err.contains("prot") && err.contains("webdav.lockin"), "{}", err);
+    assert!(!err.contains("root"));
+    let default = serde_yaml::to_string(&Config::default()).unwrap();
+    assert!(Config::from_yaml(&default, true).is_ok());
+}
+
+#[actix_web::test]
+async fn builds_bracketed_ipv6_socket_addresses() {
+    for (host, expected) in [
+        ("::", "[::]:8080"),
+        ("::1", "[::1]:8080"),
+        ("2001:db8::1", "[2001:db8::1]:8080"),
+        ("[::1]", "[::1]:8080"),
+    ] {
+        let addr = ipv6_socket_addr(host, 8080).unwrap();
+        assert!(addr.is_ipv6(), "{}", host);
+        assert_eq!(addr.port(), 8080);
+        assert_eq!(addr.to_string(), expected);
+    }
+    for host in ["127.0.0.1", "[::1", "::1]", "not-an-address"] {
+        assert!(ipv6_socket_addr(host, 8080).is_err(), "{}", host);
+    }
+}
+
+#[actix_web::test]
+async fn rejects_root_mount_path() {
+    for (mount_path, ok) in [("/", false), ("//", false), ("/zip", false), ("/dav/", true)] {
+        let mut config = Config::default();
+        config.webdav.mount_path = mount_path.to_string();
+        let result = Config::from_yaml(&serde_yaml::to_string(&config).unwrap(), false);
+        assert_eq!(result.is_ok(), ok, "{}", mount_path);
+    }
+}
+
+#[actix_web::test]
+async fn rejects_user_root_outside_webdav_root() {
+    let mut config = Config::default();
+    config.webdav.users.insert("alice".to_string(), UserConfig {
+        password: "secret".to_string(),
+        permissions: "rw".to_string(),
+        root: Some("users/../../..".to_string()),
+        landing_path: None,
+    });
+    let err = Config::from_yaml(&serde_yaml::to_string(&config).unwrap(), false).unwrap_err().to_string();
+    assert!(err.contains("alice"), "{}", err);
+
+    config.webdav.users.get_mut("alice").unwrap().root = Some("/users/alice".to_string());
+    assert!(Config::from_yaml(&serde_yaml::to_string(&config).unwrap(), false).is_ok());
+}
+
+#[actix_web::test]
+async fn summarizes_directory_by_category() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/a.png"), "12345").unwrap();
+    fs::write(dir.0.join("www/docs/b.png"), "123").unwrap();
+    fs::write(dir.0.join("www/docs/c.mp4"), "1234567").unwrap();
+    let config = dir.config();
+
+    let (status, body) = get(&config, "/?stats=1").await;
+    assert_eq!(status, StatusCode::OK);
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["files"], 2);
+    assert_eq!(stats["dirs"], 1);
+    assert_eq!(stats["categories"]["image"]["count"], 1);
+    assert_eq!(stats["categories"]["image"]["total_size"], 5);
+    assert_eq!(stats["categories"]["doc"]["total_size"], 5);
+    assert!(stats["categories"].get("video").is_none());
+
+    let (_, body) = get(&config, "/?stats=1&recursive=1").await;
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["files"], 4);
+    assert_eq!(stats["categories"]["image"]["count"], 2);
+    assert_eq!(stats["categories"]["image"]["total_size"], 8);
+    assert_eq!(stats["categories"]["video"]["total_size"], 7);
+    assert_eq!(stats["truncated"], false);
+}
+
+#[actix_web::test]
+async fn sniffs_extensionless_files_when_enabled() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/run"), "#!/bin/sh\necho hi\n").unwrap();
+    fs::write(dir.0.join("www/blob"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+    let mut config = dir.config();
+
+    let (_, body) = get(&config, "/").await;
+    assert!(body.contains("id=\"icon-run\">📄"));
+    assert!(body.contains("id=\"icon-blob\">📄"));
+
+    config.sniff_extensionless = true;
+    let (_, body) = get(&config, "/").await;
+    assert!(body.contains("id=\"icon-run\">📝"));
+    assert!(body.contains("id=\"icon-blob\">🖼️"));
+    assert!(body.contains("id=\"icon-hello.txt\">📄"));
+
+    // 文件本身始终按内容识别，纯文本脚本可以直接在浏览器中查看
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri("/run").to_request()).await;
+    assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "text/plain; charset=utf-8");
+}
+
+#[actix_web::test]
+async fn binds_random_port_when_port_is_zero() {
+    let listeners = bind_listeners("127.0.0.1:0", false).unwrap();
+    let port = listeners[0].local_addr().unwrap().port();
+    assert_ne!(port, 0);
+    assert!(listeners.iter().all(|l| l.local_addr().unwrap().port() == port));
+    assert!(std::net::TcpStream::connect(("127.0.0.1", port)).is_ok());
+}
+
+// 收集日志输出，用于检查结构化日志的字段
+#[derive(Clone, Default)]
+struct LogBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[actix_web::test]
+async fn writes_structured_access_and_auth_logs() {
+    let buffer = LogBuffer::default();
+    let writer = buffer.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    config.log.access = true;
+    let (status, _) = get(&config, "/hello.txt").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(propfind(&config, Some("admin:wrong")).await, StatusCode::UNAUTHORIZED);
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let events: Vec<serde_json::Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    let access = events.iter().find(|e| e["event"] == "access" && e["path"] == "/hello.txt").expect("access log");
+    assert_eq!(access["status"], 200);
+    assert_eq!(access["method"], "GET");
+    let denied = events.iter().find(|e| e["event"] == "webdav_denied").expect("auth log");
+    assert_eq!(denied["user"], "admin");
+    assert_eq!(denied["reason"], "invalid_password");
+    assert!(!output.contains("wrong"));
+}
+
+#[actix_web::test]
+async fn redirects_webdav_root_to_landing_path() {
+    let dir = TestDir::new();
+    fs::create_dir_all(dir.0.join("www/docs/deep")).unwrap();
+    let mut config = dir.config();
+    config.webdav.pretty_index = true;
+    config.webdav.users.get_mut("admin").unwrap().landing_path = Some("/docs/deep/".to_string());
+
+    let (status, location, _) = browse_dav(&config, "/webdav/").await;
+    assert_eq!(status, StatusCode::FOUND);
+    assert_eq!(location.as_deref(), Some("/webdav/docs/deep/"));
+
+    let (status, _, body) = browse_dav(&config, "/webdav/docs/deep/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("bookmark-bar"));
+    assert!(body.contains(r#"action="./?bookmark=add""#));
+
+    // 从页面内返回根目录时不跳转
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let req = test::TestRequest::get()
+        .uri("/webdav/")
+        .insert_header((header::ACCEPT, "text/html"))
+        .insert_header((header::REFERER, "http://localhost/webdav/docs/"))
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // 越出用户根目录的默认目录被忽略
+    config.webdav.users.get_mut("admin").unwrap().landing_path = Some("../".to_string());
+    let (status, _, _) = browse_dav(&config, "/webdav/").await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 未登录时不能修改书签
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let req = test::TestRequest::post().uri("/webdav/docs/?bookmark=add").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+    // 书签目录不能含有 ..
+    let req = test::TestRequest::post()
+        .uri("/webdav/docs/%2e%2e/?bookmark=add")
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn parent_entry_follows_configured_cwd() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    let www = config.cwd.clone();
+
+    // cwd 并非 data/www，且写法带有末尾分隔符或 . 组件时，根目录仍不显示上级目录
+    for cwd in [www.clone(), format!("{}/", www), format!("{}/./", www)] {
+        config.cwd = cwd;
+        let (_, body) = get(&config, "/").await;
+        assert!(!body.contains("data-name=\"..\""), "{}", config.cwd);
+        let (_, body) = get(&config, "/docs/").await;
+        assert!(body.contains("data-name=\"..\""), "{}", config.cwd);
+    }
+
+    #[cfg(windows)]
+    {
+        config.cwd = www.replace('/', "\\");
+        let (_, body) = get(&config, "/").await;
+        assert!(!body.contains("data-name=\"..\""), "{}", config.cwd);
+        let (_, body) = get(&config, "/docs/").await;
+        assert!(body.contains("data-name=\"..\""), "{}", config.cwd);
+    }
+}
+
+#[actix_web::test]
+async fn validates_cwd_for_both_platforms() {
+    // Windows 规则：盘符、共享路径与 .\、..\ 写法
+    for (value, ok) in [
+        ("C:\\data", true), ("d:/data", true), ("\\\\nas\\share", true),
+        (".\\www", true), ("..\\x", true), ("./www", true), ("../x", true),
+        ("C:data", false), ("www", false), ("\\data", false), ("1:\\data", false),
+    ] {
+        assert_eq!(is_valid_cwd(value, true), ok, "windows {}", value);
+    }
+    // 其他平台上反斜杠只是普通字符
+    for (value, ok) in [
+        ("/srv/www", true), ("./www", true), ("../x", true),
+        ("C:\\data", false), (".\\www", false), ("..\\x", false), ("www", false),
+    ] {
+        assert_eq!(is_valid_cwd(value, false), ok, "unix {}", value);
+    }
+
+    assert_eq!(normalize_separators("C:\\data\\www", true), "C:/data/www");
+    assert_eq!(normalize_separators(".\\www", true), "./www");
+    assert_eq!(normalize_separators("..\\x", true), "../x");
+    assert_eq!(normalize_separators("./a\\b", false), "./a\\b");
+}
+
+#[cfg(unix)]
+#[actix_web::test]
+async fn parent_entry_compares_canonical_paths() {
+    let dir = TestDir::new();
+    std::os::unix::fs::symlink(dir.0.join("www"), dir.0.join("link")).unwrap();
+    let mut config = dir.config();
+
+    // 通过软链接或 .. 组件指向同一目录的 cwd 也按根目录处理
+    for cwd in [dir.0.join("link"), dir.0.join("www/docs/..")] {
+        config.cwd = cwd.to_string_lossy().to_string();
+        let (_, body) = get(&config, "/").await;
+        assert!(!body.contains("data-name=\"..\""), "{}", config.cwd);
+        let (_, body) = get(&config, "/docs/").await;
+        assert!(body.contains("data-name=\"..\""), "{}", config.cwd);
+    }
+}
+
+#[actix_web::test]
+async fn sorting_keeps_parent_first_and_breaks_ties_by_name() {
+    let dir = TestDir::new();
+    for name in ["b.txt", "a.txt", "c.txt"] {
+        fs::write(dir.0.join("www/docs").join(name), "same").unwrap();
+    }
+    fs::write(dir.0.join("www/docs/big.txt"), "x".repeat(4096)).unwrap();
+    let config = dir.config();
+
+    let (_, body) = get(&config, "/docs/?sort=size&order=desc").await;
+    let position = |name: &str| body.find(&format!("data-name=\"{}\"", name)).unwrap();
+    assert!(position("..") < position("big.txt"));
+    assert!(position("big.txt") < position("a.txt"));
+    assert!(position("a.txt") < position("b.txt") && position("b.txt") < position("c.txt"));
+
+    let (_, body) = get(&config, "/docs/?sort=size&order=asc").await;
+    let position = |name: &str| body.find(&format!("data-name=\"{}\"", name)).unwrap();
+    assert!(position("..") < position("a.txt"));
+    assert!(position("a.txt") < position("b.txt") && position("c.txt") < position("big.txt"));
+}
+
+#[actix_web::test]
+async fn searches_directory_by_name() {
+    let dir = TestDir::new();
+    for name in ["Report-2024.pdf", "report.jpg", "photo.jpg"] {
+        fs::write(dir.0.join("www/docs").join(name), "x").unwrap();
+    }
+    let config = dir.config();
+
+    let (status, body) = get(&config, "/docs/?q=REPORT").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-name=\"Report-2024.pdf\""));
+    assert!(body.contains("data-name=\"report.jpg\""));
+    assert!(!body.contains("data-name=\"photo.jpg\""));
+    assert!(body.contains("data-name=\"..\""));
+    assert!(body.contains("“REPORT”"));
+    assert!(body.contains("&q=REPORT"));
+
+    let (_, body) = get(&config, "/docs/?q=report&type=image").await;
+    assert!(body.contains("data-name=\"report.jpg\""));
+    assert!(!body.contains("data-name=\"Report-2024.pdf\""));
+    assert!(!body.contains("data-name=\"photo.jpg\""));
+
+    let (_, body) = get(&config, "/docs/?q=%3Cb%3E").await;
+    assert!(body.contains("&lt;b&gt;"));
+    assert!(!body.contains("data-name=\"photo.jpg\""));
+}
+
+#[actix_web::test]
+async fn searches_subdirectories_recursively() {
+    let dir = TestDir::new();
+    let www = dir.0.join("www");
+    fs::create_dir_all(www.join("docs/2024/q1")).unwrap();
+    fs::write(www.join("docs/2024/q1/report.txt"), "x").unwrap();
+    fs::write(www.join("docs/report-summary.txt"), "x").unwrap();
+    fs::create_dir(dir.0.join("outside")).unwrap();
+    fs::write(dir.0.join("outside/report-secret.txt"), "x").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(dir.0.join("outside"), www.join("docs/escape")).unwrap();
+    let config = dir.config();
+
+    let (_, body) = get(&config, "/docs/?q=report").await;
+    assert!(body.contains("data-name=\"report-summary.txt\""));
+    assert!(!body.contains("2024/q1/report.txt"));
+
+    let (status, body) = get(&config, "/docs/?q=report&recursive=true").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-name=\"2024/q1/report.txt\""));
+    assert!(body.contains("href=\"./2024/q1/report.txt\""));
+    assert!(body.contains("data-name=\"report-summary.txt\""));
+    assert!(!body.contains("report-secret.txt"));
+    assert!(body.contains("&recursive=true"));
+
+    let (status, _) = get(&config, "/docs/2024/q1/report.txt").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn caps_recursive_search_results() {
+    let dir = TestDir::new();
+    let many = dir.0.join("www/docs/many");
+    fs::create_dir(&many).unwrap();
+    for i in 0..510 {
+        fs::write(many.join(format!("item-{}.txt", i)), "").unwrap();
+    }
+    let config = dir.config();
+
+    let (_, body) = get(&config, "/docs/?q=item&recursive=1").await;
+    assert_eq!(body.matches("data-name=\"many/item-").count(), 500);
+    assert!(body.contains("class=\"truncated-notice\""));
+}
+
+#[actix_web::test]
+async fn lists_directory_as_json() {
+    let dir = TestDir::new();
+    fs::write(dir.0.join("www/docs/a b.txt"), "12345").unwrap();
+    fs::create_dir(dir.0.join("www/docs/sub")).unwrap();
+    let config = dir.config();
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+
+    let req = test::TestRequest::get().uri("/api/list/docs").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/json; charset=utf-8");
+    let body: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(body["path"], "/docs");
+    let entries = body["entries"].as_array().unwrap();
+    assert!(entries.iter().all(|e| e["name"] != ".."));
+    let file = entries.iter().find(|e| e["name"] == "a b.txt").unwrap();
+    assert_eq!(file["size"], 5);
+    assert_eq!(file["is_dir"], false);
+    assert_eq!(file["url"], "/docs/a%20b.txt");
+    assert!(entries.iter().any(|e| e["name"] == "sub" && e["is_dir"] == true));
+
+    let req = test::TestRequest::get().uri("/api/list/").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["entries"].as_array().unwrap().iter().any(|e| e["name"] == "hello.txt"));
+
+    for uri in ["/api/list/missing", "/api/list/hello.txt", "/api/list/%2E%2E/"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn formats_modified_times_in_configured_timezone() {
+    let dir = TestDir::new();
+    let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_704_067_200); // 2024-01-01T00:00:00Z
+    fs::File::options().write(true).open(dir.0.join("www/hello.txt")).unwrap().set_modified(modified).unwrap();
+    let mut config = dir.config();
+
+    for (tz, iso, shown) in [
+        ("Asia/Shanghai", "2024-01-01T08:00:00+08:00", "2024-01-01 08:00:00"),
+        ("America/New_York", "2023-12-31T19:00:00-05:00", "2023-12-31 19:00:00"),
+    ] {
+        config.timezone = Some(tz.to_string());
+        let config = Config::from_yaml(&serde_yaml::to_string(&config).unwrap(), false).unwrap();
+        let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+        let req = test::TestRequest::get().uri("/api/list/").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let file = body["entries"].as_array().unwrap().iter().find(|e| e["name"] == "hello.txt").unwrap().clone();
+        assert_eq!(file["modified_iso"], iso, "{}", tz);
+        let (_, body) = get(&config, "/").await;
+        assert!(body.contains(shown), "{}", tz);
+    }
+
+    config.timezone = Some("Mars/Olympus".to_string());
+    assert!(Config::from_yaml(&serde_yaml::to_string(&config).unwrap(), false).is_err());
+}
+
+#[actix_web::test]
+async fn renders_breadcrumbs_for_each_path_segment() {
+    let dir = TestDir::new();
+    fs::create_dir_all(dir.0.join("www/docs/my photos/2024")).unwrap();
+    let mut config = dir.config();
+
+    let (_, body) = get(&config, "/docs/my%20photos/2024/").await;
+    assert!(body.contains("<a href=\"/\">根目录</a>"));
+    assert!(body.contains("<a href=\"/docs/\">docs</a>"));
+    assert!(body.contains("<a href=\"/docs/my%20photos/\">my photos</a>"));
+    assert!(body.contains("<span class=\"crumb-current\">2024</span>"));
+
+    // 关闭路径规范化时，多余或缺少的 / 得到相同的导航
+    config.normalize_paths = false;
+    let (_, odd) = get(&config, "/docs//my%20photos/2024").await;
+    let nav = |body: &str| body[body.find("<span class=\"breadcrumbs\">").unwrap()..].split("</h1>").next().unwrap().to_string();
+    assert_eq!(nav(&odd), nav(&body));
+
+    let (_, body) = get(&config, "/").await;
+    assert!(body.contains("<span class=\"breadcrumbs\"><span class=\"crumb-current\">根目录</span></span>"));
+}
+
+// 构造 multipart/form-data 请求体，字段为 (名称, 文件名, 内容)
+fn multipart_body(fields: &[(&str, Option<&str>, &str)]) -> (String, Vec<u8>) {
+    let boundary = "webdisk-test-boundary";
+    let mut body = Vec::new();
+    for (name, filename, content) in fields {
+        body.extend_from_slice(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"", boundary, name).as_bytes());
+        if let Some(filename) = filename {
+            body.extend_from_slice(format!("; filename=\"{}\"\r\nContent-Type: application/octet-stream", filename).as_bytes());
+        }
+        body.extend_from_slice(format!("\r\n\r\n{}\r\n", content).as_bytes());
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    (format!("multipart/form-data; boundary={}", boundary), body)
+}
+
+#[actix_web::test]
+async fn uploads_files_from_web_form() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    let upload = |uri: &str, auth: Option<&str>, fields: &[(&str, Option<&str>, &str)]| {
+        let (content_type, body) = multipart_body(fields);
+        let mut req = test::TestRequest::post().uri(uri).insert_header((header::CONTENT_TYPE, content_type));
+        if let Some(credentials) = auth {
+            req = req.insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode(credentials))));
+        }
+        req.set_payload(body).to_request()
+    };
+
+    // 默认不开启
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let resp = test::call_service(&app, upload("/upload/docs", Some("admin:admin"), &[("file", Some("a.txt"), "one")])).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    config.upload.web_form = true;
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let resp = test::call_service(&app, upload("/upload/docs", None, &[("file", Some("a.txt"), "one")])).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = test::call_service(&app, upload("/upload/docs", Some("admin:admin"), &[("file", Some("a.txt"), "one"), ("file", Some("../b.txt"), "two")])).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(resp.headers().get(header::LOCATION).unwrap(), "/docs/");
+    assert_eq!(fs::read_to_string(dir.0.join("www/docs/a.txt")).unwrap(), "one");
+    assert_eq!(fs::read_to_string(dir.0.join("www/docs/b.txt")).unwrap(), "two");
+
+    let resp = test::call_service(&app, upload("/upload/docs", Some("admin:admin"), &[("file", Some("a.txt"), "new")])).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(fs::read_to_string(dir.0.join("www/docs/a.txt")).unwrap(), "one");
+
+    let resp = test::call_service(&app, upload("/upload/docs", Some("admin:admin"), &[("overwrite", None, "true"), ("file", Some("a.txt"), "new")])).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(fs::read_to_string(dir.0.join("www/docs/a.txt")).unwrap(), "new");
+
+    let resp = test::call_service(&app, upload("/upload/%2E%2E/", Some("admin:admin"), &[("file", Some("x.txt"), "x")])).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert!(!dir.0.join("x.txt").exists());
+
+    // 只有 w 权限的用户不能覆盖看不到的已有文件
+    config.webdav.users.insert("drop".to_string(), UserConfig {
+        password: "drop".to_string(),
+        permissions: "w".to_string(),
+        root: None,
+        landing_path: None,
+    });
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let resp = test::call_service(&app, upload("/upload/docs", Some("drop:drop"), &[("overwrite", None, "true"), ("file", Some("a.txt"), "dropped")])).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(fs::read_to_string(dir.0.join("www/docs/a.txt")).unwrap(), "new");
+    let resp = test::call_service(&app, upload("/upload/docs?overwrite=true", Some("drop:drop"), &[("file", Some("c.txt"), "c")])).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+    // WebDAV 只读模式下网页上传同样被拒绝
+    let mut read_only = config.clone();
+    read_only.webdav.read_only = true;
+    let app = test::init_service(build_app(&read_only, &AppState::new(&read_only))).await;
+    let resp = test::call_service(&app, upload("/upload/docs", Some("admin:admin"), &[("file", Some("d.txt"), "d")])).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert!(!dir.0.join("www/docs/d.txt").exists());
+
+    let (_, body) = get(&config, "/docs/").await;
+    assert!(body.contains("action=\"/upload/docs\""));
+    assert!(fs::read_dir(dir.0.join("www/docs")).unwrap().all(|e| !e.unwrap().file_name().to_string_lossy().contains(".upload-")));
+}
+
+#[actix_web::test]
+async fn deletes_files_and_directories_from_web() {
+    let dir = TestDir::new();
+    let www = dir.0.join("www");
+    fs::write(www.join("docs/a.txt"), "a").unwrap();
+    fs::create_dir_all(www.join("docs/full/inner")).unwrap();
+    fs::write(www.join("docs/full/inner/b.txt"), "b").unwrap();
+    let mut config = dir.config();
+    let delete = |uri: &str| test::TestRequest::post()
+        .uri(uri)
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .to_request();
+
+    // 默认不开启，页面上也没有删除按钮
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    assert_eq!(test::call_service(&app, delete("/delete/docs/a.txt")).await.status(), StatusCode::NOT_FOUND);
+    let (_, body) = get(&config, "/docs/").await;
+    assert!(!body.contains("action=\"/delete/docs/a.txt\""));
+
+    config.allow_web_delete = true;
+    let (_, body) = get(&config, "/docs/").await;
+    assert!(body.contains("action=\"/delete/docs/a.txt\""));
+    assert!(body.contains("action=\"/delete/docs/full?recursive=true\""));
+
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let req = test::TestRequest::post().uri("/delete/docs/a.txt").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = test::call_service(&app, delete("/delete/docs/a.txt")).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(resp.headers().get(header::LOCATION).unwrap(), "/docs/");
+    assert!(!www.join("docs/a.txt").exists());
+
+    // 非空目录需要确认递归删除
+    assert_eq!(test::call_service(&app, delete("/delete/docs/full")).await.status(), StatusCode::CONFLICT);
+    assert!(www.join("docs/full/inner/b.txt").exists());
+    assert_eq!(test::call_service(&app, delete("/delete/docs/full?recursive=true")).await.status(), StatusCode::SEE_OTHER);
+    assert!(!www.join("docs/full").exists());
+
+    // 不能删除根目录或越出 cwd
+    for uri in ["/delete/", "/delete/?recursive=true"] {
+        assert_eq!(test::call_service(&app, delete(uri)).await.status(), StatusCode::FORBIDDEN, "{}", uri);
+    }
+    assert_eq!(test::call_service(&app, delete("/delete/%2E%2E/outside.txt")).await.status(), StatusCode::NOT_FOUND);
+    assert!(www.exists() && dir.0.join("outside.txt").exists());
+}
+
+#[actix_web::test]
+async fn web_delete_requires_read_permission_and_writable_webdav() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    config.allow_web_delete = true;
+    config.webdav.users.insert("drop".to_string(), UserConfig {
+        password: "drop".to_string(),
+        permissions: "w".to_string(),
+        root: None,
+        landing_path: None,
+    });
+    let delete = |credentials: &str| test::TestRequest::post()
+        .uri("/delete/hello.txt")
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode(credentials))))
+        .to_request();
+
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    assert_eq!(test::call_service(&app, delete("drop:drop")).await.status(), StatusCode::FORBIDDEN);
+
+    config.webdav.read_only = true;
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    assert_eq!(test::call_service(&app, delete("admin:admin")).await.status(), StatusCode::FORBIDDEN);
+    assert!(dir.0.join("www/hello.txt").exists());
+}
+
+#[actix_web::test]
+async fn creates_directories_from_web() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    let mkdir = |uri: &str, name: &str| test::TestRequest::post()
+        .uri(uri)
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .set_form([("name", name)])
+        .to_request();
+
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    assert_eq!(test::call_service(&app, mkdir("/mkdir/docs", "new")).await.status(), StatusCode::NOT_FOUND);
+
+    config.allow_web_mkdir = true;
+    let (_, body) = get(&config, "/docs/").await;
+    assert!(body.contains("action=\"/mkdir/docs\""));
+
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let resp = test::call_service(&app, mkdir("/mkdir/docs", "新 建")).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(resp.headers().get(header::LOCATION).unwrap(), "/docs/");
+    assert!(dir.0.join("www/docs/新 建").is_dir());
+
+    assert_eq!(test::call_service(&app, mkdir("/mkdir/docs", "新 建")).await.status(), StatusCode::CONFLICT);
+    for name in ["", "..", "a/b", "a\\b"] {
+        assert_eq!(test::call_service(&app, mkdir("/mkdir/docs", name)).await.status(), StatusCode::BAD_REQUEST, "{}", name);
+    }
+    assert!(!dir.0.join("www/docs/a").exists());
+    assert_eq!(test::call_service(&app, mkdir("/mkdir/%2E%2E", "escape")).await.status(), StatusCode::NOT_FOUND);
+    assert!(!dir.0.join("escape").exists());
+
+    let req = test::TestRequest::post().uri("/mkdir/docs").set_form([("name", "anon")]).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+    config.webdav.read_only = true;
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    assert_eq!(test::call_service(&app, mkdir("/mkdir/docs", "locked")).await.status(), StatusCode::FORBIDDEN);
+    assert!(!dir.0.join("www/docs/locked").exists());
+}
+
+#[actix_web::test]
+async fn renames_and_moves_entries_from_web() {
+    let dir = TestDir::new();
+    let www = dir.0.join("www");
+    fs::write(www.join("docs/a.txt"), "a").unwrap();
+    fs::write(www.join("docs/taken.txt"), "t").unwrap();
+    fs::create_dir(www.join("docs/sub")).unwrap();
+    let mut config = dir.config();
+    let rename = |from: &str, to: &str| test::TestRequest::post()
+        .uri("/rename")
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .set_form([("from", from), ("to", to)])
+        .to_request();
+
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    assert_eq!(test::call_service(&app, rename("docs/a.txt", "docs/b.txt")).await.status(), StatusCode::NOT_FOUND);
+
+    config.allow_web_rename = true;
+    let (_, body) = get(&config, "/docs/").await;
+    assert!(body.contains("class=\"rename-btn\""));
+
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let resp = test::call_service(&app, rename("docs/a.txt", "docs/100% b.txt")).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(resp.headers().get(header::LOCATION).unwrap(), "/docs/");
+    assert_eq!(fs::read_to_string(www.join("docs/100% b.txt")).unwrap(), "a");
+
+    // 目标已存在时不覆盖
+    let resp = test::call_service(&app, rename("docs/100% b.txt", "docs/taken.txt")).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(fs::read_to_string(www.join("docs/taken.txt")).unwrap(), "t");
+
+    let resp = test::call_service(&app, rename("docs/100% b.txt", "docs/sub/moved.txt")).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(resp.headers().get(header::LOCATION).unwrap(), "/docs/sub/");
+    assert!(www.join("docs/sub/moved.txt").exists());
+
+    // 目录不能移入自身，系统返回的错误会显示在页面上
+    let resp = test::call_service(&app, rename("docs", "docs/sub/docs")).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert!(www.join("docs/sub").is_dir());
+
+    assert_eq!(test::call_service(&app, rename("docs/taken.txt", "../taken.txt")).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(test::call_service(&app, rename("../outside.txt", "docs/x.txt")).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(test::call_service(&app, rename("", "moved")).await.status(), StatusCode::NOT_FOUND);
+    assert!(dir.0.join("outside.txt").exists() && !dir.0.join("taken.txt").exists());
+}
+
+#[actix_web::test]
+async fn web_rename_requires_read_permission_and_writable_webdav() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    config.allow_web_rename = true;
+    config.webdav.users.insert("drop".to_string(), UserConfig {
+        password: "drop".to_string(),
+        permissions: "w".to_string(),
+        root: None,
+        landing_path: None,
+    });
+    let rename = |credentials: &str| test::TestRequest::post()
+        .uri("/rename")
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode(credentials))))
+        .set_form([("from", "hello.txt"), ("to", "docs/hello.txt")])
+        .to_request();
+
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    assert_eq!(test::call_service(&app, rename("drop:drop")).await.status(), StatusCode::FORBIDDEN);
+
+    config.webdav.read_only = true;
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    assert_eq!(test::call_service(&app, rename("admin:admin")).await.status(), StatusCode::FORBIDDEN);
+    assert!(dir.0.join("www/hello.txt").exists() && !dir.0.join("www/docs/hello.txt").exists());
+}
+
+#[actix_web::test]
+async fn web_rename_cannot_move_into_data_files() {
+    let mut data = DataDir::new().await;
+    for name in ["cache", "webdisk-test-cache"] {
+        data.track(name);
+        let _ = fs::create_dir(data.path.join(name));
+    }
+    data.create("webdisk-test-source.txt", "evil");
+    for name in ["favicon.ico", "cache/x", "webdisk-test-cache/x"] {
+        data.track(name);
+    }
+    let outside = env::current_dir().unwrap().join("webdisk-test-rename.txt");
+    fs::write(&outside, "evil").unwrap();
+    data.created.push(outside.clone());
+    let rename = |from: &str, to: &str| test::TestRequest::post()
+        .uri("/rename")
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("admin:admin"))))
+        .set_form([("from", from), ("to", to)])
+        .to_request();
+
+    // 相对 cwd 包含 data/ 时，移入其中任何位置都被拒绝
+    let mut config = Config { cwd: "./".to_string(), allow_web_rename: true, ..Config::default() };
+    config.webdav.enabled = true;
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    for to in ["data/favicon.ico", "./data/favicon.ico", "data/webdisk-test-cache/x"] {
+        let status = test::call_service(&app, rename("webdisk-test-rename.txt", to)).await.status();
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", to);
+    }
+    assert!(outside.exists() && !data.path.join("favicon.ico").exists());
+
+    // cwd 就是数据目录时，受保护的子目录同样不能作为目标
+    config.cwd = "./data".to_string();
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let status = test::call_service(&app, rename("webdisk-test-source.txt", "cache/x")).await.status();
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(!data.path.join("cache/x").exists());
+    let status = test::call_service(&app, rename("webdisk-test-source.txt", "webdisk-test-cache/x")).await.status();
+    assert_eq!(status, StatusCode::SEE_OTHER);
+}
+
+#[actix_web::test]
+async fn requires_login_for_web_pages_when_enabled() {
+    let dir = TestDir::new();
+    let mut config = dir.config();
+    config.webdav.users.insert("uploader".to_string(), UserConfig {
+        password: "up".to_string(),
+        permissions: "w".to_string(),
+        root: None,
+        landing_path: None,
+    });
+    let (status, _) = get(&config, "/hello.txt").await;
+    assert_eq!(status, StatusCode::OK);
+
+    config.web_auth.enabled = true;
+    config.cache.max_age_secs = 60;
+    let app = test::init_service(build_app(&config, &AppState::new(&config))).await;
+    let request = |uri: &str, auth: Option<&str>| {
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(credentials) = auth {
+            req = req.insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode(credentials))));
+        }
+        req.to_request()
+    };
+
+    for uri in ["/", "/hello.txt", "/docs/", "/api/list/", "/checksum?path=hello.txt"] {
+        let resp = test::call_service(&app, request(uri, None)).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+        assert!(resp.headers().contains_key(header::WWW_AUTHENTICATE));
+    }
+    assert_eq!(test::call_service(&app, request("/hello.txt", Some("admin:wrong"))).await.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(test::call_service(&app, request("/hello.txt", Some("uploader:up"))).await.status(), StatusCode::FORBIDDEN);
+    // 打包下载同样是读取操作
+    let zip = test::TestRequest::post()
+        .uri("/zip")
+        .insert_header((header::AUTHORIZATION, format!("Basic {}", BASE64.encode("uploader:up"))))
+        .set_form([("path", ""), ("names", "hello.txt")])
+        .to_request();
+    assert_eq!(test::call_service(&app, zip).await.status(), StatusCode::FORBIDDEN);
+
+    let resp = test::call_service(&app, request("/hello.txt", Some("admin:admin"))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::CACHE_CONTROL).unwrap(), "private, max-age=60");
+    assert_eq!(test::read_body(resp).await, "hello");
+
+    // 健康检查与 WebDAV 不受影响，WebDAV 仍按自己的规则认证
+    assert_eq!(test::call_service(&app, request("/healthz", None)).await.status(), StatusCode::OK);
+    assert_eq!(propfind(&config, Some("admin:admin")).await, StatusCode::MULTI_STATUS);
+}